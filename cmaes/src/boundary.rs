@@ -0,0 +1,260 @@
+//! Handling of sampled points outside of box [`Bounds`].
+
+use nalgebra::DVector;
+
+use std::collections::VecDeque;
+
+use crate::mode::Mode;
+use crate::sampling::{Bounds, Candidate, EvaluatedPoint};
+use crate::state::State;
+
+/// How sampled points outside of the [`Bounds`] set with
+/// [`CMAESOptions::bounds`][crate::CMAESOptions::bounds] are handled. The default value is
+/// `Resample`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BoundaryHandling {
+    /// Resamples points that violate the constraints up to
+    /// [`max_resamples`][crate::CMAESOptions::max_resamples] times, after which they are used
+    /// anyway. The only mode that supports arbitrary [`Constraints`][crate::Constraints].
+    #[default]
+    Resample,
+    /// Mirrors coordinates outside of the bounds back into them (repeatedly if they are further
+    /// out than the width of the bounds). The repaired points are evaluated and used to update the
+    /// distribution.
+    Reflect,
+    /// Moves coordinates outside of the bounds to the nearest bound. The repaired points are
+    /// evaluated and used to update the distribution.
+    Project,
+    /// Evaluates the projected points and adds a quadratic penalty on the distance from the
+    /// original points, with a weight for each coordinate that is adapted to the function values
+    /// and increased while the mean is out of bounds. The distribution is updated with the
+    /// original points, so the penalty pulls it back inside the bounds.
+    ///
+    /// Based on Hansen, Niederberger, Guzzella and Koumoutsakos, "A Method for Handling
+    /// Uncertainty in Evolutionary Optimization With an Application to Feedback Control of
+    /// Combustion", 2009.
+    Penalty,
+}
+
+/// Repairs points that violate box bounds according to a [`BoundaryHandling`] other than
+/// `Resample`
+#[derive(Clone, Debug)]
+pub struct BoundaryRepair {
+    mode: BoundaryHandling,
+    bounds: Bounds,
+    /// Penalty weights and their adaptation state, used by `BoundaryHandling::Penalty`
+    penalty: PenaltyWeights,
+}
+
+impl BoundaryRepair {
+    /// Returns a `BoundaryRepair` for `mode`, or `None` if `mode` is `Resample`
+    pub fn new(mode: BoundaryHandling, bounds: Bounds, lambda: usize, mu_eff: f64) -> Option<Self> {
+        if mode == BoundaryHandling::Resample {
+            return None;
+        }
+
+        let dim = bounds.lower.len();
+        Some(Self {
+            mode,
+            bounds,
+            penalty: PenaltyWeights::new(dim, lambda, mu_eff),
+        })
+    }
+
+    /// Returns the point and the unscaled step used in the update for the unscaled step `y`
+    /// sampled from the distribution
+    pub fn candidate(&self, y: DVector<f64>, state: &State) -> Candidate {
+        let point = state.to_point(&y);
+
+        match self.mode {
+            BoundaryHandling::Reflect | BoundaryHandling::Project => {
+                let repaired = self.repair(&point, self.mode);
+                let unscaled_step = state.to_step(&repaired);
+                Candidate::repaired(repaired, unscaled_step, y)
+            }
+            _ => Candidate::new(self.repair(&point, BoundaryHandling::Project), y),
+        }
+    }
+
+    /// Returns `point` with every coordinate moved into the bounds by `mode`
+    fn repair(&self, point: &DVector<f64>, mode: BoundaryHandling) -> DVector<f64> {
+        let Bounds { lower, upper } = &self.bounds;
+
+        DVector::from_iterator(
+            point.len(),
+            point.iter().enumerate().map(|(i, &x)| match mode {
+                BoundaryHandling::Reflect => reflect(x, lower[i], upper[i]),
+                _ => x.clamp(lower[i], upper[i]),
+            }),
+        )
+    }
+
+    /// Adds the boundary penalty to the function values of `points` if the mode is `Penalty`.
+    /// Assumes that `points` are from the current generation of `state`
+    pub fn penalize(&mut self, points: &mut [EvaluatedPoint], state: &State, mode: Mode) {
+        if self.mode != BoundaryHandling::Penalty {
+            return;
+        }
+
+        let dim = state.mean().len() as f64;
+        let variances = state
+            .cov()
+            .diagonal()
+            .unwrap_or_else(|| DVector::from_fn(state.mean().len(), |i, _| state.cov().variance(i)));
+
+        let values = points.iter().map(|p| p.value()).collect::<Vec<_>>();
+        let mean_offset = state.mean() - self.repair(state.mean(), BoundaryHandling::Project);
+        self.penalty.update(&values, &mean_offset, &variances, state.sigma());
+
+        // Coordinates with larger variances are penalized less
+        let mean_log_variance = variances.iter().map(|v| v.ln()).sum::<f64>() / dim;
+        let scales = variances.map(|v| (0.9 * (v.ln() - mean_log_variance)).exp());
+
+        for point in points {
+            // The evaluated point may be rounded in integer coordinates, so the projection is
+            // computed again
+            let original = state.to_point(point.unscaled_step());
+            let offset = self.repair(&original, BoundaryHandling::Project) - original;
+            let penalty = offset
+                .iter()
+                .zip(self.penalty.gamma.iter().zip(scales.iter()))
+                .map(|(d, (gamma, scale))| gamma * d.powi(2) / scale)
+                .sum::<f64>()
+                / dim;
+
+            let value = match mode {
+                Mode::Minimize => point.value() + penalty,
+                Mode::Maximize => point.value() - penalty,
+            };
+            point.set_value(value);
+        }
+    }
+}
+
+/// Adaptive weights of the boundary penalty
+#[derive(Clone, Debug)]
+struct PenaltyWeights {
+    /// Weight of each coordinate (`gamma`)
+    gamma: DVector<f64>,
+    /// Interquartile ranges of the function values of recent generations (values at the front are
+    /// from more recent generations)
+    iqr_history: VecDeque<f64>,
+    /// Maximum length of `iqr_history`
+    max_history: usize,
+    mu_eff: f64,
+    /// Whether the weights have been set from the interquartile ranges
+    initialized: bool,
+}
+
+impl PenaltyWeights {
+    fn new(dim: usize, lambda: usize, mu_eff: f64) -> Self {
+        Self {
+            gamma: DVector::zeros(dim),
+            iqr_history: VecDeque::new(),
+            max_history: 20 + (3 * dim) / lambda,
+            mu_eff,
+            initialized: false,
+        }
+    }
+
+    /// Updates the weights with the function values of the current generation. `mean_offset` is
+    /// the difference between the mean and the projected mean
+    fn update(&mut self, values: &[f64], mean_offset: &DVector<f64>, variances: &DVector<f64>, sigma: f64) {
+        let dim = mean_offset.len() as f64;
+
+        self.iqr_history.push_front(interquartile_range(values));
+        self.iqr_history.truncate(self.max_history);
+
+        let mean_out_of_bounds = mean_offset.iter().any(|d| *d != 0.0);
+
+        // Set the weights once the mean leaves the bounds or in the second generation
+        if !self.initialized && (mean_out_of_bounds || self.iqr_history.len() >= 2) {
+            let delta_fit = median(self.iqr_history.iter().cloned().collect());
+            let mean_variance = variances.sum() / dim;
+            self.gamma.fill(2.0 * delta_fit / (sigma.powi(2) * mean_variance));
+            self.initialized = delta_fit > 0.0;
+        }
+
+        // Increase the weights of coordinates where the mean is far out of bounds
+        let tolerance = 3.0 * (dim.sqrt() / self.mu_eff).max(1.0) * sigma;
+        let factor = 1.1f64.powf((self.mu_eff / (10.0 * dim)).max(1.0));
+        for (i, gamma) in self.gamma.iter_mut().enumerate() {
+            if mean_offset[i].abs() > tolerance * variances[i].sqrt() {
+                *gamma *= factor;
+            }
+        }
+    }
+}
+
+/// Mirrors `x` into `[lower, upper]`
+fn reflect(x: f64, lower: f64, upper: f64) -> f64 {
+    if (lower..=upper).contains(&x) {
+        return x;
+    }
+
+    let width = upper - lower;
+    if width == 0.0 {
+        lower
+    } else if !width.is_finite() {
+        // Only one bound is finite
+        if x < lower {
+            2.0 * lower - x
+        } else {
+            2.0 * upper - x
+        }
+    } else {
+        let t = (x - lower).rem_euclid(2.0 * width);
+        if t <= width {
+            lower + t
+        } else {
+            lower + 2.0 * width - t
+        }
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    if n.is_multiple_of(2) {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    } else {
+        values[n / 2]
+    }
+}
+
+/// Returns the interquartile range of `values` with linear interpolation between ranks
+fn interquartile_range(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let quantile = |q: f64| {
+        let rank = q * (sorted.len() - 1) as f64;
+        let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+        sorted[low] + (rank - low as f64) * (sorted[high] - sorted[low])
+    };
+
+    quantile(0.75) - quantile(0.25)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reflect() {
+        assert_eq!(0.5, reflect(0.5, 0.0, 1.0));
+        assert_eq!(0.75, reflect(1.25, 0.0, 1.0));
+        assert_eq!(0.25, reflect(-0.25, 0.0, 1.0));
+        // Reflected again at the other bound
+        assert_eq!(0.5, reflect(2.5, 0.0, 1.0));
+        assert_eq!(0.5, reflect(-1.5, 0.0, 1.0));
+        assert_eq!(2.0, reflect(-2.0, 0.0, f64::INFINITY));
+        assert_eq!(1.0, reflect(3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_interquartile_range() {
+        assert_eq!(2.0, interquartile_range(&[4.0, 1.0, 3.0, 2.0, 5.0]));
+        assert_eq!(0.0, interquartile_range(&[1.0]));
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::VecDeque;
 
 use crate::mode::Mode;
 use crate::sampling::EvaluatedPoint;
+use crate::snapshot::HistorySnapshot;
 use crate::Individual;
 
 /// The maximum number of elements to store in the objective function value histories.
@@ -39,6 +40,30 @@ impl History {
         }
     }
 
+    /// Restores the histories from a snapshot
+    pub fn from_snapshot(snapshot: HistorySnapshot) -> Self {
+        Self {
+            best_function_values: snapshot.best_function_values,
+            median_function_values: snapshot.median_function_values,
+            current_best_individual: snapshot.current_best_individual,
+            overall_best_individual: snapshot.overall_best_individual,
+            first_median_function_value: snapshot.first_median_function_value,
+            best_median_function_value: snapshot.best_median_function_value,
+        }
+    }
+
+    /// Returns a snapshot of the histories
+    pub fn snapshot(&self) -> HistorySnapshot {
+        HistorySnapshot {
+            best_function_values: self.best_function_values.clone(),
+            median_function_values: self.median_function_values.clone(),
+            current_best_individual: self.current_best_individual.clone(),
+            overall_best_individual: self.overall_best_individual.clone(),
+            first_median_function_value: self.first_median_function_value,
+            best_median_function_value: self.best_median_function_value,
+        }
+    }
+
     pub fn best_function_values(&self) -> &VecDeque<f64> {
         &self.best_function_values
     }
@@ -0,0 +1,268 @@
+//! Integer-valued coordinates (CMA-ES with margin). See [`IntegerCoordinates`] for full
+//! documentation.
+
+use nalgebra::DVector;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ContinuousCDF, Normal};
+
+use crate::matrix::Covariance;
+use crate::sampling::Bounds;
+
+/// Coordinates of the search space that only take integer values, set with
+/// [`CMAESOptions::integer_coordinates`][crate::CMAESOptions::integer_coordinates].
+///
+/// The distribution stays continuous, and the integer coordinates of each point are rounded to the
+/// nearest integer before the point is evaluated, so the objective function and the returned
+/// [`Individual`][crate::Individual]s only see integers. Within the bounds set with
+/// [`CMAESOptions::bounds`][crate::CMAESOptions::bounds], the rounded values are also clamped to the
+/// integers inside the bounds.
+///
+/// Rounding makes the function values flat within each integer, so once the step size becomes
+/// smaller than the distance to the next integer, every point rounds to the same value and the
+/// coordinate can no longer change. To prevent this, the margin keeps the probability of sampling
+/// an integer other than that of the mean at least `margin` in every integer coordinate, by moving
+/// the mean towards the nearest integer boundary and by scaling up the distribution along the
+/// coordinate (the diagonal matrix `A`).
+///
+/// Selection favors steps that stay within the integer of the mean, which shortens the evolution
+/// paths and decreases the step size. The full and separable covariance models compensate for this
+/// in the variances of the integer coordinates, but
+/// [`CovarianceModel::LowRank`][crate::CovarianceModel::LowRank] may converge prematurely in the
+/// continuous coordinates.
+///
+/// Based on Hamano, Saito, Nomura and Shirakawa, "CMA-ES with Margin: Lower-Bounding Marginal
+/// Probability for Mixed-Integer Black-Box Optimization", 2022.
+///
+/// # Examples
+///
+/// ```
+/// use cmaes::{CMAESOptions, DVector, IntegerCoordinates};
+///
+/// // The optimum of the last coordinate is between two integers
+/// let function = |x: &DVector<f64>| x[0].powi(2) + x[1].powi(2) + (x[2] - 2.4).powi(2);
+///
+/// let mut cmaes = CMAESOptions::new(vec![1.0; 3], 1.0)
+///     .integer_coordinates(IntegerCoordinates::new(vec![2]))
+///     .max_function_evals(5000)
+///     .build(function)
+///     .unwrap();
+///
+/// let result = cmaes.run();
+/// assert_eq!(2.0, result.overall_best.unwrap().point[2]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IntegerCoordinates {
+    /// Indices of the integer coordinates. Must be distinct and smaller than the number of
+    /// dimensions.
+    pub indices: Vec<usize>,
+    /// Lower bound of the probability of sampling an integer other than that of the mean in each
+    /// integer coordinate (`alpha`). Must be between `0` and `0.5`. Default value is
+    /// `1 / (N * lambda)` if `None`.
+    pub margin: Option<f64>,
+}
+
+impl IntegerCoordinates {
+    /// Returns `IntegerCoordinates` for the coordinates at `indices` with the default margin.
+    pub fn new(indices: Vec<usize>) -> Self {
+        Self { indices, margin: None }
+    }
+}
+
+/// Returns whether the options are valid for `dim` dimensions and the bounds, if any
+pub(crate) fn is_valid(integers: &IntegerCoordinates, dim: usize, bounds: Option<&Bounds>) -> bool {
+    let mut indices = integers.indices.clone();
+    indices.sort_unstable();
+    indices.dedup();
+
+    let indices_valid = indices.len() == integers.indices.len() && indices.iter().all(|&i| i < dim);
+    let margin_valid = integers.margin.is_none_or(|margin| margin > 0.0 && margin < 0.5);
+    // Every integer coordinate must contain an integer inside the bounds
+    let bounds_valid = bounds.is_none_or(|bounds| {
+        indices
+            .iter()
+            .all(|&i| i >= bounds.lower.len() || bounds.lower[i].ceil() <= bounds.upper[i].floor())
+    });
+
+    indices_valid && margin_valid && bounds_valid
+}
+
+/// Rounds the integer coordinates of points and corrects the distribution to keep the margin
+#[derive(Clone, Debug)]
+pub struct Margin {
+    indices: Vec<usize>,
+    /// The margin (`alpha`)
+    margin: f64,
+    /// Lowest and highest integer of each integer coordinate, infinite if unbounded
+    levels: Vec<(f64, f64)>,
+}
+
+impl Margin {
+    pub fn new(integers: IntegerCoordinates, bounds: Option<&Bounds>, dim: usize, lambda: usize) -> Self {
+        let levels = integers
+            .indices
+            .iter()
+            .map(|&i| match bounds {
+                Some(bounds) => (bounds.lower[i].ceil(), bounds.upper[i].floor()),
+                None => (f64::NEG_INFINITY, f64::INFINITY),
+            })
+            .collect();
+
+        Self {
+            margin: integers.margin.unwrap_or(1.0 / (dim * lambda) as f64),
+            indices: integers.indices,
+            levels,
+        }
+    }
+
+    /// Rounds the integer coordinates of `point` to the nearest integer inside the bounds
+    pub fn encode(&self, point: &mut DVector<f64>) {
+        for (&i, &(lowest, highest)) in self.indices.iter().zip(&self.levels) {
+            point[i] = point[i].round().clamp(lowest, highest);
+        }
+    }
+
+    /// Moves the mean and adjusts the scaling `scale` of the integer coordinates so that the
+    /// probability of sampling an integer other than that of the mean is at least the margin
+    pub fn correct(&self, mean: &mut DVector<f64>, scale: &mut DVector<f64>, sigma: f64, cov: &Covariance) {
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let half_margin = self.margin / 2.0;
+
+        for (&i, &(lowest, highest)) in self.indices.iter().zip(&self.levels) {
+            if lowest == highest {
+                continue;
+            }
+
+            // Standard deviation of the marginal distribution of the coordinate
+            let std_dev = sigma * scale[i] * cov.variance(i).sqrt();
+            let m = mean[i];
+            let level = m.round().clamp(lowest, highest);
+
+            // The interval of values that round to the integer of the mean
+            let lower = if level > lowest { level - 0.5 } else { f64::NEG_INFINITY };
+            let upper = if level < highest { level + 0.5 } else { f64::INFINITY };
+
+            // Probabilities of sampling below and above the interval
+            let p_lower = normal.cdf((lower - m) / std_dev);
+            let p_upper = 1.0 - normal.cdf((upper - m) / std_dev);
+
+            if lower.is_infinite() || upper.is_infinite() {
+                // The mean is in the lowest or highest integer, so only one neighbor can be sampled.
+                // Move the mean towards it until it is sampled with probability `margin`
+                let (boundary, p) = if lower.is_infinite() {
+                    (upper, p_upper)
+                } else {
+                    (lower, p_lower)
+                };
+                if p < self.margin {
+                    let distance = std_dev * normal.inverse_cdf(1.0 - self.margin);
+                    mean[i] = boundary + (m - boundary).signum() * distance;
+                }
+                continue;
+            }
+
+            if p_lower >= half_margin && p_upper >= half_margin {
+                continue;
+            }
+
+            // Raise both probabilities to at least half the margin, taking the added probability
+            // from the other outcomes in proportion to their excess over half the margin
+            let p_middle = 1.0 - p_lower - p_upper;
+            let p_lower = p_lower.max(half_margin);
+            let p_upper = p_upper.max(half_margin);
+            let excess = 1.0 - p_lower - p_upper - p_middle;
+            let total = p_lower + p_middle + p_upper - 3.0 * half_margin;
+            let p_lower = (p_lower + excess * (p_lower - half_margin) / total).clamp(1e-10, 0.5 - 1e-10);
+            let p_upper = (p_upper + excess * (p_upper - half_margin) / total).clamp(1e-10, 0.5 - 1e-10);
+
+            // The mean and standard deviation for which the interval has these tail probabilities
+            let q_lower = normal.inverse_cdf(1.0 - p_lower);
+            let q_upper = normal.inverse_cdf(1.0 - p_upper);
+            let corrected_std_dev = (upper - lower) / (q_lower + q_upper);
+
+            mean[i] = (lower * q_upper + upper * q_lower) / (q_lower + q_upper);
+            scale[i] *= corrected_std_dev / std_dev;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::CovarianceModel;
+
+    fn margin(levels: (f64, f64)) -> Margin {
+        Margin {
+            indices: vec![0],
+            margin: 0.1,
+            levels: vec![levels],
+        }
+    }
+
+    fn tail_probabilities(mean: f64, std_dev: f64, lower: f64, upper: f64) -> (f64, f64) {
+        let normal = Normal::new(mean, std_dev).unwrap();
+        (normal.cdf(lower), 1.0 - normal.cdf(upper))
+    }
+
+    #[test]
+    fn test_encode() {
+        let margin = Margin::new(
+            IntegerCoordinates::new(vec![1, 2]),
+            Some(&Bounds {
+                lower: vec![-1.0, -1.0, 0.5],
+                upper: vec![1.0, 1.0, 3.7],
+            }),
+            3,
+            6,
+        );
+
+        let mut point = DVector::from(vec![0.3, 0.3, 0.3]);
+        margin.encode(&mut point);
+        assert_eq!(DVector::from(vec![0.3, 0.0, 1.0]), point);
+
+        let mut point = DVector::from(vec![0.6, 0.6, 3.6]);
+        margin.encode(&mut point);
+        assert_eq!(DVector::from(vec![0.6, 1.0, 3.0]), point);
+    }
+
+    #[test]
+    fn test_correct_side() {
+        let cov = Covariance::new(CovarianceModel::Full, 1, 4);
+        let margin = margin((f64::NEG_INFINITY, f64::INFINITY));
+
+        // A narrow distribution off the center of an integer
+        let mut mean = DVector::from(vec![2.2]);
+        let mut scale = DVector::from(vec![1.0]);
+        margin.correct(&mut mean, &mut scale, 0.01, &cov);
+
+        let (p_lower, p_upper) = tail_probabilities(mean[0], 0.01 * scale[0], 1.5, 2.5);
+        assert!(p_lower >= 0.05 - 1e-9, "{}", p_lower);
+        assert!(p_upper >= 0.05 - 1e-9, "{}", p_upper);
+        assert!(scale[0] > 1.0);
+        assert_eq!(2.0, mean[0].round());
+
+        // A wide distribution is left unchanged
+        let mut mean = DVector::from(vec![2.2]);
+        let mut scale = DVector::from(vec![1.0]);
+        margin.correct(&mut mean, &mut scale, 1.0, &cov);
+        assert_eq!(2.2, mean[0]);
+        assert_eq!(1.0, scale[0]);
+    }
+
+    #[test]
+    fn test_correct_edge() {
+        let cov = Covariance::new(CovarianceModel::Separable, 1, 4);
+        let margin = margin((0.0, 5.0));
+
+        // The lowest integer can only be left upwards
+        let mut mean = DVector::from(vec![-0.3]);
+        let mut scale = DVector::from(vec![1.0]);
+        margin.correct(&mut mean, &mut scale, 0.01, &cov);
+
+        let (_, p_upper) = tail_probabilities(mean[0], 0.01, 0.5, 0.5);
+        assert_approx_eq::assert_approx_eq!(0.1, p_upper, 1e-9);
+        assert_eq!(1.0, scale[0]);
+        assert!(mean[0] < 0.5);
+    }
+}
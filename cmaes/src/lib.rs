@@ -70,7 +70,28 @@
 //! The [`objective_function`] module provides traits that allow for custom objective function types
 //! that store state and parameters.
 //!
-//! The [`CMAES::next`] method provides finer control over iteration if needed.
+//! The [`CMAES::next`] method provides finer control over iteration if needed. If the objective
+//! function can't be owned by the `CMAES` or called synchronously, [`CMAES::ask`] and
+//! [`CMAES::tell`] split each generation into sampling the points and reporting their values.
+//!
+//! [`CMAES::snapshot`] and [`CMAES::restore`] save and load the state of a run, which can be used to
+//! checkpoint long runs or warm-start new runs from a learned distribution. Enable the `serde`
+//! feature to serialize [`Snapshot`]s.
+//!
+//! [`CMAESOptions::observer`] sets an [`Observer`] that is called with the [`Progress`] of the run
+//! after every generation and can stop the run early, for reporting or logging progress without
+//! parsing the printed output.
+//!
+//! Coordinates that only take integer values can be set with
+//! [`CMAESOptions::integer_coordinates`], which rounds them before evaluation and keeps them from
+//! fixing with the margin of CMA-ES with margin.
+//!
+//! For noisy objective functions, [`CMAESOptions::uncertainty_handling`] enables the uncertainty
+//! handling of UH-CMA-ES, which detects when the noise dominates the ranking of the points.
+//!
+//! Functions with several objectives can be optimized with MO-CMA-ES in the [`multi_objective`]
+//! module, which approximates the set of best trade-offs between the objectives instead of a
+//! single optimum.
 //!
 //! # Citations
 //!
@@ -99,45 +120,76 @@
 // Termination criteria are handled in the `termination` module.
 //
 // Automatic restart algorithms are contained in the `restart` module.
+//
+// Saving and loading the variable state is handled in the `snapshot` module.
+//
+// Reevaluation of points for noisy functions is handled in the `uncertainty` module.
+//
+// Rounding of integer coordinates and the margin that keeps them from fixing is handled in the
+// `integer` module.
+//
+// MO-CMA-ES is contained in the `multi_objective` module and shares no state with `CMAES`.
 
+mod boundary;
 pub mod functions;
 mod history;
+mod integer;
 mod matrix;
 mod mode;
+pub mod multi_objective;
 pub mod objective_function;
+pub mod observer;
 pub mod options;
 pub mod parameters;
+pub mod restart;
 mod sampling;
+pub mod snapshot;
 mod state;
 pub mod termination;
+mod uncertainty;
 mod utils;
 
 pub use nalgebra::DVector;
 
+pub use crate::boundary::BoundaryHandling;
 pub use crate::functions::*;
 pub use crate::history::MAX_HISTORY_LENGTH;
+pub use crate::integer::IntegerCoordinates;
 pub use crate::mode::Mode;
 use crate::objective_function::BatchObjectiveFunction;
-pub use crate::objective_function::{ObjectiveFunction, ParallelObjectiveFunction};
+pub use crate::objective_function::{MultiObjectiveFunction, ObjectiveFunction, ParallelObjectiveFunction};
+pub use crate::observer::{Observer, Progress};
 pub use crate::options::CMAESOptions;
-pub use crate::parameters::Weights;
+pub use crate::parameters::{CovarianceModel, Weights};
 pub use crate::sampling::Bounds;
 pub use crate::sampling::Constraints;
+pub use crate::snapshot::Snapshot;
 pub use crate::termination::TerminationReason;
+pub use crate::uncertainty::{NoiseTreatment, UncertaintyHandling, UncertaintyState};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use std::f64;
+use std::ops::ControlFlow;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::boundary::BoundaryRepair;
 use crate::history::History;
-use crate::matrix::SquareMatrix;
+use crate::integer::Margin;
+use crate::matrix::{Covariance, SquareMatrix};
 use crate::options::InvalidOptionsError;
 use crate::parameters::Parameters;
-use crate::sampling::{EvaluatedPoint, InvalidFunctionValueError, Sampler};
+use crate::sampling::{Candidate, EvaluatedPoint, InvalidFunctionValueError, Sampler};
+use crate::snapshot::InvalidSnapshotError;
 use crate::state::State;
 use crate::termination::TerminationCheck;
+use crate::uncertainty::{EvaluationPlan, UncertaintyHandler};
 
 /// An individual point with its corresponding objective function value.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Individual {
     pub point: DVector<f64>,
     pub value: f64,
@@ -158,6 +210,7 @@ impl Individual {
 /// - Final mean, which may be better than either individual
 /// - Reasons for termination, which can be used to decide how to interpret the result and
 ///   whether and how to restart the algorithm
+/// - Noise level measured by the uncertainty handling, if enabled
 #[derive(Clone, Debug)]
 pub struct TerminationData {
     /// Always `Some` unless the algorithm terminated in the first generation with
@@ -168,6 +221,9 @@ pub struct TerminationData {
     pub overall_best: Option<Individual>,
     pub final_mean: DVector<f64>,
     pub reasons: Vec<TerminationReason>,
+    /// The final state of the uncertainty handling. `Some` if
+    /// [`CMAESOptions::uncertainty_handling`] is set.
+    pub uncertainty: Option<UncertaintyState>,
 }
 
 /// A type that handles algorithm iteration and printing of results. Use [`CMAESOptions`]
@@ -207,8 +263,13 @@ pub struct CMAES<F> {
     print_gap_evals: Option<usize>,
     /// The last time [`CMAES::print_info`] was called, in function evaluations
     last_print_evals: usize,
+    /// Called after every generation
+    observer: Option<Arc<dyn Observer>>,
     /// The time at which the `CMAES` was created
     time_created: Instant,
+    /// Points returned by the latest [`CMAES::ask`] call that have not been passed to
+    /// [`CMAES::tell`] yet, and how they are evaluated
+    asked: Option<(Vec<Candidate>, EvaluationPlan)>,
 }
 
 impl<F> CMAES<F> {
@@ -233,23 +294,67 @@ impl<F> CMAES<F> {
             return Err(InvalidOptionsError::Cm);
         }
 
+        if options.covariance_model == (CovarianceModel::LowRank { rank: 0 }) {
+            return Err(InvalidOptionsError::CovarianceRank);
+        }
+
+        let bounds = options.constraints.as_ref().and_then(|c| c.bounds()).cloned();
+        if !options::are_bounds_valid(bounds.as_ref(), dimensions, options.boundary_handling) {
+            return Err(InvalidOptionsError::Bounds);
+        }
+
+        if let Some(integers) = &options.integer_coordinates {
+            if !integer::is_valid(integers, dimensions, bounds.as_ref()) {
+                return Err(InvalidOptionsError::IntegerCoordinates);
+            }
+        }
+
+        if let Some(uncertainty_handling) = &options.uncertainty_handling {
+            if !uncertainty::is_valid(uncertainty_handling, options.population_size) {
+                return Err(InvalidOptionsError::UncertaintyHandling);
+            }
+        }
+
         let seed = options.seed.unwrap_or_else(rand::random);
 
         // Initialize constant parameters according to the options
         let parameters = Parameters::from_options(&options, seed);
 
+        let margin = options
+            .integer_coordinates
+            .map(|integers| Margin::new(integers, bounds.as_ref(), dimensions, options.population_size));
+
+        // Points are only resampled if they are not repaired
+        let boundary = bounds.and_then(|bounds| {
+            BoundaryRepair::new(
+                options.boundary_handling,
+                bounds,
+                options.population_size,
+                parameters.mu_eff(),
+            )
+        });
+        let constraints = if boundary.is_some() { None } else { options.constraints };
+        let uncertainty = options
+            .uncertainty_handling
+            .map(|uncertainty| UncertaintyHandler::new(uncertainty, dimensions, options.population_size));
+
         // Initialize point sampler
         let sampler = Sampler::new(
             dimensions,
-            options.constraints,
+            constraints,
+            boundary,
             options.max_resamples,
             options.population_size,
             objective_function,
             seed,
-        );
+        )
+        .with_uncertainty(uncertainty)
+        .with_margin(margin);
 
         // Initialize variable parameters
-        let state = State::new(options.initial_mean, options.initial_step_size);
+        let cov = Covariance::new(options.covariance_model, dimensions, options.population_size);
+        let state =
+            State::new(options.initial_mean, options.initial_step_size, cov).with_margin(sampler.margin().is_some());
 
         // Initialize function value history
         let history = History::new();
@@ -261,7 +366,9 @@ impl<F> CMAES<F> {
             history,
             print_gap_evals: options.print_gap_evals,
             last_print_evals: 0,
+            observer: options.observer,
             time_created: Instant::now(),
+            asked: None,
         };
 
         // Print initial info
@@ -287,6 +394,9 @@ impl<F> CMAES<F> {
 
     /// Shared logic between `next` and `next_parallel`
     fn next_internal(&mut self, individuals: &[EvaluatedPoint]) -> Option<TerminationData> {
+        // Points asked from the old distribution can no longer be told
+        self.asked = None;
+
         // Update state
         if self
             .state
@@ -296,6 +406,14 @@ impl<F> CMAES<F> {
             return Some(self.get_termination_data(vec![TerminationReason::PosDefCov]));
         }
 
+        // Spread the points further apart if noise dominates their ranking
+        self.state.scale_sigma(self.sampler.adapt_to_noise());
+
+        // Keep the integer coordinates from fixing
+        if let Some(margin) = self.sampler.margin() {
+            self.state.apply_margin(margin);
+        }
+
         // Print latest state
         if let Some(gap_evals) = self.print_gap_evals {
             // The first few generations are always printed, then print_gap_evals is respected
@@ -310,16 +428,21 @@ impl<F> CMAES<F> {
         }
 
         // Terminate with the current best individual if any termination criteria are met
-        let termination_reasons = TerminationCheck {
+        let mut termination_reasons = TerminationCheck {
             current_function_evals: self.sampler.function_evals(),
             time_created: self.time_created,
             parameters: &self.parameters,
             state: &self.state,
             history: &self.history,
             individuals,
+            noisy: self.sampler.uncertainty_state().is_some_and(|u| u.level > 0.0),
         }
         .check_termination_criteria();
 
+        if self.observe().is_break() {
+            termination_reasons.push(TerminationReason::Stopped);
+        }
+
         if !termination_reasons.is_empty() {
             Some(self.get_termination_data(termination_reasons))
         } else {
@@ -327,6 +450,126 @@ impl<F> CMAES<F> {
         }
     }
 
+    /// Passes the progress of the latest generation to the observer, if any
+    fn observe(&self) -> ControlFlow<()> {
+        let Some(observer) = &self.observer else {
+            return ControlFlow::Continue(());
+        };
+        // Both are set by `sample_internal` before this is called
+        let (Some(current_best), Some(overall_best)) = (self.current_best_individual(), self.overall_best_individual())
+        else {
+            return ControlFlow::Continue(());
+        };
+
+        observer.observe(&Progress {
+            generation: self.state.generation(),
+            function_evals: self.sampler.function_evals(),
+            sigma: self.state.sigma(),
+            axis_ratio: self.axis_ratio(),
+            best_value: current_best.value,
+            median_value: self.history.median_function_values()[0],
+            best_point: &current_best.point,
+            overall_best_value: overall_best.value,
+        })
+    }
+
+    /// Samples `lambda` points from the distribution and returns them without evaluating them. The
+    /// objective function values of the points must be passed to [`tell`][Self::tell] in the same
+    /// order to advance to the next generation. Calling `ask` again before `tell` discards the
+    /// previous points.
+    ///
+    /// With [`CMAESOptions::uncertainty_handling`], the returned points include the repeated
+    /// evaluations, so a point may be returned several times and more than `lambda` points may be
+    /// returned.
+    ///
+    /// This allows evaluating points outside of the `CMAES`, for example asynchronously or pooled
+    /// with the points of other `CMAES` instances. The objective function the `CMAES` was built
+    /// with is not used, so `()` can be passed to [`CMAESOptions::build`]:
+    ///
+    /// ```
+    /// use cmaes::{CMAESOptions, DVector};
+    ///
+    /// let sphere = |x: &DVector<f64>| x.iter().map(|xi| xi.powi(2)).sum::<f64>();
+    ///
+    /// let mut instances = (0..3)
+    ///     .map(|i| {
+    ///         CMAESOptions::new(vec![1.0; 4], 1.0)
+    ///             .fun_target(1e-8)
+    ///             .seed(i)
+    ///             .build(())
+    ///             .unwrap()
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// while !instances.is_empty() {
+    ///     // Evaluate the points of every instance at once
+    ///     let asked = instances.iter_mut().map(|cmaes| cmaes.ask()).collect::<Vec<_>>();
+    ///     let mut values = asked.concat().iter().map(sphere).collect::<Vec<_>>();
+    ///
+    ///     let mut terminated = Vec::new();
+    ///     for (i, (cmaes, points)) in instances.iter_mut().zip(&asked).enumerate() {
+    ///         let rest = values.split_off(points.len());
+    ///         if cmaes.tell(&values).is_some() {
+    ///             terminated.push(i);
+    ///         }
+    ///         values = rest;
+    ///     }
+    ///
+    ///     for i in terminated.into_iter().rev() {
+    ///         instances.remove(i);
+    ///     }
+    /// }
+    /// ```
+    pub fn ask(&mut self) -> Vec<DVector<f64>> {
+        let candidates = self
+            .sampler
+            .sample_candidates(&self.state, self.parameters.parallel_update());
+        let plan = self.sampler.plan_evaluations();
+        let points = plan.points(&candidates).into_iter().cloned().collect();
+
+        self.asked = Some((candidates, plan));
+
+        points
+    }
+
+    /// Passes the objective function values of the points returned by [`ask`][Self::ask] and
+    /// advances to the next generation. Like [`next`][Self::next], returns `Some` if a termination
+    /// condition has been reached and the algorithm should be stopped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ask` was not called since the last generation or if the number of values does
+    /// not match the number of points.
+    #[must_use]
+    pub fn tell(&mut self, values: &[f64]) -> Option<TerminationData> {
+        let (candidates, plan) = self
+            .asked
+            .take()
+            .expect("`tell` was called without a preceding call to `ask`");
+        assert_eq!(
+            plan.len(),
+            values.len(),
+            "`tell` received {} values for {} points",
+            values.len(),
+            plan.len()
+        );
+
+        let individuals =
+            match self
+                .sampler
+                .evaluate_candidates(&self.state, self.parameters.mode(), candidates, plan, values)
+            {
+                Ok(x) => x,
+                Err(_) => {
+                    return Some(self.get_termination_data(vec![TerminationReason::InvalidFunctionValue]));
+                }
+            };
+
+        self.sample_internal(&individuals);
+
+        self.next_internal(&individuals)
+    }
+
     /// Consumes `self` and returns the objective function. Useful for retrieving state stored in
     /// custom objective function types.
     pub fn into_objective_function(self) -> F {
@@ -354,13 +597,29 @@ impl<F> CMAES<F> {
     }
 
     /// Returns the current covariance matrix of the distribution.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless [`CovarianceModel::Full`] is used, as the other models do not store the
+    /// matrix. Use [`covariance_matrix_dense`][Self::covariance_matrix_dense] for those.
     pub fn covariance_matrix(&self) -> &SquareMatrix<f64> {
-        self.state.cov()
+        match self.state.cov() {
+            Covariance::Full(cov) => cov.cov(),
+            _ => panic!("`covariance_matrix` requires `CovarianceModel::Full`"),
+        }
+    }
+
+    /// Returns a copy of the current covariance matrix of the distribution for any
+    /// [`CovarianceModel`]. The matrix is assembled on each call unless [`CovarianceModel::Full`]
+    /// is used.
+    pub fn covariance_matrix_dense(&self) -> SquareMatrix<f64> {
+        self.state.cov().to_matrix()
     }
 
-    /// Returns the current eigenvalues of the distribution.
+    /// Returns the current eigenvalues of the distribution. These are computed from the assembled
+    /// covariance matrix on each call if [`CovarianceModel::LowRank`] is used.
     pub fn eigenvalues(&self) -> DVector<f64> {
-        self.state.cov_sqrt_eigenvalues().diagonal().map(|x| x.powi(2))
+        self.state.cov().eigenvalues()
     }
 
     /// Returns the current step size of the distribution.
@@ -368,7 +627,8 @@ impl<F> CMAES<F> {
         self.state.sigma()
     }
 
-    /// Returns the current axis ratio of the distribution.
+    /// Returns the current axis ratio of the distribution. This is an upper bound if
+    /// [`CovarianceModel::LowRank`] is used.
     pub fn axis_ratio(&self) -> f64 {
         self.state.axis_ratio()
     }
@@ -389,6 +649,12 @@ impl<F> CMAES<F> {
         self.history.overall_best_individual()
     }
 
+    /// Returns the state of the uncertainty handling if it is enabled with
+    /// [`CMAESOptions::uncertainty_handling`].
+    pub fn uncertainty(&self) -> Option<&UncertaintyState> {
+        self.sampler.uncertainty_state()
+    }
+
     /// Returns the time at which the `CMAES` was created.
     pub fn time_created(&self) -> Instant {
         self.time_created
@@ -399,6 +665,47 @@ impl<F> CMAES<F> {
         self.time_created.elapsed()
     }
 
+    /// Returns a [`Snapshot`] of the variable state of the algorithm, which includes the
+    /// distribution, evolution paths, objective function value history and RNG state.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            generation: self.state.generation(),
+            function_evals: self.sampler.function_evals(),
+            mean: self.state.mean().clone(),
+            sigma: self.state.sigma(),
+            covariance: self.state.cov().snapshot(),
+            path_c: self.state.path_c().clone(),
+            path_sigma: self.state.path_sigma().clone(),
+            last_eigen_update_evals: self.state.last_eigen_update_evals(),
+            history: self.history.snapshot(),
+            rng: self.sampler.rng_snapshot(),
+            uncertainty: self.sampler.uncertainty_state().cloned(),
+            margin_scale: self.state.margin_scale().cloned(),
+        }
+    }
+
+    /// Replaces the variable state of the algorithm with a [`Snapshot`] taken by
+    /// [`snapshot`][Self::snapshot]. The options the `CMAES` was built with are kept, so a run
+    /// restored into a `CMAES` built with the same options continues exactly where the snapshot was
+    /// taken. The time limit is measured from the creation of this `CMAES`. Points returned by
+    /// [`ask`][Self::ask] are discarded.
+    ///
+    /// Returns `Err` and leaves the state unchanged if the snapshot does not match the dimensions or
+    /// covariance model of the `CMAES`.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), InvalidSnapshotError> {
+        snapshot::validate(&snapshot, &self.parameters)?;
+
+        self.state =
+            State::from_snapshot(&snapshot, self.parameters.lambda()).with_margin(self.sampler.margin().is_some());
+        self.sampler.restore(&snapshot.rng, snapshot.function_evals);
+        self.sampler.restore_uncertainty(snapshot.uncertainty);
+        self.history = History::from_snapshot(snapshot.history);
+        self.last_print_evals = snapshot.function_evals;
+        self.asked = None;
+
+        Ok(())
+    }
+
     /// Returns how many generations take place for each update of the eigendecomposition.
     ///
     /// For example, if this value is `3`, an eigen update will take place once for every `3`
@@ -417,13 +724,14 @@ impl<F> CMAES<F> {
             overall_best: self.overall_best_individual().cloned(),
             final_mean: self.state.mean().clone(),
             reasons,
+            uncertainty: self.sampler.uncertainty_state().cloned(),
         }
     }
 
     /// Prints various initial parameters of the algorithm as well as the headers for the columns
     /// printed by [`print_info`][Self::print_info]. The parameters that are printed are the:
     ///
-    /// - Algorithm variant (based on the [`Weights`] setting)
+    /// - Algorithm variant (based on the [`Weights`] and [`CovarianceModel`] settings)
     /// - Dimension (N)
     /// - Population size (lambda)
     /// - Seed
@@ -431,9 +739,12 @@ impl<F> CMAES<F> {
     /// This function is called automatically if [`CMAESOptions::enable_printing`] is set.
     pub fn print_initial_info(&self) {
         let params = &self.parameters;
-        let variant = match params.weights_setting() {
-            Weights::Positive | Weights::Uniform => "CMA-ES",
-            Weights::Negative => "aCMA-ES",
+        let variant = match (params.covariance_model(), params.weights_setting()) {
+            (CovarianceModel::Full, Weights::Positive | Weights::Uniform) => "CMA-ES",
+            (CovarianceModel::Full, Weights::Negative) => "aCMA-ES",
+            (CovarianceModel::Separable, Weights::Positive | Weights::Uniform) => "sep-CMA-ES",
+            (CovarianceModel::Separable, Weights::Negative) => "sep-aCMA-ES",
+            (CovarianceModel::LowRank { .. }, _) => "LM-MA-ES",
         };
         println!(
             "{} with dimension={}, lambda={}, seed={}",
@@ -694,4 +1005,25 @@ mod tests {
         assert_eq!(15, cmaes_10.state.evals_per_eigen_update(cmaes_10.parameters()));
         assert_eq!(34, cmaes_30.state.evals_per_eigen_update(cmaes_30.parameters()));
     }
+    #[test]
+    fn test_covariance_matrix() {
+        let full = CMAESOptions::new(vec![0.0; 4], 1.0).build(dummy_function).unwrap();
+        assert_eq!(full.covariance_matrix(), &full.covariance_matrix_dense());
+
+        let separable = CMAESOptions::new(vec![0.0; 4], 1.0)
+            .covariance_model(CovarianceModel::Separable)
+            .build(dummy_function)
+            .unwrap();
+        assert_eq!(SquareMatrix::identity(4, 4), separable.covariance_matrix_dense());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_covariance_matrix_not_stored() {
+        let low_rank = CMAESOptions::new(vec![0.0; 4], 1.0)
+            .covariance_model(CovarianceModel::LowRank { rank: 2 })
+            .build(dummy_function)
+            .unwrap();
+        let _ = low_rank.covariance_matrix();
+    }
 }
@@ -1,7 +1,10 @@
 //! Types related to matrix math.
 
 use nalgebra::base::VecStorage;
-use nalgebra::Dyn;
+use nalgebra::{DVector, Dyn};
+
+use crate::parameters::CovarianceModel;
+use crate::snapshot::CovarianceSnapshot;
 
 pub type SquareMatrix<T> = nalgebra::SquareMatrix<T, Dyn, VecStorage<T, Dyn, Dyn>>;
 
@@ -67,14 +70,19 @@ impl CovarianceMatrix {
             return Err(PosDefCovError);
         }
 
-        self.eigenvectors = eigen.eigenvectors;
-        self.sqrt_eigenvalues = SquareMatrix::from_diagonal(&eigen.eigenvalues.map(|x| x.sqrt()));
+        self.set_eigendecomposition(eigen.eigenvectors, eigen.eigenvalues.map(|x| x.sqrt()));
+
+        Ok(())
+    }
+
+    /// Sets the eigendecomposition and the matrices derived from it
+    fn set_eigendecomposition(&mut self, eigenvectors: SquareMatrix<f64>, sqrt_eigenvalues: DVector<f64>) {
+        self.eigenvectors = eigenvectors;
+        self.sqrt_eigenvalues = SquareMatrix::from_diagonal(&sqrt_eigenvalues);
         self.sqrt_inv = &self.eigenvectors
             * self.sqrt_eigenvalues.map(|d| if d > 0.0 { 1.0 / d } else { d })
             * self.eigenvectors.transpose();
         self.transform = &self.eigenvectors * &self.sqrt_eigenvalues;
-
-        Ok(())
     }
 
     pub fn eigenvectors(&self) -> &SquareMatrix<f64> {
@@ -95,6 +103,294 @@ impl CovarianceMatrix {
     }
 }
 
+/// A diagonal covariance matrix, as adapted by sep-CMA-ES
+pub struct DiagonalCovariance {
+    /// Diagonal of the covariance matrix
+    variances: DVector<f64>,
+    /// Square roots of the variances, which are the scales of the coordinate axes
+    std_devs: DVector<f64>,
+}
+
+impl DiagonalCovariance {
+    /// Returns an identity `DiagonalCovariance`
+    pub fn new(dim: usize) -> Self {
+        Self {
+            variances: DVector::repeat(dim, 1.0),
+            std_devs: DVector::repeat(dim, 1.0),
+        }
+    }
+
+    pub fn variances(&self) -> &DVector<f64> {
+        &self.variances
+    }
+
+    pub fn std_devs(&self) -> &DVector<f64> {
+        &self.std_devs
+    }
+
+    /// Updates the variances
+    ///
+    /// Returns `Err` if any variance is not positive and finite
+    pub fn set_variances(&mut self, new: DVector<f64>) -> Result<(), PosDefCovError> {
+        if new.iter().any(|x| !(x.is_finite() && *x > 0.0)) {
+            return Err(PosDefCovError);
+        }
+
+        self.std_devs = new.map(|x| x.sqrt());
+        self.variances = new;
+
+        Ok(())
+    }
+}
+
+/// The implicit covariance matrix `A * A^T` of limited-memory matrix adaptation (LM-MA-ES). The
+/// transform `A = F_m * ... * F_1` is a product of rank-one modifications of the identity
+/// `F_j = (1 - c_d,j) * I + c_d,j * m_j * m_j^T`, so it is never stored as a matrix
+pub struct LowRankCovariance {
+    dim: usize,
+    /// Directions of the rank-one modifications, which are evolution paths in the isotropic
+    /// space with decreasing time horizons (`m_j`)
+    directions: Vec<DVector<f64>>,
+    /// Learning rates of the directions (`c_c,j`)
+    path_rates: Vec<f64>,
+    /// Strengths of the rank-one modifications (`c_d,j`)
+    transform_rates: Vec<f64>,
+    /// Number of directions that have been updated at least once and are used by the transform
+    active: usize,
+}
+
+impl LowRankCovariance {
+    /// Returns an identity `LowRankCovariance` with `rank` directions for population size `lambda`
+    pub fn new(dim: usize, rank: usize, lambda: usize) -> Self {
+        let n = dim as f64;
+        Self {
+            dim,
+            directions: vec![DVector::zeros(dim); rank],
+            path_rates: (0..rank)
+                .map(|j| (lambda as f64 / (4f64.powi(j as i32) * n)).min(1.0))
+                .collect(),
+            // Capped so every modification stays invertible in very low dimensions
+            transform_rates: (0..rank)
+                .map(|j| (1.0 / (1.5f64.powi(j as i32) * n)).min(0.5))
+                .collect(),
+            active: 0,
+        }
+    }
+
+    /// Active directions and their transform rates in the order they are applied by the transform
+    fn factors(&self) -> impl DoubleEndedIterator<Item = (&DVector<f64>, f64)> {
+        self.directions
+            .iter()
+            .zip(self.transform_rates.iter().cloned())
+            .take(self.active)
+    }
+
+    /// Returns `A * z`
+    pub fn transform(&self, z: &DVector<f64>) -> DVector<f64> {
+        self.factors()
+            .fold(z.clone(), |d, (m, c)| (1.0 - c) * &d + (c * m.dot(&d)) * m)
+    }
+
+    /// Returns `A^T * y`
+    pub fn transform_transpose(&self, y: &DVector<f64>) -> DVector<f64> {
+        self.factors()
+            .rev()
+            .fold(y.clone(), |d, (m, c)| (1.0 - c) * &d + (c * m.dot(&d)) * m)
+    }
+
+    /// Returns `A^(-1) * y` using the Sherman-Morrison formula for each modification
+    pub fn inverse_transform(&self, y: &DVector<f64>) -> DVector<f64> {
+        self.factors().rev().fold(y.clone(), |d, (m, c)| {
+            let a = 1.0 - c;
+            (&d - (c * m.dot(&d) / (a + c * m.norm_squared())) * m) / a
+        })
+    }
+
+    /// Updates every direction with the weighted mean of the selected steps in the isotropic space
+    /// (`z_w`)
+    pub fn update(&mut self, zw: &DVector<f64>, mu_eff: f64) -> Result<(), PosDefCovError> {
+        for (m, cc) in self.directions.iter_mut().zip(self.path_rates.iter().cloned()) {
+            *m = (1.0 - cc) * &*m + (mu_eff * cc * (2.0 - cc)).sqrt() * zw;
+        }
+
+        if self.directions.iter().any(|m| m.iter().any(|x| !x.is_finite())) {
+            return Err(PosDefCovError);
+        }
+
+        self.active = (self.active + 1).min(self.directions.len());
+
+        Ok(())
+    }
+
+    /// Returns lower and upper bounds on the singular values of `A`, which are the scales of the
+    /// principal axes of the distribution
+    pub fn axis_scale_bounds(&self) -> (f64, f64) {
+        self.factors().fold((1.0, 1.0), |(min, max), (m, c)| {
+            let a = 1.0 - c;
+            // `F_j` scales `m_j` by `a + c * |m_j|^2` and every orthogonal direction by `a`
+            let along = a + c * m.norm_squared();
+            (min * a.min(along), max * a.max(along))
+        })
+    }
+}
+
+/// A covariance matrix of one of the forms selected by [`CovarianceModel`]
+pub enum Covariance {
+    Full(CovarianceMatrix),
+    Separable(DiagonalCovariance),
+    LowRank(LowRankCovariance),
+}
+
+impl Covariance {
+    /// Returns an identity `Covariance` of the given model for population size `lambda`
+    pub fn new(model: CovarianceModel, dim: usize, lambda: usize) -> Self {
+        match model {
+            CovarianceModel::Full => Covariance::Full(CovarianceMatrix::new(dim)),
+            CovarianceModel::Separable => Covariance::Separable(DiagonalCovariance::new(dim)),
+            CovarianceModel::LowRank { rank } => Covariance::LowRank(LowRankCovariance::new(dim, rank, lambda)),
+        }
+    }
+
+    /// Restores a `Covariance` from a snapshot for population size `lambda`. Assumes that the
+    /// snapshot has been validated
+    pub fn from_snapshot(snapshot: &CovarianceSnapshot, dim: usize, lambda: usize) -> Self {
+        match snapshot {
+            CovarianceSnapshot::Full {
+                cov,
+                eigenvectors,
+                sqrt_eigenvalues,
+            } => {
+                let mut matrix = CovarianceMatrix::new(dim);
+                matrix.cov = cov.clone();
+                matrix.set_eigendecomposition(eigenvectors.clone(), sqrt_eigenvalues.clone());
+                Covariance::Full(matrix)
+            }
+            CovarianceSnapshot::Separable { variances } => Covariance::Separable(DiagonalCovariance {
+                variances: variances.clone(),
+                std_devs: variances.map(|x| x.sqrt()),
+            }),
+            CovarianceSnapshot::LowRank { directions, active } => {
+                let mut matrix = LowRankCovariance::new(dim, directions.len(), lambda);
+                matrix.directions = directions.clone();
+                matrix.active = *active;
+                Covariance::LowRank(matrix)
+            }
+        }
+    }
+
+    /// Returns a snapshot of the matrix
+    pub fn snapshot(&self) -> CovarianceSnapshot {
+        match self {
+            Covariance::Full(cov) => CovarianceSnapshot::Full {
+                cov: cov.cov.clone(),
+                eigenvectors: cov.eigenvectors.clone(),
+                sqrt_eigenvalues: cov.sqrt_eigenvalues.diagonal(),
+            },
+            Covariance::Separable(cov) => CovarianceSnapshot::Separable {
+                variances: cov.variances.clone(),
+            },
+            Covariance::LowRank(cov) => CovarianceSnapshot::LowRank {
+                directions: cov.directions.clone(),
+                active: cov.active,
+            },
+        }
+    }
+
+    /// Transforms a step from `N(0, I)` to `N(0, C)`
+    pub fn transform(&self, z: &DVector<f64>) -> DVector<f64> {
+        match self {
+            Covariance::Full(cov) => cov.transform() * z,
+            Covariance::Separable(cov) => cov.std_devs().component_mul(z),
+            Covariance::LowRank(cov) => cov.transform(z),
+        }
+    }
+
+    /// Transforms a step from `N(0, C)` back to `N(0, I)` (`C^(-1/2) * y` for the full and diagonal
+    /// models and `A^(-1) * y` for the low-rank model)
+    pub fn whiten(&self, y: &DVector<f64>) -> DVector<f64> {
+        match self {
+            Covariance::Full(cov) => cov.sqrt_inv() * y,
+            Covariance::Separable(cov) => y.component_div(cov.std_devs()),
+            Covariance::LowRank(cov) => cov.inverse_transform(y),
+        }
+    }
+
+    /// Returns the diagonal of the matrix if it is cheap to compute (`None` for the low-rank model)
+    pub fn diagonal(&self) -> Option<DVector<f64>> {
+        match self {
+            Covariance::Full(cov) => Some(cov.cov().diagonal()),
+            Covariance::Separable(cov) => Some(cov.variances().clone()),
+            Covariance::LowRank(_) => None,
+        }
+    }
+
+    /// Returns the variance of coordinate `i` (`C_ii`)
+    pub fn variance(&self, i: usize) -> f64 {
+        match self {
+            Covariance::Full(cov) => cov.cov()[(i, i)],
+            Covariance::Separable(cov) => cov.variances()[i],
+            Covariance::LowRank(cov) => {
+                let mut e = DVector::zeros(cov.dim);
+                e[i] = 1.0;
+                cov.transform_transpose(&e).norm_squared()
+            }
+        }
+    }
+
+    /// Returns the `i`th axis of the distribution scaled by its standard deviation (the `i`th
+    /// column of the transform). These are the principal axes for the full and diagonal models
+    pub fn axis(&self, i: usize) -> DVector<f64> {
+        match self {
+            Covariance::Full(cov) => cov.sqrt_eigenvalues()[(i, i)] * cov.eigenvectors().column(i),
+            Covariance::Separable(cov) => {
+                let mut axis = DVector::zeros(cov.std_devs().len());
+                axis[i] = cov.std_devs()[i];
+                axis
+            }
+            Covariance::LowRank(cov) => {
+                let mut e = DVector::zeros(cov.dim);
+                e[i] = 1.0;
+                cov.transform(&e)
+            }
+        }
+    }
+
+    /// Returns the smallest and largest standard deviations along the principal axes. These are
+    /// exact for the full and diagonal models and lower and upper bounds for the low-rank model
+    pub fn axis_scale_range(&self) -> (f64, f64) {
+        match self {
+            Covariance::Full(cov) => {
+                let diag = cov.sqrt_eigenvalues().diagonal();
+                (diag.min(), diag.max())
+            }
+            Covariance::Separable(cov) => (cov.std_devs().min(), cov.std_devs().max()),
+            Covariance::LowRank(cov) => cov.axis_scale_bounds(),
+        }
+    }
+
+    /// Returns the matrix itself, assembling it if it is not stored
+    pub fn to_matrix(&self) -> SquareMatrix<f64> {
+        match self {
+            Covariance::Full(cov) => cov.cov().clone(),
+            Covariance::Separable(cov) => SquareMatrix::from_diagonal(cov.variances()),
+            Covariance::LowRank(cov) => {
+                let columns = (0..cov.dim).map(|i| self.axis(i)).collect::<Vec<_>>();
+                let transform = SquareMatrix::from_columns(&columns);
+                &transform * transform.transpose()
+            }
+        }
+    }
+
+    /// Returns the eigenvalues of the matrix, computing them if they are not stored
+    pub fn eigenvalues(&self) -> DVector<f64> {
+        match self {
+            Covariance::Full(cov) => cov.sqrt_eigenvalues().diagonal().map(|x| x.powi(2)),
+            Covariance::Separable(cov) => cov.variances().clone(),
+            Covariance::LowRank(_) => nalgebra::SymmetricEigen::new(self.to_matrix()).eigenvalues,
+        }
+    }
+}
+
 /// The covariance matrix is not positive definite
 #[derive(Clone, Debug)]
 pub struct PosDefCovError;
@@ -128,4 +424,52 @@ mod tests {
             .unwrap();
         assert!(cov.update_eigendecomposition().is_err());
     }
+
+    #[test]
+    fn test_diagonal_covariance() {
+        let mut cov = Covariance::new(CovarianceModel::Separable, 3, 6);
+        if let Covariance::Separable(diagonal) = &mut cov {
+            diagonal.set_variances(vec![4.0, 1.0, 0.25].into()).unwrap();
+            assert!(diagonal.set_variances(vec![1.0, 0.0, 1.0].into()).is_err());
+        }
+
+        let y = cov.transform(&vec![1.0, 1.0, 1.0].into());
+        assert_eq!(y, DVector::from(vec![2.0, 1.0, 0.5]));
+        assert_eq!(cov.whiten(&y), DVector::from(vec![1.0, 1.0, 1.0]));
+        assert_eq!(cov.axis_scale_range(), (0.5, 2.0));
+        assert_eq!(
+            cov.to_matrix(),
+            SquareMatrix::from_diagonal(&vec![4.0, 1.0, 0.25].into())
+        );
+    }
+
+    #[test]
+    fn test_low_rank_covariance() {
+        let dim = 4;
+        let mut cov = LowRankCovariance::new(dim, 3, 8);
+
+        // Identity until the first update
+        let z = DVector::from(vec![1.0, -2.0, 0.5, 3.0]);
+        assert_eq!(cov.transform(&z), z);
+
+        cov.update(&vec![1.0, 0.5, 0.0, -1.0].into(), 2.0).unwrap();
+        cov.update(&vec![0.2, -0.3, 1.5, 0.0].into(), 2.0).unwrap();
+        let cov = Covariance::LowRank(cov);
+
+        for (x, expected) in cov.whiten(&cov.transform(&z)).iter().zip(z.iter()) {
+            assert_approx_eq!(x, expected);
+        }
+
+        // The assembled matrix matches the transform
+        let matrix = cov.to_matrix();
+        for i in 0..dim {
+            assert_approx_eq!(matrix[(i, i)], cov.variance(i));
+        }
+
+        let eigenvalues = cov.eigenvalues();
+        let (min, max) = cov.axis_scale_range();
+        assert!(eigenvalues
+            .iter()
+            .all(|e| e.sqrt() >= min - 1e-12 && e.sqrt() <= max + 1e-12));
+    }
 }
@@ -7,20 +7,15 @@ use crate::utils;
 /// The mode to use when optimizing a function.
 ///
 /// The default value is `Minimize`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Mode {
     /// Minimize the value of the function.
+    #[default]
     Minimize,
     /// Maximize the value of the function.
     Maximize,
 }
 
-impl Default for Mode {
-    fn default() -> Self {
-        Self::Minimize
-    }
-}
-
 impl Mode {
     /// Compares the two values
     /// For use in sorting
@@ -64,13 +59,13 @@ mod tests {
 
     #[test]
     fn test_is_better() {
-        assert_eq!(true, Mode::Minimize.is_better(1.0, 2.0));
-        assert_eq!(false, Mode::Minimize.is_better(2.0, 2.0));
-        assert_eq!(false, Mode::Minimize.is_better(2.0, 1.0));
+        assert!(Mode::Minimize.is_better(1.0, 2.0));
+        assert!(!Mode::Minimize.is_better(2.0, 2.0));
+        assert!(!Mode::Minimize.is_better(2.0, 1.0));
 
-        assert_eq!(false, Mode::Maximize.is_better(1.0, 2.0));
-        assert_eq!(false, Mode::Maximize.is_better(2.0, 2.0));
-        assert_eq!(true, Mode::Maximize.is_better(2.0, 1.0));
+        assert!(!Mode::Maximize.is_better(1.0, 2.0));
+        assert!(!Mode::Maximize.is_better(2.0, 2.0));
+        assert!(Mode::Maximize.is_better(2.0, 1.0));
     }
 
     #[test]
@@ -0,0 +1,158 @@
+//! The archive of non-dominated individuals of a [`MOCMAES`][super::MOCMAES]. See
+//! [`ParetoArchive`] for full documentation.
+
+use nalgebra::DVector;
+
+use super::selection::dominates;
+use super::{hypervolume, MOIndividual};
+use crate::mode::Mode;
+
+/// The set of mutually non-dominated individuals found so far, which approximates the Pareto
+/// front of the objective function. A [`MOCMAES`][super::MOCMAES] adds every evaluated point to its
+/// archive, so the archive also keeps solutions that have dropped out of the population.
+#[derive(Clone, Debug)]
+pub struct ParetoArchive {
+    individuals: Vec<MOIndividual>,
+    /// The objective values of `individuals` converted so that every objective is minimized
+    costs: Vec<DVector<f64>>,
+    mode: Mode,
+    max_size: Option<usize>,
+}
+
+impl ParetoArchive {
+    /// Returns an empty `ParetoArchive` for objectives optimized according to `mode`. If
+    /// `max_size` is set, the individual that contributes the least hypervolume is removed whenever
+    /// the archive grows beyond it.
+    pub fn new(mode: Mode, max_size: Option<usize>) -> Self {
+        Self {
+            individuals: Vec::new(),
+            costs: Vec::new(),
+            mode,
+            max_size,
+        }
+    }
+
+    /// Adds `individual` to the archive unless it is dominated by or has the same objective values
+    /// as an individual in the archive. Removes the individuals it dominates. Returns whether it
+    /// was added.
+    pub fn insert(&mut self, individual: MOIndividual) -> bool {
+        let costs = to_costs(&individual.values, self.mode);
+
+        if self.costs.iter().any(|c| dominates(c, &costs) || *c == costs) {
+            return false;
+        }
+
+        let mut i = 0;
+        while i < self.costs.len() {
+            if dominates(&costs, &self.costs[i]) {
+                self.costs.swap_remove(i);
+                self.individuals.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        self.costs.push(costs);
+        self.individuals.push(individual);
+
+        if let Some(max_size) = self.max_size {
+            if self.len() > max_size {
+                let contributions = hypervolume::contributions(&self.costs, None);
+                let worst = (0..self.len())
+                    .min_by(|&a, &b| contributions[a].total_cmp(&contributions[b]))
+                    .unwrap();
+                self.costs.swap_remove(worst);
+                self.individuals.swap_remove(worst);
+            }
+        }
+
+        true
+    }
+
+    /// Returns the individuals in the archive in no particular order.
+    pub fn individuals(&self) -> &[MOIndividual] {
+        &self.individuals
+    }
+
+    /// Returns the number of individuals in the archive.
+    pub fn len(&self) -> usize {
+        self.individuals.len()
+    }
+
+    /// Returns whether the archive is empty.
+    pub fn is_empty(&self) -> bool {
+        self.individuals.is_empty()
+    }
+
+    /// Returns the hypervolume of the archive relative to `reference`, which is in the same units
+    /// as the objective values. Higher is better in both modes.
+    pub fn hypervolume(&self, reference: &DVector<f64>) -> f64 {
+        hypervolume(&self.costs, &to_costs(reference, self.mode))
+    }
+}
+
+/// Converts objective values so that every objective is minimized
+pub(crate) fn to_costs(values: &DVector<f64>, mode: Mode) -> DVector<f64> {
+    match mode {
+        Mode::Minimize => values.clone(),
+        Mode::Maximize => -values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn individual(values: &[f64]) -> MOIndividual {
+        MOIndividual {
+            point: DVector::zeros(1),
+            values: DVector::from(values.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut archive = ParetoArchive::new(Mode::Minimize, None);
+
+        assert!(archive.insert(individual(&[2.0, 2.0])));
+        assert!(archive.insert(individual(&[1.0, 3.0])));
+        // Dominated and duplicate
+        assert!(!archive.insert(individual(&[2.0, 3.0])));
+        assert!(!archive.insert(individual(&[1.0, 3.0])));
+        assert_eq!(2, archive.len());
+
+        // Dominates both
+        assert!(archive.insert(individual(&[1.0, 1.0])));
+        assert_eq!(1, archive.len());
+        assert_eq!(4.0, archive.hypervolume(&DVector::from(vec![3.0, 3.0])));
+    }
+
+    #[test]
+    fn test_maximize() {
+        let mut archive = ParetoArchive::new(Mode::Maximize, None);
+
+        assert!(archive.insert(individual(&[1.0, 1.0])));
+        assert!(archive.insert(individual(&[2.0, 2.0])));
+        assert!(!archive.insert(individual(&[0.0, 2.0])));
+        assert_eq!(1, archive.len());
+        assert_eq!(4.0, archive.hypervolume(&DVector::from(vec![0.0, 0.0])));
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut archive = ParetoArchive::new(Mode::Minimize, Some(3));
+
+        for values in [[0.0, 3.0], [1.0, 1.0], [2.0, 0.5], [3.0, 0.0]] {
+            assert!(archive.insert(individual(&values)));
+        }
+
+        // The point with the smallest contribution is removed and the extremes are kept
+        let mut kept = archive
+            .individuals()
+            .iter()
+            .map(|individual| individual.values[0])
+            .collect::<Vec<_>>();
+        kept.sort_by(f64::total_cmp);
+        assert_eq!(vec![0.0, 1.0, 3.0], kept);
+    }
+}
@@ -0,0 +1,165 @@
+//! The hypervolume indicator of sets of objective vectors
+
+use nalgebra::DVector;
+
+/// Returns the hypervolume (the size of the region dominated by `points` and bounded by
+/// `reference`), assuming that every objective is minimized. Points that do not dominate
+/// `reference` in every objective do not contribute.
+///
+/// # Examples
+///
+/// ```
+/// use cmaes::multi_objective::hypervolume;
+/// use cmaes::DVector;
+///
+/// let points = [DVector::from(vec![1.0, 2.0]), DVector::from(vec![2.0, 1.0])];
+/// let reference = DVector::from(vec![3.0, 3.0]);
+///
+/// assert_eq!(3.0, hypervolume(&points, &reference));
+/// ```
+pub fn hypervolume(points: &[DVector<f64>], reference: &DVector<f64>) -> f64 {
+    let points = points
+        .iter()
+        .filter(|p| p.iter().zip(reference.iter()).all(|(pi, ri)| pi < ri))
+        .map(|p| p.as_slice())
+        .collect();
+
+    hypervolume_slices(points, reference.as_slice())
+}
+
+/// Computes the hypervolume of points that all dominate `reference` by slicing it along the last
+/// objective
+fn hypervolume_slices(mut points: Vec<&[f64]>, reference: &[f64]) -> f64 {
+    let m = reference.len();
+
+    if points.is_empty() {
+        return 0.0;
+    }
+
+    match m {
+        1 => reference[0] - points.iter().map(|p| p[0]).fold(f64::INFINITY, f64::min),
+        2 => {
+            points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+
+            let mut volume = 0.0;
+            let mut bound = reference[1];
+            for p in points {
+                if p[1] < bound {
+                    volume += (reference[0] - p[0]) * (bound - p[1]);
+                    bound = p[1];
+                }
+            }
+            volume
+        }
+        _ => {
+            points.sort_by(|a, b| a[m - 1].total_cmp(&b[m - 1]));
+
+            let mut volume = 0.0;
+            for i in 0..points.len() {
+                let upper = points.get(i + 1).map_or(reference[m - 1], |p| p[m - 1]);
+                let depth = upper - points[i][m - 1];
+
+                if depth > 0.0 {
+                    let projected = points[..=i].iter().map(|p| &p[..m - 1]).collect();
+                    volume += depth * hypervolume_slices(projected, &reference[..m - 1]);
+                }
+            }
+            volume
+        }
+    }
+}
+
+/// Returns the hypervolume contributed by each of the mutually non-dominated `points` alone.
+///
+/// Without a `reference`, the points that are best in an objective are assigned an infinite
+/// contribution so that the extent of the front is kept, and the rest are measured against a
+/// reference slightly worse than the worst value of each objective.
+pub(crate) fn contributions(points: &[DVector<f64>], reference: Option<&DVector<f64>>) -> Vec<f64> {
+    let Some(first) = points.first() else {
+        return Vec::new();
+    };
+    let m = first.len();
+
+    let mut extreme = vec![false; points.len()];
+    let reference = match reference {
+        Some(reference) => reference.clone(),
+        None => {
+            for j in 0..m {
+                let best = (0..points.len())
+                    .min_by(|&a, &b| points[a][j].total_cmp(&points[b][j]))
+                    .unwrap();
+                extreme[best] = true;
+            }
+
+            DVector::from_fn(m, |j, _| {
+                let (min, max) = points.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), p| {
+                    (min.min(p[j]), max.max(p[j]))
+                });
+                let range = max - min;
+                max + if range > 0.0 { 0.1 * range } else { 1.0 }
+            })
+        }
+    };
+
+    let total = hypervolume(points, &reference);
+
+    (0..points.len())
+        .map(|i| {
+            if extreme[i] {
+                return f64::INFINITY;
+            }
+
+            let others = points
+                .iter()
+                .enumerate()
+                .filter(|(k, _)| *k != i)
+                .map(|(_, p)| p.clone())
+                .collect::<Vec<_>>();
+            total - hypervolume(&others, &reference)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn points(values: &[&[f64]]) -> Vec<DVector<f64>> {
+        values.iter().map(|v| DVector::from(v.to_vec())).collect()
+    }
+
+    #[test]
+    fn test_hypervolume() {
+        let reference = DVector::from(vec![4.0, 4.0]);
+        assert_eq!(0.0, hypervolume(&[], &reference));
+        // Dominated and out of reference points add nothing
+        assert_eq!(
+            5.0,
+            hypervolume(
+                &points(&[&[1.0, 3.0], &[2.0, 2.0], &[3.0, 3.0], &[5.0, 0.0]]),
+                &reference
+            )
+        );
+
+        // Boxes of volume 4 and 2 that overlap in a unit cube, plus a dominated box inside the second
+        let reference = DVector::from(vec![3.0, 3.0, 3.0]);
+        let front = points(&[&[1.0, 1.0, 2.0], &[2.0, 2.0, 1.0], &[2.0, 2.0, 2.0]]);
+        assert_eq!(5.0, hypervolume(&front, &reference));
+    }
+
+    #[test]
+    fn test_contributions() {
+        let front = points(&[&[0.0, 3.0], &[1.0, 1.0], &[2.0, 0.5], &[3.0, 0.0]]);
+        let reference = DVector::from(vec![4.0, 4.0]);
+
+        assert_eq!(vec![1.0, 2.0, 0.5, 0.5], contributions(&front, Some(&reference)));
+
+        // The extremes are kept and the others do not depend on the reference
+        let without_reference = contributions(&front, None);
+        assert_eq!(f64::INFINITY, without_reference[0]);
+        assert_eq!(f64::INFINITY, without_reference[3]);
+        assert_approx_eq!(2.0, without_reference[1]);
+        assert_approx_eq!(0.5, without_reference[2]);
+    }
+}
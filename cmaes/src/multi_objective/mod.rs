@@ -0,0 +1,472 @@
+//! Multi-objective optimization with MO-CMA-ES. See [`MOCMAES`] for full documentation.
+//!
+//! Instead of a single value, the objective function returns the value of each of several
+//! objectives (see [`MultiObjectiveFunction`]). There is usually no point that is best in every
+//! objective, so the result is a set of trade-offs: the points that are not dominated by any other
+//! point found (no other point is at least as good in every objective and better in one). These are
+//! collected in a [`ParetoArchive`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cmaes::multi_objective::MOCMAESOptions;
+//! use cmaes::DVector;
+//!
+//! // The points between (-1, ..., -1) and (1, ..., 1) are the trade-offs between the distances to
+//! // both
+//! let two_spheres = |x: &DVector<f64>| {
+//!     DVector::from(vec![(x.add_scalar(-1.0)).norm_squared(), (x.add_scalar(1.0)).norm_squared()])
+//! };
+//!
+//! let mut mocmaes = MOCMAESOptions::new(vec![0.0; 10], 0.5)
+//!     .population_size(50)
+//!     .max_function_evals(50_000)
+//!     .build(two_spheres)
+//!     .unwrap();
+//!
+//! let results = mocmaes.run();
+//! for individual in &results.pareto_front {
+//!     println!("{} {}", individual.values[0], individual.values[1]);
+//! }
+//! ```
+//!
+//! # Citations
+//!
+//! Igel, Christian, Hansen, Nikolaus, and Roth, Stefan. “Covariance Matrix Adaptation for
+//! Multi-objective Optimization.” Evolutionary Computation, vol. 15, no. 1, 2007, pp. 1-28,
+//! <https://doi.org/10.1162/evco.2007.15.1.1>.
+
+mod archive;
+mod hypervolume;
+mod options;
+mod selection;
+
+pub use archive::ParetoArchive;
+pub use hypervolume::hypervolume;
+pub use options::{InvalidMOOptionsError, MOCMAESOptions};
+
+use nalgebra::DVector;
+use rand::distributions::Distribution;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use statrs::distribution::Normal;
+
+use std::time::{Duration, Instant};
+
+use crate::matrix::SquareMatrix;
+use crate::objective_function::{BatchMultiObjectiveFunction, MultiObjectiveFunction};
+use crate::TerminationReason;
+
+/// A point with the value of each objective at it.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MOIndividual {
+    pub point: DVector<f64>,
+    pub values: DVector<f64>,
+}
+
+/// Data returned when a [`MOCMAES`] terminates.
+#[derive(Clone, Debug)]
+pub struct MOTerminationData {
+    /// The individuals of the [`ParetoArchive`], which are the best trade-offs between the
+    /// objectives found. Empty if the algorithm terminated in the first generation with
+    /// [`TerminationReason::InvalidFunctionValue`].
+    pub pareto_front: Vec<MOIndividual>,
+    pub reasons: Vec<TerminationReason>,
+}
+
+/// Constant parameters of the (1+1)-CMA-ES of every individual
+#[derive(Clone, Debug)]
+struct Parameters {
+    /// Step size damping
+    d: f64,
+    /// Target success rate
+    p_target: f64,
+    /// Learning rate of the success rate
+    c_p: f64,
+    /// Learning rate of the evolution path
+    c_c: f64,
+    /// Learning rate of the covariance matrix
+    c_cov: f64,
+    /// Success rate above which the evolution path is not updated with the step
+    p_thresh: f64,
+}
+
+impl Parameters {
+    fn new(dim: usize) -> Self {
+        let n = dim as f64;
+        let p_target = 1.0 / (5.0 + 0.5);
+
+        Self {
+            d: 1.0 + n / 2.0,
+            p_target,
+            c_p: p_target / (2.0 + p_target),
+            c_c: 2.0 / (n + 2.0),
+            c_cov: 2.0 / (n.powi(2) + 6.0),
+            p_thresh: 0.44,
+        }
+    }
+}
+
+/// An individual of the population with the state of its own (1+1)-CMA-ES
+#[derive(Clone, Debug)]
+struct Member {
+    individual: MOIndividual,
+    /// The objective values converted so that every objective is minimized
+    costs: DVector<f64>,
+    sigma: f64,
+    cov: SquareMatrix<f64>,
+    /// Lower triangular `A` with `cov = A * A^T`, used to sample steps
+    cholesky: SquareMatrix<f64>,
+    /// Evolution path used to update the covariance matrix
+    path_c: DVector<f64>,
+    /// Smoothed rate of successful offspring
+    success_rate: f64,
+}
+
+impl Member {
+    /// Updates the success rate and step size with whether the latest offspring was selected
+    fn update_step_size(&mut self, success: bool, params: &Parameters) {
+        let success = if success { 1.0 } else { 0.0 };
+        self.success_rate = (1.0 - params.c_p) * self.success_rate + params.c_p * success;
+        self.sigma *= ((self.success_rate - params.p_target) / (params.d * (1.0 - params.p_target))).exp();
+    }
+
+    /// Updates the evolution path and covariance matrix with the unscaled step to a successful
+    /// offspring. Keeps the old matrix if the new one is not positive definite.
+    fn update_covariance(&mut self, step: &DVector<f64>, params: &Parameters) {
+        let c_c = params.c_c;
+        let c_cov = params.c_cov;
+
+        let cov = if self.success_rate < params.p_thresh {
+            self.path_c = (1.0 - c_c) * &self.path_c + (c_c * (2.0 - c_c)).sqrt() * step;
+            (1.0 - c_cov) * &self.cov + c_cov * &self.path_c * self.path_c.transpose()
+        } else {
+            // The step is not added while the step size is increasing quickly
+            self.path_c *= 1.0 - c_c;
+            (1.0 - c_cov) * &self.cov + c_cov * (&self.path_c * self.path_c.transpose() + c_c * (2.0 - c_c) * &self.cov)
+        };
+
+        if let Some(cholesky) = cov.clone().cholesky() {
+            self.cholesky = cholesky.l();
+            self.cov = cov;
+        }
+    }
+
+    /// Returns the largest standard deviation of the distribution in a coordinate
+    fn max_std(&self) -> f64 {
+        self.sigma * self.cov.diagonal().max().sqrt()
+    }
+}
+
+/// A type that runs MO-CMA-ES. Use [`MOCMAESOptions`] to create a `MOCMAES`.
+///
+/// This is the generational (mu + mu)-MO-CMA-ES of Igel et al. (2007). Every individual of the
+/// population has its own (1+1)-CMA-ES distribution and produces one offspring per generation.
+/// The best `population_size` of the parents and offspring survive: the points are sorted into
+/// fronts by Pareto dominance, and the last front that fits partially is reduced by repeatedly
+/// removing the point that contributes the least hypervolume to it. An offspring is successful if it ranks better than its parent (it is in an earlier front, or
+/// contributes more to the same front), which adapts the step sizes of both. Successful offspring
+/// also adapt their covariance matrix.
+///
+/// Every evaluated point is offered to the [`ParetoArchive`], which is returned on termination.
+pub struct MOCMAES<F> {
+    objective_function: F,
+    options: MOCMAESOptions,
+    parameters: Parameters,
+    /// Empty until the initial population is evaluated in the first generation
+    population: Vec<Member>,
+    archive: ParetoArchive,
+    /// The `TolX` termination criterion
+    tol_x: f64,
+    rng: ChaCha12Rng,
+    generation: usize,
+    function_evals: usize,
+    /// The time at which the `MOCMAES` was created
+    time_created: Instant,
+}
+
+impl<F> MOCMAES<F> {
+    /// Initializes a `MOCMAES` from a set of [`MOCMAESOptions`]. [`MOCMAESOptions::build`] should
+    /// generally be used instead.
+    pub fn new(objective_function: F, options: MOCMAESOptions) -> Result<Self, InvalidMOOptionsError> {
+        options::validate(&options)?;
+
+        let seed = options.seed.unwrap_or_else(rand::random);
+
+        Ok(Self {
+            objective_function,
+            parameters: Parameters::new(options.initial_mean.len()),
+            population: Vec::new(),
+            archive: ParetoArchive::new(options.mode, options.max_archive_size),
+            tol_x: options.tol_x.unwrap_or(1e-12 * options.initial_step_size),
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            generation: 0,
+            function_evals: 0,
+            time_created: Instant::now(),
+            options,
+        })
+    }
+
+    /// Shared logic between `next` and `next_batch`. `evaluate` returns the objective values of
+    /// each point.
+    fn next_internal<E: FnOnce(&mut F, &[DVector<f64>]) -> Vec<DVector<f64>>>(
+        &mut self,
+        evaluate: E,
+    ) -> Option<MOTerminationData> {
+        let dim = self.options.initial_mean.len();
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let sample = |rng: &mut ChaCha12Rng| DVector::from_iterator(dim, (0..dim).map(|_| normal.sample(rng)));
+
+        // The initial population is the initial mean and points around it, and later generations
+        // are one offspring of each individual
+        let (points, steps): (Vec<_>, Vec<_>) = if self.population.is_empty() {
+            (0..self.options.population_size)
+                .map(|k| {
+                    let step = if k == 0 {
+                        DVector::zeros(dim)
+                    } else {
+                        sample(&mut self.rng)
+                    };
+                    (
+                        &self.options.initial_mean + self.options.initial_step_size * &step,
+                        step,
+                    )
+                })
+                .unzip()
+        } else {
+            self.population
+                .iter()
+                .map(|member| {
+                    let step = &member.cholesky * sample(&mut self.rng);
+                    (&member.individual.point + member.sigma * &step, step)
+                })
+                .unzip()
+        };
+
+        let values = evaluate(&mut self.objective_function, &points);
+        assert_eq!(
+            points.len(),
+            values.len(),
+            "the objective function returned {} values for {} points",
+            values.len(),
+            points.len()
+        );
+
+        if values.iter().any(|v| v.iter().any(|x| x.is_nan())) {
+            return Some(self.get_termination_data(vec![TerminationReason::InvalidFunctionValue]));
+        }
+
+        self.function_evals += points.len();
+
+        let offspring = points
+            .into_iter()
+            .zip(values)
+            .map(|(point, values)| {
+                let individual = MOIndividual { point, values };
+                self.archive.insert(individual.clone());
+                individual
+            })
+            .collect::<Vec<_>>();
+
+        if self.population.is_empty() {
+            self.initialize_population(offspring);
+        } else {
+            self.select(offspring, steps);
+        }
+
+        self.generation += 1;
+
+        self.check_termination()
+            .map(|reasons| self.get_termination_data(reasons))
+    }
+
+    /// Creates the population from the evaluated initial points
+    fn initialize_population(&mut self, individuals: Vec<MOIndividual>) {
+        let dim = self.options.initial_mean.len();
+        let objectives = individuals[0].values.len();
+
+        if let Some(reference) = &self.options.reference_point {
+            assert_eq!(
+                objectives,
+                reference.len(),
+                "the reference point has {} objectives, but the objective function returned {}",
+                reference.len(),
+                objectives
+            );
+        }
+
+        self.population = individuals
+            .into_iter()
+            .map(|individual| Member {
+                costs: archive::to_costs(&individual.values, self.options.mode),
+                individual,
+                sigma: self.options.initial_step_size,
+                cov: SquareMatrix::identity(dim, dim),
+                cholesky: SquareMatrix::identity(dim, dim),
+                path_c: DVector::zeros(dim),
+                success_rate: self.parameters.p_target,
+            })
+            .collect();
+    }
+
+    /// Selects the next population from the current population and the `offspring` of each
+    /// individual, which were sampled with the unscaled `steps`
+    fn select(&mut self, offspring: Vec<MOIndividual>, steps: Vec<DVector<f64>>) {
+        let mu = self.population.len();
+        let params = &self.parameters;
+
+        let mut candidates = self.population.clone();
+        candidates.extend(
+            self.population
+                .iter()
+                .zip(offspring)
+                .map(|(parent, individual)| Member {
+                    costs: archive::to_costs(&individual.values, self.options.mode),
+                    individual,
+                    ..parent.clone()
+                }),
+        );
+
+        let costs = candidates.iter().map(|m| m.costs.clone()).collect::<Vec<_>>();
+        let reference = self
+            .options
+            .reference_point
+            .as_ref()
+            .map(|reference| archive::to_costs(reference, self.options.mode));
+        let selected = selection::select(&costs, mu, reference.as_ref());
+
+        let ranks = selection::rank(&costs, reference.as_ref());
+        for (k, step) in steps.iter().enumerate() {
+            let (parent, child) = (ranks[k], ranks[mu + k]);
+            let success = child.0 < parent.0 || (child.0 == parent.0 && child.1 > parent.1);
+
+            candidates[k].update_step_size(success, params);
+
+            let child = &mut candidates[mu + k];
+            child.update_step_size(success, params);
+            if success {
+                child.update_covariance(step, params);
+            }
+        }
+
+        self.population = selected.into_iter().map(|i| candidates[i].clone()).collect();
+    }
+
+    /// Returns the termination reasons that apply to the current state
+    fn check_termination(&self) -> Option<Vec<TerminationReason>> {
+        let options = &self.options;
+        let mut reasons = Vec::new();
+
+        if options.max_function_evals.is_some_and(|max| self.function_evals >= max) {
+            reasons.push(TerminationReason::MaxFunctionEvals);
+        }
+
+        if options.max_generations.is_some_and(|max| self.generation >= max) {
+            reasons.push(TerminationReason::MaxGenerations);
+        }
+
+        if options.max_time.is_some_and(|max| self.elapsed() >= max) {
+            reasons.push(TerminationReason::MaxTime);
+        }
+
+        if self.population.iter().all(|member| member.max_std() < self.tol_x) {
+            reasons.push(TerminationReason::TolX);
+        }
+
+        (!reasons.is_empty()).then_some(reasons)
+    }
+
+    fn get_termination_data(&self, reasons: Vec<TerminationReason>) -> MOTerminationData {
+        MOTerminationData {
+            pareto_front: self.archive.individuals().to_vec(),
+            reasons,
+        }
+    }
+
+    /// Consumes `self` and returns the objective function.
+    pub fn into_objective_function(self) -> F {
+        self.objective_function
+    }
+
+    /// Returns the options the `MOCMAES` was built with.
+    pub fn options(&self) -> &MOCMAESOptions {
+        &self.options
+    }
+
+    /// Returns the number of generations that have been completed.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Returns the number of times the objective function has been evaluated.
+    pub fn function_evals(&self) -> usize {
+        self.function_evals
+    }
+
+    /// Returns the individuals of the current population.
+    pub fn population(&self) -> impl Iterator<Item = &MOIndividual> {
+        self.population.iter().map(|member| &member.individual)
+    }
+
+    /// Returns the step size of each individual of the current population.
+    pub fn step_sizes(&self) -> impl Iterator<Item = f64> + '_ {
+        self.population.iter().map(|member| member.sigma)
+    }
+
+    /// Returns the archive of the non-dominated individuals found so far.
+    pub fn archive(&self) -> &ParetoArchive {
+        &self.archive
+    }
+
+    /// Returns the time at which the `MOCMAES` was created.
+    pub fn time_created(&self) -> Instant {
+        self.time_created
+    }
+
+    /// Returns the time elapsed since the `MOCMAES` was created.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(self.time_created)
+    }
+}
+
+impl<F: MultiObjectiveFunction> MOCMAES<F> {
+    /// Iterates the algorithm until termination. [`next`][Self::next] can be called manually if
+    /// more control over termination is needed.
+    pub fn run(&mut self) -> MOTerminationData {
+        loop {
+            if let Some(data) = self.next() {
+                break data;
+            }
+        }
+    }
+
+    /// Advances to the next generation. Returns `Some` if a termination condition has been reached
+    /// and the algorithm should be stopped.
+    #[allow(clippy::should_implement_trait)]
+    #[must_use]
+    pub fn next(&mut self) -> Option<MOTerminationData> {
+        self.next_internal(|function, points| points.iter().map(|x| function.evaluate(x)).collect())
+    }
+}
+
+impl<F: BatchMultiObjectiveFunction> MOCMAES<F> {
+    /// Like [`run`][Self::run], but evaluates the points of each generation in a batch. Requires
+    /// that `F` implements [`BatchMultiObjectiveFunction`].
+    pub fn run_batch(&mut self) -> MOTerminationData {
+        loop {
+            if let Some(data) = self.next_batch() {
+                break data;
+            }
+        }
+    }
+
+    /// Like [`next`][Self::next], but evaluates the points of each generation in a batch. Requires
+    /// that `F` implements [`BatchMultiObjectiveFunction`].
+    #[must_use]
+    pub fn next_batch(&mut self) -> Option<MOTerminationData> {
+        self.next_internal(|function, points| function.evaluate_batch(points))
+    }
+}
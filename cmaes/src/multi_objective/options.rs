@@ -0,0 +1,182 @@
+//! Types related to initializing a [`MOCMAES`]. See [`MOCMAESOptions`] for full documentation.
+
+use nalgebra::DVector;
+
+use std::time::Duration;
+
+use super::MOCMAES;
+use crate::mode::Mode;
+use crate::options;
+
+/// A builder for [`MOCMAES`]. Used to adjust parameters of the algorithm to each particular
+/// problem and to change other options. See the fields and methods for a full list of options.
+///
+/// # Examples
+///
+/// ```
+/// use cmaes::multi_objective::MOCMAESOptions;
+/// use cmaes::DVector;
+///
+/// let two_spheres = |x: &DVector<f64>| {
+///     DVector::from(vec![(x.add_scalar(-1.0)).norm_squared(), (x.add_scalar(1.0)).norm_squared()])
+/// };
+///
+/// let mocmaes = MOCMAESOptions::new(vec![0.0; 5], 0.5)
+///     .population_size(20)
+///     .max_function_evals(10_000)
+///     .build(two_spheres)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct MOCMAESOptions {
+    /// Mean of the initial population. Its length is the number of dimensions to search.
+    pub initial_mean: DVector<f64>,
+    /// Initial step size of every individual. The initial population is `initial_mean` and points
+    /// sampled around it with this standard deviation.
+    pub initial_step_size: f64,
+    /// The mode to use for every objective. Default value is [`Mode::Minimize`].
+    pub mode: Mode,
+    /// Number of individuals (`mu`), each of which produces one offspring per generation. Default
+    /// value is `4 + floor(3 * ln(dimensions))`.
+    pub population_size: usize,
+    /// Reference point of the hypervolume used for selection, in the same units as the objective
+    /// values. By default the points that are best in an objective are always kept and the
+    /// reference is placed just beyond the worst values of the front.
+    pub reference_point: Option<DVector<f64>>,
+    /// Maximum number of individuals kept in the Pareto archive. When exceeded, the individual that
+    /// contributes the least hypervolume is removed. Default value is `None` (unlimited).
+    pub max_archive_size: Option<usize>,
+    /// The value to use for the
+    /// [`TerminationReason::MaxFunctionEvals`][crate::TerminationReason::MaxFunctionEvals]
+    /// termination criterion. Default value is `None`.
+    pub max_function_evals: Option<usize>,
+    /// The value to use for the
+    /// [`TerminationReason::MaxGenerations`][crate::TerminationReason::MaxGenerations] termination
+    /// criterion. Default value is `None`.
+    pub max_generations: Option<usize>,
+    /// The value to use for the [`TerminationReason::MaxTime`][crate::TerminationReason::MaxTime]
+    /// termination criterion. Default value is `None`.
+    pub max_time: Option<Duration>,
+    /// The value to use for the [`TerminationReason::TolX`][crate::TerminationReason::TolX]
+    /// termination criterion, which is met when the standard deviation of every individual is below
+    /// it in every coordinate. Default value is `1e-12 * initial_step_size` if `None`.
+    pub tol_x: Option<f64>,
+    /// The seed for the RNG used in the algorithm. By default a random seed is used if this field
+    /// is `None`.
+    pub seed: Option<u64>,
+}
+
+impl MOCMAESOptions {
+    /// Creates a new `MOCMAESOptions` with default values. Set individual options using the
+    /// provided methods.
+    pub fn new<V: Into<DVector<f64>>>(initial_mean: V, initial_step_size: f64) -> Self {
+        let initial_mean = initial_mean.into();
+        let dimensions = initial_mean.len();
+        Self {
+            initial_mean,
+            initial_step_size,
+            mode: Mode::Minimize,
+            population_size: 4 + (3.0 * (dimensions as f64).ln()).floor() as usize,
+            reference_point: None,
+            max_archive_size: None,
+            max_function_evals: None,
+            max_generations: None,
+            max_time: None,
+            tol_x: None,
+            seed: None,
+        }
+    }
+
+    /// Changes the optimization mode.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Changes the population size from the default value.
+    pub fn population_size(mut self, population_size: usize) -> Self {
+        self.population_size = population_size;
+        self
+    }
+
+    /// Sets the reference point of the hypervolume used for selection.
+    pub fn reference_point<V: Into<DVector<f64>>>(mut self, reference_point: V) -> Self {
+        self.reference_point = Some(reference_point.into());
+        self
+    }
+
+    /// Limits the number of individuals kept in the Pareto archive.
+    pub fn max_archive_size(mut self, max_archive_size: usize) -> Self {
+        self.max_archive_size = Some(max_archive_size);
+        self
+    }
+
+    /// Changes the maximum number of function evaluations from the default value.
+    pub fn max_function_evals(mut self, max_function_evals: usize) -> Self {
+        self.max_function_evals = Some(max_function_evals);
+        self
+    }
+
+    /// Changes the maximum number of generations from the default value.
+    pub fn max_generations(mut self, max_generations: usize) -> Self {
+        self.max_generations = Some(max_generations);
+        self
+    }
+
+    /// Changes the maximum time from the default value.
+    pub fn max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    /// Changes the value for the `TolX` termination criterion from the default value.
+    pub fn tol_x(mut self, tol_x: f64) -> Self {
+        self.tol_x = Some(tol_x);
+        self
+    }
+
+    /// Sets the seed for the RNG.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Attempts to build the [`MOCMAES`] using the chosen options.
+    pub fn build<F>(self, objective_function: F) -> Result<MOCMAES<F>, InvalidMOOptionsError> {
+        MOCMAES::new(objective_function, self)
+    }
+}
+
+/// Represents invalid options for a [`MOCMAES`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidMOOptionsError {
+    /// The number of dimensions is set to zero.
+    Dimensions,
+    /// The population size is set to zero.
+    PopulationSize,
+    /// The initial step size is negative or non-normal.
+    InitialStepSize,
+    /// The maximum archive size is set to zero.
+    ArchiveSize,
+}
+
+/// Checks that the options are valid
+pub(super) fn validate(options: &MOCMAESOptions) -> Result<(), InvalidMOOptionsError> {
+    if options.initial_mean.is_empty() {
+        return Err(InvalidMOOptionsError::Dimensions);
+    }
+
+    if options.population_size == 0 {
+        return Err(InvalidMOOptionsError::PopulationSize);
+    }
+
+    if !options::is_initial_step_size_valid(options.initial_step_size) {
+        return Err(InvalidMOOptionsError::InitialStepSize);
+    }
+
+    if options.max_archive_size == Some(0) {
+        return Err(InvalidMOOptionsError::ArchiveSize);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Ranking and selection of individuals by Pareto dominance
+
+use nalgebra::DVector;
+
+use super::hypervolume;
+
+/// Returns whether `a` Pareto-dominates `b` (is no worse in every objective and better in at least
+/// one), assuming that every objective is minimized
+pub(crate) fn dominates(a: &DVector<f64>, b: &DVector<f64>) -> bool {
+    let mut better = false;
+
+    for (ai, bi) in a.iter().zip(b.iter()) {
+        if ai > bi {
+            return false;
+        }
+        better |= ai < bi;
+    }
+
+    better
+}
+
+/// Sorts the indices of `costs` into fronts of mutually non-dominated points, where each point is
+/// only dominated by points of earlier fronts
+pub(crate) fn non_dominated_sort(costs: &[DVector<f64>]) -> Vec<Vec<usize>> {
+    let n = costs.len();
+    // The points each point dominates and the number of points that dominate it
+    let mut dominated = vec![Vec::new(); n];
+    let mut domination_counts = vec![0usize; n];
+
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if dominates(&costs[i], &costs[j]) {
+                dominated[i].push(j);
+                domination_counts[j] += 1;
+            } else if dominates(&costs[j], &costs[i]) {
+                dominated[j].push(i);
+                domination_counts[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::new();
+    let mut front = (0..n).filter(|&i| domination_counts[i] == 0).collect::<Vec<_>>();
+
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &i in &front {
+            for &j in &dominated[i] {
+                domination_counts[j] -= 1;
+                if domination_counts[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+
+    fronts
+}
+
+/// Returns the front of each point of `costs` and its hypervolume contribution to the front, by
+/// which points of the same front are compared
+pub(crate) fn rank(costs: &[DVector<f64>], reference: Option<&DVector<f64>>) -> Vec<(usize, f64)> {
+    let mut ranks = vec![(0, 0.0); costs.len()];
+
+    for (front_index, front) in non_dominated_sort(costs).into_iter().enumerate() {
+        let points = front.iter().map(|&i| costs[i].clone()).collect::<Vec<_>>();
+        let contributions = hypervolume::contributions(&points, reference);
+
+        for (i, contribution) in front.into_iter().zip(contributions) {
+            ranks[i] = (front_index, contribution);
+        }
+    }
+
+    ranks
+}
+
+/// Returns the indices of the `count` best points of `costs`. Whole fronts are selected in order,
+/// and the front that does not fit is reduced by repeatedly removing the point that contributes
+/// the least hypervolume to it.
+pub(crate) fn select(costs: &[DVector<f64>], count: usize, reference: Option<&DVector<f64>>) -> Vec<usize> {
+    let mut selected = Vec::with_capacity(count);
+
+    for mut front in non_dominated_sort(costs) {
+        let remaining = count - selected.len();
+        if remaining == 0 {
+            break;
+        }
+
+        if front.len() > remaining {
+            reduce(costs, &mut front, remaining, reference);
+        }
+        selected.extend(front);
+    }
+
+    selected
+}
+
+/// Removes the points that contribute the least hypervolume from `front` until `count` are left
+fn reduce(costs: &[DVector<f64>], front: &mut Vec<usize>, count: usize, reference: Option<&DVector<f64>>) {
+    // Contributions change with every removal
+    while front.len() > count {
+        let points = front.iter().map(|&i| costs[i].clone()).collect::<Vec<_>>();
+        let contributions = hypervolume::contributions(&points, reference);
+        let worst = (0..front.len())
+            .min_by(|&a, &b| contributions[a].total_cmp(&contributions[b]))
+            .unwrap();
+        front.remove(worst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(values: &[&[f64]]) -> Vec<DVector<f64>> {
+        values.iter().map(|v| DVector::from(v.to_vec())).collect()
+    }
+
+    #[test]
+    fn test_dominates() {
+        let a = DVector::from(vec![1.0, 2.0]);
+        let b = DVector::from(vec![1.0, 3.0]);
+        let c = DVector::from(vec![0.0, 4.0]);
+
+        assert!(dominates(&a, &b));
+        assert!(!dominates(&b, &a));
+        assert!(!dominates(&a, &a));
+        assert!(!dominates(&a, &c));
+        assert!(!dominates(&c, &a));
+    }
+
+    #[test]
+    fn test_non_dominated_sort() {
+        let costs = points(&[&[2.0, 2.0], &[1.0, 3.0], &[3.0, 3.0], &[3.0, 1.0], &[4.0, 4.0]]);
+
+        assert_eq!(vec![vec![0, 1, 3], vec![2], vec![4]], non_dominated_sort(&costs));
+    }
+
+    #[test]
+    fn test_select() {
+        let costs = points(&[&[0.0, 3.0], &[1.0, 1.0], &[2.0, 0.5], &[3.0, 0.0], &[4.0, 4.0]]);
+
+        // The dominated point is never selected before the first front is used up, and the point
+        // with the smallest contribution is removed from it
+        let mut selected = select(&costs, 3, None);
+        selected.sort();
+        assert_eq!(vec![0, 1, 3], selected);
+
+        assert_eq!(5, select(&costs, 5, None).len());
+
+        let ranks = rank(&costs, None);
+        assert_eq!(0, ranks[1].0);
+        assert!(ranks[1].1 > ranks[2].1);
+        assert_eq!(1, ranks[4].0);
+    }
+}
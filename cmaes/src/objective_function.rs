@@ -188,6 +188,51 @@ impl BatchObjectiveFunction for Box<dyn BatchObjectiveFunction> {
     }
 }
 
+/// Like [`ObjectiveFunction`], but for functions with several objectives that are optimized by a
+/// [`MOCMAES`][crate::multi_objective::MOCMAES]. Returns the value of each objective, which must
+/// be the same number of values for every point.
+///
+/// ```
+/// use cmaes::DVector;
+///
+/// // Distances to two points, whose Pareto set is the line segment between them
+/// let two_spheres = |x: &DVector<f64>| {
+///     DVector::from(vec![(x.add_scalar(-1.0)).norm_squared(), (x.add_scalar(1.0)).norm_squared()])
+/// };
+/// ```
+pub trait MultiObjectiveFunction {
+    fn evaluate(&mut self, x: &DVector<f64>) -> DVector<f64>;
+}
+
+impl<F: FnMut(&DVector<f64>) -> DVector<f64>> MultiObjectiveFunction for F {
+    fn evaluate(&mut self, x: &DVector<f64>) -> DVector<f64> {
+        (self)(x)
+    }
+}
+
+impl MultiObjectiveFunction for Box<dyn MultiObjectiveFunction> {
+    fn evaluate(&mut self, x: &DVector<f64>) -> DVector<f64> {
+        self.as_mut().evaluate(x)
+    }
+}
+
+/// Like [`MultiObjectiveFunction`], but for batch objective function evaluation.
+pub trait BatchMultiObjectiveFunction {
+    fn evaluate_batch(&self, xs: &[DVector<f64>]) -> Vec<DVector<f64>>;
+}
+
+impl<F: Fn(&[DVector<f64>]) -> Vec<DVector<f64>>> BatchMultiObjectiveFunction for F {
+    fn evaluate_batch(&self, x: &[DVector<f64>]) -> Vec<DVector<f64>> {
+        (self)(x)
+    }
+}
+
+impl BatchMultiObjectiveFunction for Box<dyn BatchMultiObjectiveFunction> {
+    fn evaluate_batch(&self, x: &[DVector<f64>]) -> Vec<DVector<f64>> {
+        self.as_ref().evaluate_batch(x)
+    }
+}
+
 /// A type that wraps any [`ObjectiveFunction`] and scales the input vectors before passing them to
 /// the wrapped function.
 ///
@@ -0,0 +1,77 @@
+//! Callbacks that follow the progress of a run. See [`Observer`].
+
+use nalgebra::DVector;
+
+use std::fmt;
+use std::ops::ControlFlow;
+
+/// The state of a [`CMAES`][crate::CMAES] after a generation, passed to its [`Observer`].
+#[derive(Clone, Debug)]
+pub struct Progress<'a> {
+    /// Generations completed.
+    pub generation: usize,
+    /// Function evaluations made, including those of a restored run.
+    pub function_evals: usize,
+    /// Overall standard deviation of the distribution.
+    pub sigma: f64,
+    /// Ratio of the longest to the shortest axis of the distribution.
+    pub axis_ratio: f64,
+    /// Best function value of the latest generation.
+    pub best_value: f64,
+    /// Median function value of the latest generation.
+    pub median_value: f64,
+    /// Best point of the latest generation.
+    pub best_point: &'a DVector<f64>,
+    /// Best function value of any generation.
+    pub overall_best_value: f64,
+}
+
+/// Called by a [`CMAES`][crate::CMAES] after every generation, including the last one, with the
+/// [`Progress`] of the run. Returning [`ControlFlow::Break`] stops the run with
+/// [`TerminationReason::Stopped`][crate::TerminationReason::Stopped], which can be used to abort a
+/// run on an external signal.
+///
+/// Implemented for closures, so an observer can be set directly with
+/// [`CMAESOptions::observer`][crate::CMAESOptions::observer]:
+///
+/// ```
+/// use cmaes::{CMAESOptions, DVector, TerminationReason};
+/// use std::ops::ControlFlow;
+///
+/// let sphere = |x: &DVector<f64>| x.magnitude();
+/// let mut cmaes_state = CMAESOptions::new(vec![1.0; 4], 1.0)
+///     .observer(|progress: &cmaes::Progress| {
+///         println!("{}: {:e}", progress.generation, progress.best_value);
+///         if progress.generation < 10 {
+///             ControlFlow::Continue(())
+///         } else {
+///             ControlFlow::Break(())
+///         }
+///     })
+///     .build(sphere)
+///     .unwrap();
+///
+/// let results = cmaes_state.run();
+/// assert_eq!(results.reasons, [TerminationReason::Stopped]);
+/// ```
+///
+/// An observer is shared by every clone of the options it was set on, so it must be `Send` and
+/// `Sync`. Observers that keep state need interior mutability.
+pub trait Observer: Send + Sync {
+    fn observe(&self, progress: &Progress) -> ControlFlow<()>;
+}
+
+impl<F> Observer for F
+where
+    F: Fn(&Progress) -> ControlFlow<()> + Send + Sync,
+{
+    fn observe(&self, progress: &Progress) -> ControlFlow<()> {
+        self(progress)
+    }
+}
+
+impl fmt::Debug for dyn Observer {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Observer")
+    }
+}
@@ -2,12 +2,17 @@
 
 use nalgebra::DVector;
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::boundary::BoundaryHandling;
+use crate::integer::IntegerCoordinates;
 use crate::mode::Mode;
-use crate::parameters::Weights;
+use crate::observer::Observer;
+use crate::parameters::{CovarianceModel, Weights};
 use crate::sampling::Bounds;
 use crate::sampling::Constraints;
+use crate::uncertainty::UncertaintyHandling;
 use crate::CMAES;
 
 /// A builder for [`CMAES`]. Used to adjust parameters of the algorithm to each particular
@@ -57,9 +62,19 @@ pub struct CMAESOptions {
     /// How many times to resample points in order to stay inside `bounds`.
     /// `None` disables the limit. Defaults to 10.
     pub max_resamples: Option<usize>,
+    /// How points outside of the bounds are handled. Default value is
+    /// [`BoundaryHandling::Resample`], which also applies to constraints other than [`Bounds`].
+    /// The other modes require the constraints to be set with [`bounds`][Self::bounds] and never
+    /// resample.
+    pub boundary_handling: BoundaryHandling,
     /// The distribution to use when assigning weights to individuals. Default value is
     /// [`Weights::Negative`].
     pub weights: Weights,
+    /// The representation of the covariance matrix. Default value is [`CovarianceModel::Full`].
+    ///
+    /// [`CovarianceModel::Separable`] and [`CovarianceModel::LowRank`] scale to much higher
+    /// dimensions than the full model, which stores and eigendecomposes an `N x N` matrix.
+    pub covariance_model: CovarianceModel,
     /// Whether to perform the state update in parallel using multiple threads. Default value is
     /// `false`.
     ///
@@ -72,6 +87,14 @@ pub struct CMAESOptions {
     /// The learning rate for adapting the mean. Can be reduced for noisy functions. Default value
     /// is `1.0`.
     pub cm: f64,
+    /// Enables the uncertainty handling of UH-CMA-ES for noisy objective functions, which measures
+    /// how much the noise changes the ranking of the points and increases the step size or the
+    /// number of evaluations per point while it dominates. Default value is `None` (disabled).
+    pub uncertainty_handling: Option<UncertaintyHandling>,
+    /// Coordinates that only take integer values. They are rounded before evaluation, and the
+    /// margin of CMA-ES with margin keeps them from fixing to one value. Default value is `None`
+    /// (all coordinates are continuous).
+    pub integer_coordinates: Option<IntegerCoordinates>,
     /// The value to use for the
     /// [`TerminationReason::MaxFunctionEvals`][crate::TerminationReason::MaxFunctionEvals]
     /// termination criterion. Default value is `None`.
@@ -124,6 +147,9 @@ pub struct CMAESOptions {
     /// [`CMAES::print_info`] call. Default value is `None`, meaning no info will be
     /// automatically printed.
     pub print_gap_evals: Option<usize>,
+    /// Called after every generation with the progress of the run, and can stop the run early.
+    /// Default value is `None`.
+    pub observer: Option<Arc<dyn Observer>>,
 }
 
 impl CMAESOptions {
@@ -145,9 +171,13 @@ impl CMAESOptions {
             population_size: 4 + (3.0 * (dimensions as f64).ln()).floor() as usize,
             constraints: None,
             max_resamples: Some(10),
+            boundary_handling: BoundaryHandling::Resample,
             weights: Weights::Negative,
+            covariance_model: CovarianceModel::Full,
             parallel_update: false,
             cm: 1.0,
+            uncertainty_handling: None,
+            integer_coordinates: None,
             max_function_evals: None,
             max_generations: None,
             max_time: None,
@@ -162,6 +192,7 @@ impl CMAESOptions {
             seed: None,
 
             print_gap_evals: None,
+            observer: None,
         }
     }
 
@@ -208,6 +239,13 @@ impl CMAESOptions {
         self
     }
 
+    /// Changes how points outside of the bounds are handled from the default value. See
+    /// [`BoundaryHandling`] for possible modes.
+    pub fn boundary_handling(mut self, boundary_handling: BoundaryHandling) -> Self {
+        self.boundary_handling = boundary_handling;
+        self
+    }
+
     /// Changes the weight distribution from the default value. See [`Weights`] for
     /// possible distributions.
     pub fn weights(mut self, weights: Weights) -> Self {
@@ -215,6 +253,13 @@ impl CMAESOptions {
         self
     }
 
+    /// Changes the covariance matrix representation from the default value. See
+    /// [`CovarianceModel`] for possible representations.
+    pub fn covariance_model(mut self, covariance_model: CovarianceModel) -> Self {
+        self.covariance_model = covariance_model;
+        self
+    }
+
     /// Sets whether to perform the state update in parallel.
     pub fn parallel_update(mut self, parallel_update: bool) -> Self {
         self.parallel_update = parallel_update;
@@ -228,6 +273,20 @@ impl CMAESOptions {
         self
     }
 
+    /// Enables uncertainty handling for noisy objective functions. See [`UncertaintyHandling`]
+    /// for the options.
+    pub fn uncertainty_handling(mut self, uncertainty_handling: UncertaintyHandling) -> Self {
+        self.uncertainty_handling = Some(uncertainty_handling);
+        self
+    }
+
+    /// Sets the coordinates that only take integer values. See [`IntegerCoordinates`] for the
+    /// options.
+    pub fn integer_coordinates(mut self, integer_coordinates: IntegerCoordinates) -> Self {
+        self.integer_coordinates = Some(integer_coordinates);
+        self
+    }
+
     /// Changes the value for the `MaxFunctionEvals` termination criterion from the default value
     /// (see [`TerminationReason::MaxFunctionEvals`][crate::TerminationReason::MaxFunctionEvals]).
     pub fn max_function_evals(mut self, max_function_evals: usize) -> Self {
@@ -320,6 +379,12 @@ impl CMAESOptions {
         self
     }
 
+    /// Sets an [`Observer`] to call after every generation, which can also stop the run early.
+    pub fn observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
     /// Attempts to build the [`CMAES`] using the chosen options.
     pub fn build<F>(self, objective_function: F) -> Result<CMAES<F>, InvalidOptionsError> {
         CMAES::new(objective_function, self)
@@ -337,6 +402,30 @@ pub enum InvalidOptionsError {
     InitialStepSize,
     /// The learning rate is outside the valid range (`0.0` to `1.0`).
     Cm,
+    /// The rank of [`CovarianceModel::LowRank`] is zero.
+    CovarianceRank,
+    /// The bounds do not match the number of dimensions, a lower bound is greater than its upper
+    /// bound, or a [`BoundaryHandling`] mode other than `Resample` is used without bounds.
+    Bounds,
+    /// An option of the [`UncertaintyHandling`] is outside of its valid range.
+    UncertaintyHandling,
+    /// An index of the [`IntegerCoordinates`] is out of range or repeated, the margin is outside
+    /// of its valid range, or the bounds of an integer coordinate contain no integer.
+    IntegerCoordinates,
+}
+
+/// Returns whether the bounds are valid and are set if required by `boundary_handling`
+pub(crate) fn are_bounds_valid(
+    bounds: Option<&Bounds>,
+    dimensions: usize,
+    boundary_handling: BoundaryHandling,
+) -> bool {
+    match bounds {
+        Some(Bounds { lower, upper }) => {
+            lower.len() == dimensions && upper.len() == dimensions && lower.iter().zip(upper).all(|(l, u)| l <= u)
+        }
+        None => boundary_handling == BoundaryHandling::Resample,
+    }
 }
 
 /// Returns whether the initial step size is valid (greater than zero and normal)
@@ -396,5 +485,37 @@ mod tests {
                 .build(dummy_function),
             Err(InvalidOptionsError::Cm),
         ));
+        assert!(matches!(
+            CMAESOptions::new(vec![1.0; 5], 1.0)
+                .covariance_model(CovarianceModel::LowRank { rank: 0 })
+                .build(dummy_function),
+            Err(InvalidOptionsError::CovarianceRank),
+        ));
+        assert!(CMAESOptions::new(vec![1.0; 5], 1.0)
+            .covariance_model(CovarianceModel::LowRank { rank: 1 })
+            .build(dummy_function)
+            .is_ok());
+        assert!(matches!(
+            CMAESOptions::new(vec![1.0; 5], 1.0)
+                .uncertainty_handling(UncertaintyHandling {
+                    reevaluations: Some(100.0),
+                    ..Default::default()
+                })
+                .build(dummy_function),
+            Err(InvalidOptionsError::UncertaintyHandling),
+        ));
+        assert!(matches!(
+            CMAESOptions::new(vec![1.0; 5], 1.0)
+                .uncertainty_handling(UncertaintyHandling {
+                    theta: 0.0,
+                    ..Default::default()
+                })
+                .build(dummy_function),
+            Err(InvalidOptionsError::UncertaintyHandling),
+        ));
+        assert!(CMAESOptions::new(vec![1.0; 5], 1.0)
+            .uncertainty_handling(Default::default())
+            .build(dummy_function)
+            .is_ok());
     }
 }
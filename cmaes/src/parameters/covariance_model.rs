@@ -0,0 +1,39 @@
+//! Selection of the covariance matrix representation.
+
+/// The representation of the covariance matrix of the distribution. The default value is `Full`.
+///
+/// The full model stores and eigendecomposes an `N x N` matrix, which becomes the bottleneck for
+/// large `N` (thousands of dimensions). The other models never store an `N x N` matrix and take
+/// `O(N)` (`Separable`) or `O(rank * N)` (`LowRank`) time and memory per sample, at the cost of
+/// learning fewer dependencies between variables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CovarianceModel {
+    /// A full covariance matrix. Learns arbitrary linear dependencies between variables.
+    #[default]
+    Full,
+    /// A diagonal covariance matrix with learning rates increased by `(N + 2) / 3`. With this
+    /// setting, the algorithm is known as sep-CMA-ES. Works well on separable or nearly separable
+    /// functions and learns the diagonal much faster than the full model in high dimensions.
+    ///
+    /// Based on Ros and Hansen, "A Simple Modification in CMA-ES Achieving Linear Time and Space
+    /// Complexity", 2008.
+    Separable,
+    /// A transform built from `rank` rank-one modifications of the identity, learned from
+    /// evolution paths with increasingly long time horizons. With this setting, the algorithm is
+    /// known as LM-MA-ES. Learns a few dominant search directions in high dimensions, but adapts
+    /// to strongly ill-conditioned functions much more slowly than the other models. The step size
+    /// is adapted with a faster learning rate and without damping as in LM-MA-ES.
+    ///
+    /// The recommended rank is `4 + floor(3 * ln(N))` (see [`default_rank`][Self::default_rank]).
+    ///
+    /// Based on Loshchilov, Glasmachers and Beyer, "Large Scale Black-box Optimization by
+    /// Limited-Memory Matrix Adaptation", 2018.
+    LowRank { rank: usize },
+}
+
+impl CovarianceModel {
+    /// Returns the recommended rank of the `LowRank` model for `dimensions` dimensions.
+    pub fn default_rank(dimensions: usize) -> usize {
+        4 + (3.0 * (dimensions as f64).ln()).floor() as usize
+    }
+}
@@ -1,11 +1,13 @@
 //! Initialization of constant parameters of the algorithm.
 
+mod covariance_model;
 mod weights;
 
 use nalgebra::DVector;
 
 use std::time::Duration;
 
+pub use covariance_model::CovarianceModel;
 pub use weights::Weights;
 
 use crate::mode::Mode;
@@ -82,6 +84,8 @@ pub struct Parameters {
     mu_eff: f64,
     /// Individual weights
     weights: FinalWeights,
+    /// Representation of the covariance matrix
+    covariance_model: CovarianceModel,
     /// Learning rate for rank-one update cumulation
     cc: f64,
     /// Learning rate for rank-one update
@@ -113,14 +117,25 @@ impl Parameters {
         // Covariance matrix adaptation
         let a_cov = 2.0;
         let cc = (4.0 + mu_eff / dim as f64) / (dim as f64 + 4.0 + 2.0 * mu_eff / dim as f64);
-        let c1 = a_cov / ((dim as f64 + 1.3).powi(2) + mu_eff);
-        let cmu =
+        let mut c1 = a_cov / ((dim as f64 + 1.3).powi(2) + mu_eff);
+        let mut cmu =
             (1.0 - c1).min(a_cov * (mu_eff - 2.0 + 1.0 / mu_eff) / ((dim as f64 + 2.0).powi(2) + a_cov * mu_eff / 2.0));
 
+        // A diagonal matrix has far fewer degrees of freedom, so it can be learned faster
+        if options.covariance_model == CovarianceModel::Separable {
+            let factor = (dim as f64 + 2.0) / 3.0;
+            c1 = (c1 * factor).min(1.0);
+            cmu = (cmu * factor).min(1.0 - c1);
+        }
+
         let final_weights = initial_weights.finalize(dim, c1, cmu);
 
         // Step size adaptation
-        let cs = (mu_eff + 2.0) / (dim as f64 + mu_eff + 5.0);
+        let cs = match options.covariance_model {
+            // LM-MA-ES uses a faster rate, limited for low dimensions
+            CovarianceModel::LowRank { .. } => (2.0 * options.population_size as f64 / dim as f64).min(0.5),
+            _ => (mu_eff + 2.0) / (dim as f64 + mu_eff + 5.0),
+        };
         let damp_s = 1.0 + cs + 2.0 * (((mu_eff - 1.0) / (dim as f64 + 1.0)).sqrt() - 1.0).max(0.0);
 
         Parameters {
@@ -131,6 +146,7 @@ impl Parameters {
             initial_sigma: options.initial_step_size,
             mu_eff,
             weights: final_weights,
+            covariance_model: options.covariance_model,
             cc,
             c1,
             cs,
@@ -188,6 +204,11 @@ impl Parameters {
         self.weights.setting()
     }
 
+    /// Returns the representation of the covariance matrix.
+    pub fn covariance_model(&self) -> CovarianceModel {
+        self.covariance_model
+    }
+
     /// Returns the learning rate for rank-one update cumulation `cc`.
     pub fn cc(&self) -> f64 {
         self.cc
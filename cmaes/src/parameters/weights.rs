@@ -5,25 +5,20 @@ use nalgebra::DVector;
 use std::ops::Deref;
 
 /// The distribution of weights for the population. The default value is `Negative`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Weights {
     /// Weights are higher for higher-ranked selected individuals and are zero for the rest of the
     /// population. Usually performs slightly worse than `Negative`.
     Positive,
     /// Similar to `Positive`, but non-selected individuals have negative weights. With this
     /// setting, the algorithm is known as active CMA-ES or aCMA-ES.
+    #[default]
     Negative,
     /// Weights for selected individuals are equal and are zero for the rest of the population. This
     /// setting will likely perform much worse than the others.
     Uniform,
 }
 
-impl Default for Weights {
-    fn default() -> Self {
-        Self::Negative
-    }
-}
-
 /// Initial distribution of weights, before normalization
 #[derive(Clone, Debug)]
 pub(super) struct InitialWeights {
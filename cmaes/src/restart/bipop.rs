@@ -0,0 +1,178 @@
+//! The BIPOP restart strategy. See [`BIPOP`] for full documentation.
+
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+
+use super::ipop::scale_population_size;
+use super::{InvalidRestartOptionsError, RunParameters, Schedule};
+
+/// BIPOP-CMA-ES: alternates between two regimes of restarts, choosing the one that has used fewer
+/// function evaluations so far.
+///
+/// - The large regime restarts like [`IPOP`][super::IPOP], multiplying the population size by
+///   `increase_factor` each time and using the default initial step size.
+/// - The small regime samples `u` uniformly from `[0, 1)` and uses the population size
+///   `default * (0.5 * large / default)^(u^2)` and the initial step size
+///   `default * 10^(-2u)`, where `large` is the latest large population size. Its runs are limited
+///   to half the function evaluations of the latest large regime run.
+///
+/// The first run uses the default population size and counts towards the large regime.
+///
+/// Based on Hansen, "Benchmarking a BI-Population CMA-ES on the BBOB-2009 Function Testbed", 2009.
+#[derive(Clone, Debug)]
+pub struct BIPOP {
+    /// Factor by which the population size of the large regime grows with each of its restarts.
+    /// Default value is `2.0`.
+    pub increase_factor: f64,
+    /// Maximum number of runs in the large regime, including the first run. Restarts stop when it
+    /// is reached. Default value is `10`.
+    pub max_large_runs: usize,
+}
+
+impl Default for BIPOP {
+    fn default() -> Self {
+        Self {
+            increase_factor: 2.0,
+            max_large_runs: 10,
+        }
+    }
+}
+
+impl BIPOP {
+    pub(super) fn validate(&self) -> Result<(), InvalidRestartOptionsError> {
+        if !(self.increase_factor > 1.0 && self.increase_factor.is_finite()) {
+            return Err(InvalidRestartOptionsError::IncreaseFactor);
+        }
+
+        if self.max_large_runs == 0 {
+            return Err(InvalidRestartOptionsError::MaxRuns);
+        }
+
+        Ok(())
+    }
+}
+
+/// Which regime a run belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Regime {
+    Large,
+    Small,
+}
+
+/// State of a BIPOP restart sequence
+pub(super) struct BIPOPSchedule {
+    strategy: BIPOP,
+    large_runs: usize,
+    large_population_size: Option<usize>,
+    large_evals: usize,
+    small_evals: usize,
+    last_large_evals: usize,
+    current: Regime,
+}
+
+impl BIPOPSchedule {
+    pub(super) fn new(strategy: BIPOP) -> Self {
+        Self {
+            strategy,
+            large_runs: 0,
+            large_population_size: None,
+            large_evals: 0,
+            small_evals: 0,
+            last_large_evals: 0,
+            current: Regime::Large,
+        }
+    }
+}
+
+impl Schedule for BIPOPSchedule {
+    fn next_run(
+        &mut self,
+        default_population_size: usize,
+        default_step_size: f64,
+        rng: &mut ChaCha12Rng,
+    ) -> Option<RunParameters> {
+        match self.large_population_size {
+            Some(large_population_size) if self.small_evals < self.large_evals => {
+                let u: f64 = rng.gen();
+                let factor = (0.5 * large_population_size as f64 / default_population_size as f64).powf(u * u);
+
+                self.current = Regime::Small;
+                Some(RunParameters {
+                    population_size: scale_population_size(default_population_size, factor),
+                    initial_step_size: default_step_size * 10f64.powf(-2.0 * u),
+                    max_function_evals: Some((self.last_large_evals / 2).max(1)),
+                })
+            }
+            _ => {
+                if self.large_runs >= self.strategy.max_large_runs {
+                    return None;
+                }
+
+                let population_size = match self.large_population_size {
+                    Some(size) => scale_population_size(size, self.strategy.increase_factor),
+                    None => default_population_size,
+                };
+
+                self.large_runs += 1;
+                self.large_population_size = Some(population_size);
+                self.current = Regime::Large;
+                Some(RunParameters {
+                    population_size,
+                    initial_step_size: default_step_size,
+                    max_function_evals: None,
+                })
+            }
+        }
+    }
+
+    fn record_run(&mut self, function_evals: usize) {
+        match self.current {
+            Regime::Large => {
+                self.large_evals += function_evals;
+                self.last_large_evals = function_evals;
+            }
+            Regime::Small => self.small_evals += function_evals,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_regimes() {
+        let mut schedule = BIPOPSchedule::new(BIPOP {
+            increase_factor: 2.0,
+            max_large_runs: 4,
+        });
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+        let mut large_population_sizes = Vec::new();
+        let mut large_evals = 500;
+
+        while let Some(run) = schedule.next_run(10, 1.0, &mut rng) {
+            match schedule.current {
+                Regime::Large => {
+                    assert_eq!(1.0, run.initial_step_size);
+                    assert_eq!(None, run.max_function_evals);
+                    large_population_sizes.push(run.population_size);
+                    large_evals *= 2;
+                    schedule.record_run(large_evals);
+                }
+                Regime::Small => {
+                    let large_population_size = *large_population_sizes.last().unwrap();
+                    assert!(run.population_size >= 10);
+                    assert!(run.population_size <= (large_population_size / 2).max(10));
+                    assert!(run.initial_step_size > 0.01 && run.initial_step_size <= 1.0);
+                    assert_eq!(Some(large_evals / 2), run.max_function_evals);
+                    schedule.record_run(large_evals / 4);
+                }
+            }
+        }
+
+        assert_eq!(vec![10, 20, 40, 80], large_population_sizes);
+        // The small regime catches up with the large regime before stopping
+        assert!(schedule.small_evals >= schedule.large_evals);
+    }
+}
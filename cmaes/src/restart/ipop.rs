@@ -0,0 +1,109 @@
+//! The IPOP restart strategy. See [`IPOP`] for full documentation.
+
+use rand_chacha::ChaCha12Rng;
+
+use super::{InvalidRestartOptionsError, RunParameters, Schedule};
+
+/// IPOP-CMA-ES: restarts with the default initial step size and a population size multiplied by
+/// `increase_factor` after every run.
+///
+/// Based on Auger and Hansen, "A Restart CMA Evolution Strategy with Increasing Population Size",
+/// 2005.
+#[derive(Clone, Debug)]
+pub struct IPOP {
+    /// Factor by which the population size grows with each restart. Default value is `2.0`.
+    pub increase_factor: f64,
+    /// Maximum number of runs, including the first. Default value is `10`.
+    pub max_runs: usize,
+}
+
+impl Default for IPOP {
+    fn default() -> Self {
+        Self {
+            increase_factor: 2.0,
+            max_runs: 10,
+        }
+    }
+}
+
+impl IPOP {
+    pub(super) fn validate(&self) -> Result<(), InvalidRestartOptionsError> {
+        if !(self.increase_factor > 1.0 && self.increase_factor.is_finite()) {
+            return Err(InvalidRestartOptionsError::IncreaseFactor);
+        }
+
+        if self.max_runs == 0 {
+            return Err(InvalidRestartOptionsError::MaxRuns);
+        }
+
+        Ok(())
+    }
+}
+
+/// State of an IPOP restart sequence
+pub(super) struct IPOPSchedule {
+    strategy: IPOP,
+    runs: usize,
+}
+
+impl IPOPSchedule {
+    pub(super) fn new(strategy: IPOP) -> Self {
+        Self { strategy, runs: 0 }
+    }
+}
+
+impl Schedule for IPOPSchedule {
+    fn next_run(
+        &mut self,
+        default_population_size: usize,
+        default_step_size: f64,
+        _: &mut ChaCha12Rng,
+    ) -> Option<RunParameters> {
+        if self.runs >= self.strategy.max_runs {
+            return None;
+        }
+
+        let population_size = scale_population_size(
+            default_population_size,
+            self.strategy.increase_factor.powi(self.runs as i32),
+        );
+        self.runs += 1;
+
+        Some(RunParameters {
+            population_size,
+            initial_step_size: default_step_size,
+            max_function_evals: None,
+        })
+    }
+
+    fn record_run(&mut self, _: usize) {}
+}
+
+/// `population_size * factor`, rounded down and at least `population_size`
+pub(super) fn scale_population_size(population_size: usize, factor: f64) -> usize {
+    ((population_size as f64 * factor).floor() as usize).max(population_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_population_sizes() {
+        let mut schedule = IPOPSchedule::new(IPOP {
+            increase_factor: 2.0,
+            max_runs: 4,
+        });
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+
+        let runs = std::iter::from_fn(|| schedule.next_run(10, 0.5, &mut rng)).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![10, 20, 40, 80],
+            runs.iter().map(|run| run.population_size).collect::<Vec<_>>()
+        );
+        assert!(runs.iter().all(|run| run.initial_step_size == 0.5));
+        assert!(runs.iter().all(|run| run.max_function_evals.is_none()));
+    }
+}
@@ -0,0 +1,311 @@
+//! Automatic restart strategies for CMA-ES. See [`Restarter`] for full documentation.
+//!
+//! A single CMA-ES run can converge to a local optimum or stagnate. Restart strategies repeatedly
+//! run the algorithm from new initial means while varying the population size and initial step
+//! size, which makes it much more likely to find the global optimum of multimodal functions. The
+//! strategies are:
+//!
+//! - [`IPOP`]: increases the population size with each restart
+//! - [`BIPOP`]: alternates between increasing population sizes and small population sizes with
+//!   varied initial step sizes
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cmaes::restart::{RestartOptions, RestartStrategy};
+//! use cmaes::DVector;
+//!
+//! // The Rastrigin function has many local minima
+//! let rastrigin = |x: &DVector<f64>| {
+//!     10.0 * x.len() as f64
+//!         + x.iter()
+//!             .map(|xi| xi.powi(2) - 10.0 * (2.0 * std::f64::consts::PI * xi).cos())
+//!             .sum::<f64>()
+//! };
+//!
+//! let restarter = RestartOptions::new(10, -5.0..=5.0, RestartStrategy::IPOP(Default::default()))
+//!     .fun_target(1e-10)
+//!     .max_function_evals(1_000_000)
+//!     .build()
+//!     .unwrap();
+//!
+//! let results = restarter.run(|| rastrigin);
+//! println!("{:?}", results.best);
+//! ```
+
+mod bipop;
+mod ipop;
+mod options;
+
+pub use bipop::BIPOP;
+pub use ipop::IPOP;
+pub use options::{InvalidRestartOptionsError, RestartOptions};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use std::time::Instant;
+
+use crate::objective_function::BatchObjectiveFunction;
+use crate::{
+    CMAESOptions, Individual, ObjectiveFunction, ParallelObjectiveFunction, TerminationData, TerminationReason, CMAES,
+};
+
+/// The restart strategy of a [`Restarter`].
+#[derive(Clone, Debug)]
+pub enum RestartStrategy {
+    IPOP(IPOP),
+    BIPOP(BIPOP),
+}
+
+impl RestartStrategy {
+    fn validate(&self) -> Result<(), InvalidRestartOptionsError> {
+        match self {
+            RestartStrategy::IPOP(ipop) => ipop.validate(),
+            RestartStrategy::BIPOP(bipop) => bipop.validate(),
+        }
+    }
+
+    fn schedule(&self) -> Box<dyn Schedule> {
+        match self {
+            RestartStrategy::IPOP(ipop) => Box::new(ipop::IPOPSchedule::new(ipop.clone())),
+            RestartStrategy::BIPOP(bipop) => Box::new(bipop::BIPOPSchedule::new(bipop.clone())),
+        }
+    }
+}
+
+/// Parameters of one run chosen by a strategy
+#[derive(Clone, Debug)]
+struct RunParameters {
+    population_size: usize,
+    initial_step_size: f64,
+    /// Function evaluation limit of the run set by the strategy
+    max_function_evals: Option<usize>,
+}
+
+/// The state of a strategy over a sequence of runs
+trait Schedule {
+    /// Returns the parameters of the next run, or `None` if the strategy has no more runs.
+    fn next_run(
+        &mut self,
+        default_population_size: usize,
+        default_step_size: f64,
+        rng: &mut ChaCha12Rng,
+    ) -> Option<RunParameters>;
+
+    /// Records the number of function evaluations used by the latest run.
+    fn record_run(&mut self, function_evals: usize);
+}
+
+/// Data of a single run of a [`Restarter`].
+#[derive(Clone, Debug)]
+pub struct RunData {
+    pub population_size: usize,
+    pub initial_step_size: f64,
+    pub final_step_size: f64,
+    pub generations: usize,
+    pub function_evals: usize,
+    pub termination_data: TerminationData,
+}
+
+/// Data returned when a [`Restarter`] terminates.
+#[derive(Clone, Debug)]
+pub struct RestartResults {
+    /// The best individual of all runs. Always `Some` unless every run terminated in the first
+    /// generation with
+    /// [`TerminationReason::InvalidFunctionValue`][crate::TerminationReason::InvalidFunctionValue].
+    pub best: Option<Individual>,
+    /// Total number of function evaluations of all runs.
+    pub function_evals: usize,
+    /// Data of each run in order.
+    pub runs: Vec<RunData>,
+}
+
+/// A type that runs CMA-ES repeatedly according to a [`RestartStrategy`]. Use [`RestartOptions`] to
+/// create a `Restarter`.
+///
+/// Runs continue until one reaches `fun_target`, the function evaluation or time limit is hit, or
+/// the strategy has no more runs. Each `run_*` method takes a function that creates the objective
+/// function of each run.
+#[derive(Clone, Debug)]
+pub struct Restarter {
+    options: RestartOptions,
+}
+
+impl Restarter {
+    /// Initializes a `Restarter` from a set of [`RestartOptions`]. [`RestartOptions::build`] should
+    /// generally be used instead.
+    pub fn new(options: RestartOptions) -> Result<Self, InvalidRestartOptionsError> {
+        options::validate(&options)?;
+        options.strategy.validate()?;
+
+        Ok(Self { options })
+    }
+
+    /// Returns the options of the `Restarter`.
+    pub fn options(&self) -> &RestartOptions {
+        &self.options
+    }
+
+    /// Runs the restarts, creating the objective function of each run with `objective_function`.
+    pub fn run<F: ObjectiveFunction, G: FnMut() -> F>(&self, objective_function: G) -> RestartResults {
+        self.run_internal(objective_function, |cmaes| cmaes.run())
+    }
+
+    /// Like [`run`][Self::run], but executes the objective function of each run in parallel using
+    /// multiple threads.
+    pub fn run_parallel<F: ParallelObjectiveFunction, G: FnMut() -> F>(&self, objective_function: G) -> RestartResults {
+        self.run_internal(objective_function, |cmaes| cmaes.run_parallel())
+    }
+
+    /// Like [`run`][Self::run], but executes the objective function of each run in a batch.
+    pub fn run_batch<F: BatchObjectiveFunction, G: FnMut() -> F>(&self, objective_function: G) -> RestartResults {
+        self.run_internal(objective_function, |cmaes| cmaes.run_batch())
+    }
+
+    /// Shared logic between `run`, `run_parallel` and `run_batch`
+    fn run_internal<F, G, R>(&self, mut objective_function: G, mut run: R) -> RestartResults
+    where
+        G: FnMut() -> F,
+        R: FnMut(&mut CMAES<F>) -> TerminationData,
+    {
+        let options = &self.options;
+        let time_created = Instant::now();
+        let mut rng = ChaCha12Rng::seed_from_u64(options.seed.unwrap_or_else(rand::random));
+        let mut schedule = options.strategy.schedule();
+        let default_population_size = CMAESOptions::new(vec![0.0; options.dimensions], 1.0).population_size;
+
+        let mut results = RestartResults {
+            best: None,
+            function_evals: 0,
+            runs: Vec::new(),
+        };
+
+        while let Some(parameters) = schedule.next_run(default_population_size, options.initial_step_size, &mut rng) {
+            let remaining_evals = options
+                .max_function_evals
+                .map(|max_evals| max_evals.saturating_sub(results.function_evals));
+            let remaining_time = options
+                .max_time
+                .map(|max_time| max_time.saturating_sub(time_created.elapsed()));
+
+            if remaining_evals == Some(0) || remaining_time.is_some_and(|time| time.is_zero()) {
+                break;
+            }
+
+            let initial_mean = match &options.initial_mean {
+                Some(mean) => mean.clone(),
+                None => (0..options.dimensions)
+                    .map(|_| rng.gen_range(options.search_range.clone()))
+                    .collect::<Vec<_>>()
+                    .into(),
+            };
+
+            let mut run_options = CMAESOptions::new(initial_mean, parameters.initial_step_size)
+                .mode(options.mode)
+                .covariance_model(options.covariance_model)
+                .boundary_handling(options.boundary_handling)
+                .population_size(parameters.population_size)
+                .tol_fun(options.tol_fun)
+                .tol_fun_rel(options.tol_fun_rel)
+                .tol_fun_hist(options.tol_fun_hist)
+                .tol_x_up(options.tol_x_up)
+                .tol_condition_cov(options.tol_condition_cov)
+                .seed(rng.gen());
+            if let Some(bounds) = &options.bounds {
+                run_options = run_options.bounds(bounds.lower.clone(), bounds.upper.clone());
+            }
+            run_options.integer_coordinates = options.integer_coordinates.clone();
+            run_options.fun_target = options.fun_target;
+            run_options.max_generations = options.max_generations_per_run;
+            run_options.max_time = remaining_time;
+            run_options.tol_x = options.tol_x;
+            run_options.tol_stagnation = options.tol_stagnation;
+            run_options.observer = options.observer.clone();
+            run_options.max_function_evals = match (remaining_evals, parameters.max_function_evals) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            // Options are validated when building the `Restarter`
+            let mut cmaes = run_options.build(objective_function()).unwrap();
+            let termination_data = run(&mut cmaes);
+            let function_evals = cmaes.function_evals();
+
+            schedule.record_run(function_evals);
+            results.function_evals += function_evals;
+
+            if let Some(best) = &termination_data.overall_best {
+                let is_better = results
+                    .best
+                    .as_ref()
+                    .is_none_or(|current| options.mode.is_better(best.value, current.value));
+                if is_better {
+                    results.best = Some(best.clone());
+                }
+            }
+
+            let run_data = RunData {
+                population_size: parameters.population_size,
+                initial_step_size: parameters.initial_step_size,
+                final_step_size: cmaes.sigma(),
+                generations: cmaes.generation(),
+                function_evals,
+                termination_data,
+            };
+
+            if options.enable_printing {
+                print_run(results.runs.len(), &run_data);
+            }
+
+            let reasons = &run_data.termination_data.reasons;
+            let finished =
+                reasons.contains(&TerminationReason::FunTarget) || reasons.contains(&TerminationReason::Stopped);
+            results.runs.push(run_data);
+
+            if finished {
+                break;
+            }
+        }
+
+        if options.enable_printing {
+            if let Some(best) = &results.best {
+                println!(
+                    "Restarts finished after {} run(s) and {} function evaluations with best function value {:e}",
+                    results.runs.len(),
+                    results.function_evals,
+                    best.value
+                );
+            }
+        }
+
+        results
+    }
+}
+
+/// Prints a summary of a run
+fn print_run(index: usize, run: &RunData) {
+    let reasons = run
+        .termination_data
+        .reasons
+        .iter()
+        .map(|reason| format!("`{}`", reason))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let best = run
+        .termination_data
+        .overall_best
+        .as_ref()
+        .map(|best| format!("{:e}", best.value))
+        .unwrap_or_default();
+
+    println!(
+        "Run {}: lambda={}, sigma0={:e}, evals={}, best={}, reason(s): {}",
+        index + 1,
+        run.population_size,
+        run.initial_step_size,
+        run.function_evals,
+        best,
+        reasons
+    );
+}
@@ -0,0 +1,429 @@
+//! Types related to initializing a [`Restarter`]. See [`RestartOptions`] for full documentation.
+
+use nalgebra::DVector;
+
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{RestartStrategy, Restarter};
+use crate::boundary::BoundaryHandling;
+use crate::integer::{self, IntegerCoordinates};
+use crate::mode::Mode;
+use crate::observer::Observer;
+use crate::options;
+use crate::parameters::CovarianceModel;
+use crate::sampling::Bounds;
+
+/// A builder for [`Restarter`]. Used to choose the restart strategy and to adjust the parameters
+/// shared by every run. See the fields and methods for a full list of options.
+///
+/// # Examples
+///
+/// ```
+/// use cmaes::restart::{RestartOptions, RestartStrategy, IPOP};
+///
+/// let restarter = RestartOptions::new(10, -5.0..=5.0, RestartStrategy::IPOP(IPOP::default()))
+///     .fun_target(1e-10)
+///     .max_function_evals(100_000)
+///     .seed(1)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct RestartOptions {
+    /// Number of dimensions to search.
+    pub dimensions: usize,
+    /// Range from which the initial mean of each run is sampled uniformly in every coordinate.
+    pub search_range: RangeInclusive<f64>,
+    /// The restart strategy to use.
+    pub strategy: RestartStrategy,
+    /// The mode to use when optimizing the objective function. Default value is [`Mode::Minimize`].
+    pub mode: Mode,
+    /// Initial mean of every run. Overrides sampling from `search_range` if set. Default value is
+    /// `None`.
+    pub initial_mean: Option<DVector<f64>>,
+    /// Initial step size of runs with the default step size. Default value is a quarter of the
+    /// width of `search_range`.
+    pub initial_step_size: f64,
+    /// The covariance matrix representation of every run. Default value is
+    /// [`CovarianceModel::Full`].
+    pub covariance_model: CovarianceModel,
+    /// Bounds of every run, handled according to `boundary_handling`. Independent of
+    /// `search_range`. Default value is `None`.
+    pub bounds: Option<Bounds>,
+    /// See [`CMAESOptions::boundary_handling`][crate::CMAESOptions::boundary_handling]. Default
+    /// value is [`BoundaryHandling::Resample`].
+    pub boundary_handling: BoundaryHandling,
+    /// See [`CMAESOptions::integer_coordinates`][crate::CMAESOptions::integer_coordinates].
+    /// Default value is `None`.
+    pub integer_coordinates: Option<IntegerCoordinates>,
+    /// The value to use for the
+    /// [`TerminationReason::FunTarget`][crate::TerminationReason::FunTarget] termination criterion
+    /// of every run. Reaching it also stops the restarts. Default value is `None`.
+    pub fun_target: Option<f64>,
+    /// Maximum number of function evaluations over all runs. Default value is `None`.
+    pub max_function_evals: Option<usize>,
+    /// The value to use for the
+    /// [`TerminationReason::MaxGenerations`][crate::TerminationReason::MaxGenerations]
+    /// termination criterion of every run. Default value is `None`.
+    pub max_generations_per_run: Option<usize>,
+    /// Maximum time spent over all runs. Default value is `None`.
+    pub max_time: Option<Duration>,
+    /// See [`CMAESOptions::tol_fun`][crate::CMAESOptions::tol_fun]. Default value is `1e-12`.
+    pub tol_fun: f64,
+    /// See [`CMAESOptions::tol_fun_rel`][crate::CMAESOptions::tol_fun_rel]. Default value is `0`
+    /// (disabled).
+    pub tol_fun_rel: f64,
+    /// See [`CMAESOptions::tol_fun_hist`][crate::CMAESOptions::tol_fun_hist]. Default value is
+    /// `1e-12`.
+    pub tol_fun_hist: f64,
+    /// See [`CMAESOptions::tol_x`][crate::CMAESOptions::tol_x]. Default value is `None`.
+    pub tol_x: Option<f64>,
+    /// See [`CMAESOptions::tol_stagnation`][crate::CMAESOptions::tol_stagnation]. Default value is
+    /// `None`.
+    pub tol_stagnation: Option<usize>,
+    /// See [`CMAESOptions::tol_x_up`][crate::CMAESOptions::tol_x_up]. Default value is `1e+8`.
+    pub tol_x_up: f64,
+    /// See [`CMAESOptions::tol_condition_cov`][crate::CMAESOptions::tol_condition_cov]. Default
+    /// value is `1e+14`.
+    pub tol_condition_cov: f64,
+    /// The seed for the RNG used to sample initial means, small population sizes and step sizes, and
+    /// the seed of each run. By default a random seed is used if this field is `None`.
+    pub seed: Option<u64>,
+    /// Whether to print a summary of each run. Default value is `false`.
+    pub enable_printing: bool,
+    /// See [`CMAESOptions::observer`][crate::CMAESOptions::observer]. Shared by every run, and
+    /// stopping a run also stops the restarts. Default value is `None`.
+    pub observer: Option<Arc<dyn Observer>>,
+}
+
+impl RestartOptions {
+    /// Creates a new `RestartOptions` with default values. Set individual options using the
+    /// provided methods.
+    ///
+    /// - `dimensions` is the number of dimensions to search.
+    /// - `search_range` is the range from which the initial mean of each run is sampled in every
+    ///   coordinate. The solution is expected to lie in it.
+    pub fn new(dimensions: usize, search_range: RangeInclusive<f64>, strategy: RestartStrategy) -> Self {
+        let initial_step_size = (search_range.end() - search_range.start()) / 4.0;
+        Self {
+            dimensions,
+            search_range,
+            strategy,
+            mode: Mode::Minimize,
+            initial_mean: None,
+            initial_step_size,
+            covariance_model: CovarianceModel::Full,
+            bounds: None,
+            boundary_handling: BoundaryHandling::Resample,
+            integer_coordinates: None,
+            fun_target: None,
+            max_function_evals: None,
+            max_generations_per_run: None,
+            max_time: None,
+            tol_fun: 1e-12,
+            tol_fun_rel: 0.0,
+            tol_fun_hist: 1e-12,
+            tol_x: None,
+            tol_stagnation: None,
+            tol_x_up: 1e8,
+            tol_condition_cov: 1e14,
+            seed: None,
+            enable_printing: false,
+            observer: None,
+        }
+    }
+
+    /// Changes the optimization mode.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Starts every run from `initial_mean` instead of a random point in the search range.
+    pub fn initial_mean<V: Into<DVector<f64>>>(mut self, initial_mean: V) -> Self {
+        self.initial_mean = Some(initial_mean.into());
+        self
+    }
+
+    /// Changes the default initial step size.
+    pub fn initial_step_size(mut self, initial_step_size: f64) -> Self {
+        self.initial_step_size = initial_step_size;
+        self
+    }
+
+    /// Changes the covariance matrix representation of every run.
+    pub fn covariance_model(mut self, covariance_model: CovarianceModel) -> Self {
+        self.covariance_model = covariance_model;
+        self
+    }
+
+    /// Sets the bounds of every run. Vector length must match the number of dimensions.
+    pub fn bounds(mut self, lower: Vec<f64>, upper: Vec<f64>) -> Self {
+        self.bounds = Some(Bounds { lower, upper });
+        self
+    }
+
+    /// Changes how points outside of the bounds are handled from the default value.
+    pub fn boundary_handling(mut self, boundary_handling: BoundaryHandling) -> Self {
+        self.boundary_handling = boundary_handling;
+        self
+    }
+
+    /// Sets the coordinates that only take integer values in every run.
+    pub fn integer_coordinates(mut self, integer_coordinates: IntegerCoordinates) -> Self {
+        self.integer_coordinates = Some(integer_coordinates);
+        self
+    }
+
+    /// Changes the value for the `FunTarget` termination criterion.
+    pub fn fun_target(mut self, fun_target: f64) -> Self {
+        self.fun_target = Some(fun_target);
+        self
+    }
+
+    /// Changes the maximum number of function evaluations over all runs.
+    pub fn max_function_evals(mut self, max_function_evals: usize) -> Self {
+        self.max_function_evals = Some(max_function_evals);
+        self
+    }
+
+    /// Changes the value for the `MaxGenerations` termination criterion of every run.
+    pub fn max_generations_per_run(mut self, max_generations_per_run: usize) -> Self {
+        self.max_generations_per_run = Some(max_generations_per_run);
+        self
+    }
+
+    /// Changes the maximum time spent over all runs.
+    pub fn max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    /// Changes the value for the `TolFun` termination criterion.
+    pub fn tol_fun(mut self, tol_fun: f64) -> Self {
+        self.tol_fun = tol_fun;
+        self
+    }
+
+    /// Changes the value for the `TolFunRel` termination criterion.
+    pub fn tol_fun_rel(mut self, tol_fun_rel: f64) -> Self {
+        self.tol_fun_rel = tol_fun_rel;
+        self
+    }
+
+    /// Changes the value for the `TolFunHist` termination criterion.
+    pub fn tol_fun_hist(mut self, tol_fun_hist: f64) -> Self {
+        self.tol_fun_hist = tol_fun_hist;
+        self
+    }
+
+    /// Changes the value for the `TolX` termination criterion.
+    pub fn tol_x(mut self, tol_x: f64) -> Self {
+        self.tol_x = Some(tol_x);
+        self
+    }
+
+    /// Changes the value for the `TolStagnation` termination criterion.
+    pub fn tol_stagnation(mut self, tol_stagnation: usize) -> Self {
+        self.tol_stagnation = Some(tol_stagnation);
+        self
+    }
+
+    /// Changes the value for the `TolXUp` termination criterion.
+    pub fn tol_x_up(mut self, tol_x_up: f64) -> Self {
+        self.tol_x_up = tol_x_up;
+        self
+    }
+
+    /// Changes the value for the `TolConditionCov` termination criterion.
+    pub fn tol_condition_cov(mut self, tol_condition_cov: f64) -> Self {
+        self.tol_condition_cov = tol_condition_cov;
+        self
+    }
+
+    /// Sets the seed for the RNG.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Enables or disables printing a summary of each run.
+    pub fn enable_printing(mut self, enable_printing: bool) -> Self {
+        self.enable_printing = enable_printing;
+        self
+    }
+
+    /// Sets an [`Observer`] to call after every generation of every run, which can also stop the
+    /// restarts early.
+    pub fn observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Attempts to build the [`Restarter`] using the chosen options.
+    pub fn build(self) -> Result<Restarter, InvalidRestartOptionsError> {
+        Restarter::new(self)
+    }
+}
+
+/// Represents invalid options for a [`Restarter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidRestartOptionsError {
+    /// The number of dimensions is set to zero.
+    Dimensions,
+    /// The search range is empty or not finite.
+    SearchRange,
+    /// The initial mean does not have `dimensions` elements.
+    InitialMean,
+    /// The initial step size is negative or non-normal.
+    InitialStepSize,
+    /// The rank of [`CovarianceModel::LowRank`] is zero.
+    CovarianceRank,
+    /// The bounds are invalid or missing, see
+    /// [`InvalidOptionsError::Bounds`][crate::options::InvalidOptionsError::Bounds].
+    Bounds,
+    /// The integer coordinates are invalid, see
+    /// [`InvalidOptionsError::IntegerCoordinates`][crate::options::InvalidOptionsError::IntegerCoordinates].
+    IntegerCoordinates,
+    /// The population size increase factor of the strategy is not greater than one.
+    IncreaseFactor,
+    /// The strategy allows no runs.
+    MaxRuns,
+}
+
+/// Checks the options shared by all strategies
+pub(super) fn validate(options: &RestartOptions) -> Result<(), InvalidRestartOptionsError> {
+    if options.dimensions == 0 {
+        return Err(InvalidRestartOptionsError::Dimensions);
+    }
+
+    let (start, end) = (*options.search_range.start(), *options.search_range.end());
+    if !start.is_finite() || !end.is_finite() || start > end {
+        return Err(InvalidRestartOptionsError::SearchRange);
+    }
+
+    if options
+        .initial_mean
+        .as_ref()
+        .is_some_and(|mean| mean.len() != options.dimensions)
+    {
+        return Err(InvalidRestartOptionsError::InitialMean);
+    }
+
+    if !options::is_initial_step_size_valid(options.initial_step_size) {
+        return Err(InvalidRestartOptionsError::InitialStepSize);
+    }
+
+    if options.covariance_model == (CovarianceModel::LowRank { rank: 0 }) {
+        return Err(InvalidRestartOptionsError::CovarianceRank);
+    }
+
+    if !options::are_bounds_valid(options.bounds.as_ref(), options.dimensions, options.boundary_handling) {
+        return Err(InvalidRestartOptionsError::Bounds);
+    }
+
+    if options
+        .integer_coordinates
+        .as_ref()
+        .is_some_and(|integers| !integer::is_valid(integers, options.dimensions, options.bounds.as_ref()))
+    {
+        return Err(InvalidRestartOptionsError::IntegerCoordinates);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restart::{BIPOP, IPOP};
+
+    fn ipop() -> RestartStrategy {
+        RestartStrategy::IPOP(IPOP::default())
+    }
+
+    #[test]
+    fn test_build() {
+        assert!(RestartOptions::new(5, -1.0..=1.0, ipop()).build().is_ok());
+        assert!(
+            RestartOptions::new(5, -1.0..=1.0, RestartStrategy::BIPOP(BIPOP::default()))
+                .build()
+                .is_ok()
+        );
+        assert!(matches!(
+            RestartOptions::new(0, -1.0..=1.0, ipop()).build(),
+            Err(InvalidRestartOptionsError::Dimensions),
+        ));
+        assert!(matches!(
+            RestartOptions::new(5, 1.0..=-1.0, ipop()).build(),
+            Err(InvalidRestartOptionsError::SearchRange),
+        ));
+        assert!(matches!(
+            RestartOptions::new(5, f64::NEG_INFINITY..=1.0, ipop()).build(),
+            Err(InvalidRestartOptionsError::SearchRange),
+        ));
+        assert!(matches!(
+            RestartOptions::new(5, 0.0..=0.0, ipop()).build(),
+            Err(InvalidRestartOptionsError::InitialStepSize),
+        ));
+        assert!(RestartOptions::new(5, 0.0..=0.0, ipop())
+            .initial_step_size(1.0)
+            .build()
+            .is_ok());
+        assert!(matches!(
+            RestartOptions::new(5, -1.0..=1.0, ipop())
+                .initial_mean(vec![0.0; 4])
+                .build(),
+            Err(InvalidRestartOptionsError::InitialMean),
+        ));
+        assert!(matches!(
+            RestartOptions::new(5, -1.0..=1.0, ipop())
+                .covariance_model(CovarianceModel::LowRank { rank: 0 })
+                .build(),
+            Err(InvalidRestartOptionsError::CovarianceRank),
+        ));
+        assert!(RestartOptions::new(5, -1.0..=1.0, ipop())
+            .bounds(vec![-1.0; 5], vec![1.0; 5])
+            .boundary_handling(BoundaryHandling::Reflect)
+            .build()
+            .is_ok());
+        assert!(matches!(
+            RestartOptions::new(5, -1.0..=1.0, ipop())
+                .boundary_handling(BoundaryHandling::Reflect)
+                .build(),
+            Err(InvalidRestartOptionsError::Bounds),
+        ));
+        assert!(matches!(
+            RestartOptions::new(5, -1.0..=1.0, ipop())
+                .integer_coordinates(IntegerCoordinates::new(vec![5]))
+                .build(),
+            Err(InvalidRestartOptionsError::IntegerCoordinates),
+        ));
+        assert!(matches!(
+            RestartOptions::new(
+                5,
+                -1.0..=1.0,
+                RestartStrategy::IPOP(IPOP {
+                    increase_factor: 1.0,
+                    ..Default::default()
+                })
+            )
+            .build(),
+            Err(InvalidRestartOptionsError::IncreaseFactor),
+        ));
+        assert!(matches!(
+            RestartOptions::new(
+                5,
+                -1.0..=1.0,
+                RestartStrategy::BIPOP(BIPOP {
+                    max_large_runs: 0,
+                    ..Default::default()
+                })
+            )
+            .build(),
+            Err(InvalidRestartOptionsError::MaxRuns),
+        ));
+    }
+}
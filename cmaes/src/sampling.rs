@@ -7,14 +7,24 @@ use rand_chacha::ChaCha12Rng;
 use rayon::prelude::*;
 use statrs::distribution::Normal;
 
+use crate::boundary::BoundaryRepair;
+use crate::integer::Margin;
 use crate::mode::Mode;
 use crate::objective_function::BatchObjectiveFunction;
+use crate::snapshot::RngSnapshot;
 use crate::state::State;
+use crate::uncertainty::{EvaluationPlan, UncertaintyHandler, UncertaintyState};
 use crate::{ObjectiveFunction, ParallelObjectiveFunction};
 
 pub trait Constraints: Sync + std::fmt::Debug {
     fn meets_constraints(&self, x: &DVector<f64>) -> bool;
     fn clone_box(&self) -> Box<dyn Constraints>;
+
+    /// Returns the box bounds the constraints consist of, if any. Required by the repairing
+    /// [`BoundaryHandling`][crate::BoundaryHandling] modes.
+    fn bounds(&self) -> Option<&Bounds> {
+        None
+    }
 }
 
 impl Clone for Box<dyn Constraints> {
@@ -37,6 +47,10 @@ impl Constraints for Bounds {
     fn clone_box(&self) -> Box<dyn Constraints> {
         Box::new(self.clone())
     }
+
+    fn bounds(&self) -> Option<&Bounds> {
+        Some(self)
+    }
 }
 
 /// A type for sampling and evaluating points from the distribution for each generation
@@ -45,6 +59,12 @@ pub struct Sampler<F> {
     dim: usize,
     /// If set, resamples until all points satisfy the constraints
     constraints: Option<Box<dyn Constraints>>,
+    /// If set, repairs points outside of the bounds instead of resampling them
+    boundary: Option<BoundaryRepair>,
+    /// If set, reevaluates points to measure the noise of the objective function
+    uncertainty: Option<UncertaintyHandler>,
+    /// If set, rounds the integer coordinates of points
+    margin: Option<Margin>,
     /// The maximum number of resamples.
     /// If this limit is hit, uses points even if they violate the constraints
     max_resamples: Option<usize>,
@@ -62,6 +82,7 @@ impl<F> Sampler<F> {
     pub fn new(
         dim: usize,
         constraints: Option<Box<dyn Constraints>>,
+        boundary: Option<BoundaryRepair>,
         max_resamples: Option<usize>,
         population_size: usize,
         objective_function: F,
@@ -70,6 +91,9 @@ impl<F> Sampler<F> {
         Self {
             dim,
             constraints,
+            boundary,
+            uncertainty: None,
+            margin: None,
             max_resamples,
             population_size,
             rng: ChaCha12Rng::seed_from_u64(rng_seed),
@@ -78,14 +102,69 @@ impl<F> Sampler<F> {
         }
     }
 
+    /// Enables uncertainty handling
+    pub fn with_uncertainty(mut self, uncertainty: Option<UncertaintyHandler>) -> Self {
+        self.uncertainty = uncertainty;
+        self
+    }
+
+    /// Enables integer coordinates
+    pub fn with_margin(mut self, margin: Option<Margin>) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn margin(&self) -> Option<&Margin> {
+        self.margin.as_ref()
+    }
+
     /// Shared logic between `sample` and `sample_parallel`
-    fn sample_internal<P: Fn(Vec<DVector<f64>>, &mut F) -> Result<Vec<EvaluatedPoint>, InvalidFunctionValueError>>(
+    fn sample_internal<P: Fn(Vec<&DVector<f64>>, &mut F) -> Vec<f64>>(
         &mut self,
         state: &State,
         mode: Mode,
         parallel_update: bool,
         evaluate_points: P,
     ) -> Result<Vec<EvaluatedPoint>, InvalidFunctionValueError> {
+        let candidates = self.sample_candidates(state, parallel_update);
+        let plan = self.plan_evaluations();
+        let values = evaluate_points(plan.points(&candidates), &mut self.objective_function);
+
+        self.evaluate_candidates(state, mode, candidates, plan, &values)
+    }
+
+    /// Returns how the points of a new generation are evaluated
+    pub fn plan_evaluations(&mut self) -> EvaluationPlan {
+        match &self.uncertainty {
+            Some(uncertainty) => uncertainty.plan(self.population_size, &mut self.rng),
+            None => EvaluationPlan::single(self.population_size),
+        }
+    }
+
+    /// Samples a new generation of points without evaluating them, resampling according to the
+    /// constraints or repairing points outside of the bounds, and rounding integer coordinates
+    pub fn sample_candidates(&mut self, state: &State, parallel_update: bool) -> Vec<Candidate> {
+        let y = self.sample_steps(state, parallel_update);
+
+        let mut candidates = match &self.boundary {
+            Some(boundary) => y.into_iter().map(|yk| boundary.candidate(yk, state)).collect(),
+            None => y
+                .into_iter()
+                .map(|yk| Candidate::new(state.to_point(&yk), yk))
+                .collect::<Vec<_>>(),
+        };
+
+        if let Some(margin) = &self.margin {
+            for candidate in &mut candidates {
+                margin.encode(&mut candidate.point);
+            }
+        }
+
+        candidates
+    }
+
+    /// Samples the unscaled steps from the mean of a new generation of points
+    fn sample_steps(&mut self, state: &State, parallel_update: bool) -> Vec<DVector<f64>> {
         let normal = Normal::new(0.0, 1.0).unwrap();
 
         // Random steps in the distribution N(0, I)
@@ -93,10 +172,10 @@ impl<F> Sampler<F> {
             let z = (0..n)
                 .map(|_| DVector::from_iterator(self.dim, (0..self.dim).map(|_| normal.sample(&mut self.rng))))
                 .collect::<Vec<_>>();
-            let transform = |zk| state.cov_transform() * zk;
+            let transform = |zk| state.cov().transform(&zk);
 
             let ok_constraints = |yk: &DVector<f64>| match constraints {
-                Some(constraints) => constraints.meets_constraints(&to_point(yk, state.mean(), state.sigma())),
+                Some(constraints) => constraints.meets_constraints(&state.to_point(yk)),
                 None => true,
             };
 
@@ -129,18 +208,89 @@ impl<F> Sampler<F> {
             i += 1;
         }
 
-        let mut points = evaluate_points(y, &mut self.objective_function)?;
+        y
+    }
+
+    /// Returns the points of a generation sampled by `sample_candidates` with their objective
+    /// function values, sorted in ascending order by value. `values` are the function values of
+    /// the points returned by `plan.points`
+    ///
+    /// Returns Err if any value is invalid
+    pub fn evaluate_candidates(
+        &mut self,
+        state: &State,
+        mode: Mode,
+        candidates: Vec<Candidate>,
+        plan: EvaluationPlan,
+        values: &[f64],
+    ) -> Result<Vec<EvaluatedPoint>, InvalidFunctionValueError> {
+        if values.iter().any(|value| value.is_nan()) {
+            return Err(InvalidFunctionValueError);
+        }
+
+        let combined;
+        let point_values = match &mut self.uncertainty {
+            Some(uncertainty) => {
+                combined = uncertainty.combine(plan, values, mode);
+                &combined
+            }
+            None => values,
+        };
+
+        let mut points = candidates
+            .into_iter()
+            .zip(point_values)
+            .map(|(candidate, &value)| EvaluatedPoint::from_candidate(candidate, |_| value))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        self.function_evals += points.len();
+        self.function_evals += values.len();
+
+        if let Some(boundary) = &mut self.boundary {
+            boundary.penalize(&mut points, state, mode);
+        }
 
         points.sort_by(|a, b| mode.sort_cmp(a.value, b.value));
         Ok(points)
     }
 
+    /// Applies the noise treatment after the distribution was updated. Returns the factor by
+    /// which to multiply the step size
+    pub fn adapt_to_noise(&mut self) -> f64 {
+        self.uncertainty.as_mut().map_or(1.0, |uncertainty| uncertainty.adapt())
+    }
+
+    pub fn uncertainty_state(&self) -> Option<&UncertaintyState> {
+        self.uncertainty.as_ref().map(|uncertainty| uncertainty.state())
+    }
+
+    /// Restores the state of the uncertainty handling if it is enabled
+    pub fn restore_uncertainty(&mut self, state: Option<UncertaintyState>) {
+        if let Some(uncertainty) = &mut self.uncertainty {
+            uncertainty.restore(state);
+        }
+    }
+
     pub fn function_evals(&self) -> usize {
         self.function_evals
     }
 
+    /// Returns the position of the RNG in its stream
+    pub fn rng_snapshot(&self) -> RngSnapshot {
+        RngSnapshot {
+            seed: self.rng.get_seed(),
+            stream: self.rng.get_stream(),
+            word_pos: self.rng.get_word_pos(),
+        }
+    }
+
+    /// Restores the RNG and the number of function evaluations
+    pub fn restore(&mut self, rng: &RngSnapshot, function_evals: usize) {
+        self.rng = ChaCha12Rng::from_seed(rng.seed);
+        self.rng.set_stream(rng.stream);
+        self.rng.set_word_pos(rng.word_pos);
+        self.function_evals = function_evals;
+    }
+
     /// Consumes `self` and returns the objective function
     pub fn into_objective_function(self) -> F {
         self.objective_function
@@ -158,10 +308,8 @@ impl<F: ObjectiveFunction> Sampler<F> {
         mode: Mode,
         parallel_update: bool,
     ) -> Result<Vec<EvaluatedPoint>, InvalidFunctionValueError> {
-        self.sample_internal(state, mode, parallel_update, |y, objective_function| {
-            y.into_iter()
-                .map(|yk| EvaluatedPoint::new(yk, state.mean(), state.sigma(), |x| objective_function.evaluate(x)))
-                .collect::<Result<Vec<_>, _>>()
+        self.sample_internal(state, mode, parallel_update, |points, objective_function| {
+            points.into_iter().map(|x| objective_function.evaluate(x)).collect()
         })
     }
 }
@@ -174,14 +322,11 @@ impl<F: ParallelObjectiveFunction> Sampler<F> {
         mode: Mode,
         parallel_update: bool,
     ) -> Result<Vec<EvaluatedPoint>, InvalidFunctionValueError> {
-        self.sample_internal(state, mode, parallel_update, |y, objective_function| {
-            y.into_par_iter()
-                .map(|yk| {
-                    EvaluatedPoint::new(yk, state.mean(), state.sigma(), |x| {
-                        objective_function.evaluate_parallel(x)
-                    })
-                })
-                .collect::<Result<Vec<_>, _>>()
+        self.sample_internal(state, mode, parallel_update, |points, objective_function| {
+            points
+                .into_par_iter()
+                .map(|x| objective_function.evaluate_parallel(x))
+                .collect()
         })
     }
 }
@@ -193,20 +338,48 @@ impl<F: BatchObjectiveFunction> Sampler<F> {
         mode: Mode,
         parallel_update: bool,
     ) -> Result<Vec<EvaluatedPoint>, InvalidFunctionValueError> {
-        self.sample_internal(state, mode, parallel_update, |y, objective_function| {
-            let points = y
-                .iter()
-                .map(|yk| to_point(yk, state.mean(), state.sigma()))
-                .collect::<Vec<_>>();
-            let values = objective_function.evaluate_batch(&points);
-            y.into_iter()
-                .zip(values)
-                .map(|(yk, value)| EvaluatedPoint::new(yk, state.mean(), state.sigma(), |_| value))
-                .collect::<Result<Vec<_>, _>>()
+        self.sample_internal(state, mode, parallel_update, |points, objective_function| {
+            let points = points.into_iter().cloned().collect::<Vec<_>>();
+            objective_function.evaluate_batch(&points)
         })
     }
 }
 
+/// A point from the distribution that has not been evaluated yet
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// The point to evaluate
+    point: DVector<f64>,
+    /// The step from the mean used to update the distribution before scaling by sigma
+    unscaled_step: DVector<f64>,
+    /// The step sampled from the distribution if the point was repaired into the bounds
+    sampled_step: Option<DVector<f64>>,
+}
+
+impl Candidate {
+    pub fn new(point: DVector<f64>, unscaled_step: DVector<f64>) -> Self {
+        Self {
+            point,
+            unscaled_step,
+            sampled_step: None,
+        }
+    }
+
+    /// Returns a new `Candidate` for a point that was repaired into the bounds, where
+    /// `unscaled_step` is the step to the repaired point and `sampled_step` is the original step
+    pub fn repaired(point: DVector<f64>, unscaled_step: DVector<f64>, sampled_step: DVector<f64>) -> Self {
+        Self {
+            point,
+            unscaled_step,
+            sampled_step: Some(sampled_step),
+        }
+    }
+
+    pub fn point(&self) -> &DVector<f64> {
+        &self.point
+    }
+}
+
 /// A point from the distribution that has been evaluated by the objective function
 #[derive(Clone, Debug)]
 pub struct EvaluatedPoint {
@@ -215,26 +388,40 @@ pub struct EvaluatedPoint {
     /// The step from the mean of the point before scaling by sigma
     /// In the distribution N(0, cov)
     unscaled_step: DVector<f64>,
+    /// The step sampled from the distribution if the point was repaired into the bounds
+    sampled_step: Option<DVector<f64>>,
     /// The objective value at the point
     value: f64,
 }
 
-fn to_point(unscaled_step: &DVector<f64>, mean: &DVector<f64>, sigma: f64) -> DVector<f64> {
-    mean + sigma * unscaled_step
-}
-
 impl EvaluatedPoint {
     /// Returns a new `EvaluatedPoint` from the unscaled step from the mean, the mean, and the step
     /// size
     ///
     /// Returns `Err` if the objective function returned an invalid value
+    #[cfg(test)]
     pub fn new<F: FnMut(&DVector<f64>) -> f64>(
         unscaled_step: DVector<f64>,
         mean: &DVector<f64>,
         sigma: f64,
+        objective_function: F,
+    ) -> Result<Self, InvalidFunctionValueError> {
+        let point = mean + sigma * &unscaled_step;
+        Self::from_candidate(Candidate::new(point, unscaled_step), objective_function)
+    }
+
+    /// Returns a new `EvaluatedPoint` from a sampled candidate
+    ///
+    /// Returns `Err` if the objective function returned an invalid value
+    pub fn from_candidate<F: FnMut(&DVector<f64>) -> f64>(
+        candidate: Candidate,
         mut objective_function: F,
     ) -> Result<Self, InvalidFunctionValueError> {
-        let point = to_point(&unscaled_step, mean, sigma);
+        let Candidate {
+            point,
+            unscaled_step,
+            sampled_step,
+        } = candidate;
         let value = objective_function(&point);
 
         if value.is_nan() {
@@ -243,6 +430,7 @@ impl EvaluatedPoint {
             Ok(Self {
                 point,
                 unscaled_step,
+                sampled_step,
                 value,
             })
         }
@@ -256,9 +444,20 @@ impl EvaluatedPoint {
         &self.unscaled_step
     }
 
+    /// Returns the step used in the update with a negative weight. Repaired steps are shortened
+    /// towards the bounds, so penalizing them would shrink the distribution in the directions of
+    /// the good, repaired steps; the step sampled from the distribution is used instead
+    pub fn negative_step(&self) -> &DVector<f64> {
+        self.sampled_step.as_ref().unwrap_or(&self.unscaled_step)
+    }
+
     pub fn value(&self) -> f64 {
         self.value
     }
+
+    pub fn set_value(&mut self, value: f64) {
+        self.value = value;
+    }
 }
 
 /// The objective function returned an invalid value
@@ -268,6 +467,7 @@ pub struct InvalidFunctionValueError;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::matrix::{Covariance, CovarianceMatrix};
 
     #[test]
     fn test_evaluated_point() {
@@ -291,8 +491,16 @@ mod tests {
     fn test_sample() {
         let dim = 10;
         let population_size = 12;
-        let mut sampler = Sampler::new(dim, None, None, population_size, Box::new(|_: &DVector<f64>| 0.0), 1);
-        let state = State::new(vec![0.0; dim].into(), 2.0);
+        let mut sampler = Sampler::new(
+            dim,
+            None,
+            None,
+            None,
+            population_size,
+            Box::new(|_: &DVector<f64>| 0.0),
+            1,
+        );
+        let state = State::new(vec![0.0; dim].into(), 2.0, Covariance::Full(CovarianceMatrix::new(dim)));
 
         let n = 5;
         for _ in 0..n {
@@ -307,6 +515,7 @@ mod tests {
             dim,
             None,
             None,
+            None,
             population_size,
             Box::new(|_: &DVector<f64>| f64::NAN),
             1,
@@ -332,12 +541,13 @@ mod tests {
             let mut sampler = Sampler::new(
                 dim,
                 Some(Box::new(bounds.clone())),
+                None,
                 Some(0),
                 population_size,
                 objective_function,
                 1,
             );
-            let state = State::new(vec![0.0; dim].into(), 2.0);
+            let state = State::new(vec![0.0; dim].into(), 2.0, Covariance::Full(CovarianceMatrix::new(dim)));
             let individuals = sampler.sample(&state, Mode::Minimize, false).unwrap();
 
             assert!(individuals[0].point[0] < bounds.lower[0] || individuals[0].point[0] > bounds.upper[0]);
@@ -348,12 +558,13 @@ mod tests {
             let mut sampler = Sampler::new(
                 dim,
                 Some(Box::new(bounds.clone())),
+                None,
                 Some(10),
                 population_size,
                 objective_function,
                 1,
             );
-            let state = State::new(vec![0.0; dim].into(), 2.0);
+            let state = State::new(vec![0.0; dim].into(), 2.0, Covariance::Full(CovarianceMatrix::new(dim)));
             let individuals = sampler.sample(&state, Mode::Minimize, false).unwrap();
 
             assert!(individuals[0].point[0] >= bounds.lower[0]);
@@ -366,11 +577,12 @@ mod tests {
                 dim,
                 Some(Box::new(bounds.clone())),
                 None,
+                None,
                 population_size,
                 objective_function,
                 1,
             );
-            let state = State::new(vec![0.0; dim].into(), 2.0);
+            let state = State::new(vec![0.0; dim].into(), 2.0, Covariance::Full(CovarianceMatrix::new(dim)));
             let individuals = sampler.sample(&state, Mode::Minimize, false).unwrap();
 
             assert!(individuals[0].point[0] >= bounds.lower[0]);
@@ -393,8 +605,8 @@ mod tests {
         let dim = 10;
         let population_size = expected.len();
 
-        let mut sampler = Sampler::new(dim, None, None, population_size, function, 1);
-        let state = State::new(vec![0.0; dim].into(), 2.0);
+        let mut sampler = Sampler::new(dim, None, None, None, population_size, function, 1);
+        let state = State::new(vec![0.0; dim].into(), 2.0, Covariance::Full(CovarianceMatrix::new(dim)));
 
         let individuals = sampler.sample(&state, mode, false).unwrap();
         let values = individuals.into_iter().map(|ind| ind.value).collect::<Vec<_>>();
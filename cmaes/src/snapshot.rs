@@ -0,0 +1,173 @@
+//! Snapshots of the variable state of a [`CMAES`][crate::CMAES]. See [`Snapshot`] for full
+//! documentation.
+
+use nalgebra::DVector;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+
+use crate::matrix::SquareMatrix;
+use crate::parameters::{CovarianceModel, Parameters};
+use crate::{Individual, UncertaintyState};
+
+/// A copy of the variable state of a [`CMAES`][crate::CMAES], returned by
+/// [`CMAES::snapshot`][crate::CMAES::snapshot].
+///
+/// A snapshot covers the distribution, evolution paths, objective function value history, RNG
+/// state, uncertainty handling state and margin of integer coordinates, but not the options the `CMAES` was built with. Restoring it with
+/// [`CMAES::restore`][crate::CMAES::restore] into a `CMAES` built with the same options continues
+/// the run exactly as if it had not been interrupted. The options may also be changed to warm-start
+/// a new run from the learned distribution, for example with a larger function evaluation limit.
+/// Note that termination criteria count function evaluations and generations from the start of
+/// the original run.
+///
+/// With the `serde` feature enabled, snapshots implement `Serialize` and `Deserialize`, so they can
+/// be written to disk to checkpoint long runs or to inspect the final distribution.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// The number of generations that have been completed
+    pub generation: usize,
+    /// The number of times the objective function has been evaluated
+    pub function_evals: usize,
+    /// The distribution mean
+    pub mean: DVector<f64>,
+    /// The distribution step size
+    pub sigma: f64,
+    /// The distribution covariance matrix
+    pub covariance: CovarianceSnapshot,
+    /// Evolution path of the mean used to update the covariance matrix
+    pub path_c: DVector<f64>,
+    /// Evolution path of the mean used to update the step size
+    pub path_sigma: DVector<f64>,
+    /// The last time the eigendecomposition was updated, in function evals
+    pub last_eigen_update_evals: usize,
+    /// The objective function value history
+    pub history: HistorySnapshot,
+    /// The state of the RNG used to sample points
+    pub rng: RngSnapshot,
+    /// The state of the uncertainty handling, if enabled. Restoring `None` into a `CMAES` with
+    /// uncertainty handling resets it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub uncertainty: Option<UncertaintyState>,
+    /// The scaling of the sampled steps that keeps the margin of the integer coordinates (the
+    /// diagonal of `A`), if any are set. Restoring `None` into a `CMAES` with integer coordinates
+    /// resets it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub margin_scale: Option<DVector<f64>>,
+}
+
+/// The covariance matrix of a [`Snapshot`] in the form used by its [`CovarianceModel`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CovarianceSnapshot {
+    /// The matrix of [`CovarianceModel::Full`]. The eigendecomposition is stored because it is
+    /// only updated every few generations and may lag behind `cov`.
+    Full {
+        cov: SquareMatrix<f64>,
+        /// Normalized eigenvectors (`B`)
+        eigenvectors: SquareMatrix<f64>,
+        /// Square roots of the eigenvalues (the diagonal of `D`)
+        sqrt_eigenvalues: DVector<f64>,
+    },
+    /// The diagonal of the matrix of [`CovarianceModel::Separable`].
+    Separable { variances: DVector<f64> },
+    /// The directions of the rank-one modifications of [`CovarianceModel::LowRank`], of which the
+    /// first `active` are used.
+    LowRank {
+        directions: Vec<DVector<f64>>,
+        active: usize,
+    },
+}
+
+impl CovarianceSnapshot {
+    /// Returns the [`CovarianceModel`] of the matrix.
+    pub fn model(&self) -> CovarianceModel {
+        match self {
+            CovarianceSnapshot::Full { .. } => CovarianceModel::Full,
+            CovarianceSnapshot::Separable { .. } => CovarianceModel::Separable,
+            CovarianceSnapshot::LowRank { directions, .. } => CovarianceModel::LowRank { rank: directions.len() },
+        }
+    }
+
+    /// Returns whether every vector and matrix has `dim` dimensions
+    fn has_dimensions(&self, dim: usize) -> bool {
+        match self {
+            CovarianceSnapshot::Full {
+                cov,
+                eigenvectors,
+                sqrt_eigenvalues,
+            } => cov.shape() == (dim, dim) && eigenvectors.shape() == (dim, dim) && sqrt_eigenvalues.len() == dim,
+            CovarianceSnapshot::Separable { variances } => variances.len() == dim,
+            CovarianceSnapshot::LowRank { directions, active } => {
+                directions.iter().all(|m| m.len() == dim) && *active <= directions.len()
+            }
+        }
+    }
+}
+
+/// The objective function value history of a [`Snapshot`]. Values at the front of the histories
+/// are from more recent generations. The default value is an empty history, which can be used to
+/// reset the history-based termination criteria of a warm-started run.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HistorySnapshot {
+    pub best_function_values: VecDeque<f64>,
+    pub median_function_values: VecDeque<f64>,
+    pub current_best_individual: Option<Individual>,
+    pub overall_best_individual: Option<Individual>,
+    pub first_median_function_value: Option<f64>,
+    pub best_median_function_value: Option<f64>,
+}
+
+/// The position of the ChaCha12 RNG of a [`Snapshot`] in its stream.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RngSnapshot {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+/// An invalid [`Snapshot`] was passed to [`CMAES::restore`][crate::CMAES::restore].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidSnapshotError {
+    /// The number of dimensions of a vector or matrix does not match that of the `CMAES`.
+    Dimensions,
+    /// The covariance matrix does not use the covariance model of the `CMAES`.
+    CovarianceModel,
+    /// The step size is not positive and finite.
+    Sigma,
+}
+
+/// Checks that `snapshot` can be restored into a `CMAES` with the given parameters
+pub(crate) fn validate(snapshot: &Snapshot, parameters: &Parameters) -> Result<(), InvalidSnapshotError> {
+    let dim = parameters.dim();
+
+    if snapshot.covariance.model() != parameters.covariance_model() {
+        return Err(InvalidSnapshotError::CovarianceModel);
+    }
+
+    let history = &snapshot.history;
+    let individuals_match = [&history.current_best_individual, &history.overall_best_individual]
+        .into_iter()
+        .flatten()
+        .all(|individual| individual.point.len() == dim);
+
+    if snapshot.mean.len() != dim
+        || snapshot.path_c.len() != dim
+        || snapshot.path_sigma.len() != dim
+        || !snapshot.covariance.has_dimensions(dim)
+        || !individuals_match
+        || snapshot.margin_scale.as_ref().is_some_and(|scale| scale.len() != dim)
+    {
+        return Err(InvalidSnapshotError::Dimensions);
+    }
+
+    if !(snapshot.sigma.is_finite() && snapshot.sigma > 0.0) {
+        return Err(InvalidSnapshotError::Sigma);
+    }
+
+    Ok(())
+}
@@ -2,9 +2,13 @@
 
 use nalgebra::DVector;
 
-use crate::matrix::{CovarianceMatrix, PosDefCovError, SquareMatrix};
+use crate::integer::Margin;
+#[cfg(test)]
+use crate::matrix::CovarianceMatrix;
+use crate::matrix::{Covariance, PosDefCovError, SquareMatrix};
 use crate::parameters::Parameters;
 use crate::sampling::EvaluatedPoint;
+use crate::snapshot::Snapshot;
 use rayon::prelude::*;
 
 /// Stores the variable state of the algorithm and handles updating it
@@ -14,7 +18,7 @@ pub struct State {
     /// The distribution mean
     mean: DVector<f64>,
     /// The distribution covariance matrix
-    cov: CovarianceMatrix,
+    cov: Covariance,
     /// The distribution step size
     sigma: f64,
     /// Evolution path of the mean used to update the covariance matrix
@@ -23,14 +27,16 @@ pub struct State {
     path_sigma: DVector<f64>,
     /// The last time the eigendecomposition was updated, in function evals
     last_eigen_update_evals: usize,
+    /// Scaling of the sampled steps in each coordinate that keeps the margin of the integer
+    /// coordinates (the diagonal of `A`). `None` without integer coordinates
+    margin_scale: Option<DVector<f64>>,
 }
 
 impl State {
-    /// Initializes the variable state of the algorithm
-    pub fn new(initial_mean: DVector<f64>, initial_sigma: f64) -> Self {
+    /// Initializes the variable state of the algorithm with an identity covariance matrix `cov`
+    pub fn new(initial_mean: DVector<f64>, initial_sigma: f64, cov: Covariance) -> Self {
         let dim = initial_mean.len();
         let mean = initial_mean;
-        let cov = CovarianceMatrix::new(dim);
         let sigma = initial_sigma;
         let path_c = DVector::zeros(dim);
         let path_sigma = DVector::zeros(dim);
@@ -43,9 +49,41 @@ impl State {
             path_c,
             path_sigma,
             last_eigen_update_evals: 0,
+            margin_scale: None,
         }
     }
 
+    /// Restores the variable state from a snapshot for population size `lambda`. Assumes that the
+    /// snapshot has been validated
+    pub fn from_snapshot(snapshot: &Snapshot, lambda: usize) -> Self {
+        let dim = snapshot.mean.len();
+
+        Self {
+            generation: snapshot.generation,
+            mean: snapshot.mean.clone(),
+            cov: Covariance::from_snapshot(&snapshot.covariance, dim, lambda),
+            sigma: snapshot.sigma,
+            path_c: snapshot.path_c.clone(),
+            path_sigma: snapshot.path_sigma.clone(),
+            last_eigen_update_evals: snapshot.last_eigen_update_evals,
+            margin_scale: snapshot.margin_scale.clone(),
+        }
+    }
+
+    /// Keeps the margin scaling if `enabled`, initializing it to the identity if not set, or
+    /// removes it otherwise
+    pub fn with_margin(mut self, enabled: bool) -> Self {
+        self.margin_scale = match enabled {
+            true => Some(
+                self.margin_scale
+                    .take()
+                    .unwrap_or_else(|| DVector::from_element(self.mean.len(), 1.0)),
+            ),
+            false => None,
+        };
+        self
+    }
+
     /// Updates the variable state using the provided sampled individuals
     pub fn update(
         &mut self,
@@ -71,12 +109,19 @@ impl State {
             .enumerate()
             .map(|(i, p)| p.unscaled_step() * params.weights()[i])
             .sum::<DVector<f64>>();
-        self.mean = &self.mean + &(cm * self.sigma * &yw);
+        self.mean = match &self.margin_scale {
+            Some(scale) => &self.mean + &(cm * self.sigma * yw.component_mul(scale)),
+            None => &self.mean + &(cm * self.sigma * &yw),
+        };
 
         // Update evolution paths
-        let sqrt_inv_c = self.cov.sqrt_inv();
+        let path_sigma_scale = (cs * (2.0 - cs) * mu_eff).sqrt();
+        let path_sigma_step = match &self.cov {
+            Covariance::Full(cov) => path_sigma_scale * cov.sqrt_inv() * &yw,
+            cov => path_sigma_scale * cov.whiten(&yw),
+        };
 
-        self.path_sigma = (1.0 - cs) * &self.path_sigma + (cs * (2.0 - cs) * mu_eff).sqrt() * sqrt_inv_c * &yw;
+        self.path_sigma = (1.0 - cs) * &self.path_sigma + path_sigma_step;
 
         // Expectation of N(0, I)
         let chi_n = (dim as f64).sqrt() * (1.0 - 1.0 / (4.0 * dim as f64) + 1.0 / (21.0 * dim.pow(2) as f64));
@@ -92,42 +137,81 @@ impl State {
         self.path_c = (1.0 - cc) * &self.path_c + hs * (cc * (2.0 - cc) * mu_eff).sqrt() * &yw;
 
         // Update step size
-        self.sigma *= ((cs / damp_s) * ((self.path_sigma.magnitude() / chi_n) - 1.0)).exp();
-
-        // Update covariance matrix
-
-        // Calculates the weighted contribution of each individual to the rank-mu update
-        let map_weights = |(i, w): (usize, f64)| {
-            // Scale negative weights to maintain positive definiteness of cov
-            let mut wc = w;
-            if w < 0.0 {
-                wc *= dim as f64 / (sqrt_inv_c * individuals[i].unscaled_step()).magnitude().powi(2);
-            }
-
-            wc * individuals[i].unscaled_step() * individuals[i].unscaled_step().transpose()
-        };
-        let rank_mu_update = if params.parallel_update() {
-            rank_mu_update_parallel(params.weights().as_slice(), map_weights, || {
-                SquareMatrix::zeros(dim, dim)
-            })
-        } else {
-            rank_mu_update(params.weights().as_slice(), map_weights)
+        self.sigma *= match self.cov {
+            // LM-MA-ES compares the squared norm of the path to its expectation without damping
+            Covariance::LowRank(_) => ((cs / 2.0) * (self.path_sigma.norm_squared() / dim as f64 - 1.0)).exp(),
+            _ => ((cs / damp_s) * ((self.path_sigma.magnitude() / chi_n) - 1.0)).exp(),
         };
 
+        // Update covariance matrix
         let delta_hs = (1.0 - hs) * cc * (2.0 - cc);
-        let cov_new = (1.0 + c1 * delta_hs - c1 - cmu * params.weights().iter().sum::<f64>()) * self.cov.cov()
-            + c1 * &self.path_c * self.path_c.transpose()
-            + cmu * rank_mu_update;
+        let old_cov_weight = 1.0 + c1 * delta_hs - c1 - cmu * params.weights().iter().sum::<f64>();
 
-        // Update eigendecomposition occasionally (updating every generation is unnecessary and
-        // inefficient for high dim)
+        // Weight of each individual in the rank-mu update
+        // Negative weights are scaled to maintain positive definiteness of cov
+        let rank_mu_weights = params
+            .weights()
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| {
+                if w < 0.0 {
+                    w * (dim as f64 / self.cov.whiten(individuals[i].negative_step()).magnitude().powi(2))
+                } else {
+                    w
+                }
+            })
+            .collect::<Vec<_>>();
         let evals_per_eigen = self.evals_per_eigen_update(params);
-        let do_eigen_update = current_function_evals >= self.last_eigen_update_evals + evals_per_eigen;
-
-        self.cov.set_cov(cov_new, do_eigen_update)?;
 
-        if do_eigen_update {
-            self.last_eigen_update_evals = current_function_evals;
+        match &mut self.cov {
+            Covariance::Full(cov) => {
+                // Calculates the weighted contribution of each individual to the rank-mu update
+                let map_weights = |(i, w): (usize, f64)| {
+                    let step = update_step(&individuals[i], w);
+                    w * step * step.transpose()
+                };
+                let rank_mu_update = if params.parallel_update() {
+                    rank_mu_update_parallel(&rank_mu_weights, map_weights, || SquareMatrix::zeros(dim, dim))
+                } else {
+                    rank_mu_update(&rank_mu_weights, map_weights)
+                };
+
+                let cov_new =
+                    old_cov_weight * cov.cov() + c1 * &self.path_c * self.path_c.transpose() + cmu * rank_mu_update;
+
+                // Update eigendecomposition occasionally (updating every generation is unnecessary
+                // and inefficient for high dim)
+                let do_eigen_update = current_function_evals >= self.last_eigen_update_evals + evals_per_eigen;
+
+                cov.set_cov(cov_new, do_eigen_update)?;
+
+                if do_eigen_update {
+                    self.last_eigen_update_evals = current_function_evals;
+                }
+            }
+            Covariance::Separable(cov) => {
+                // Only the diagonal of each outer product is needed
+                let rank_mu_update = rank_mu_weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &w)| {
+                        let step = update_step(&individuals[i], w);
+                        w * step.component_mul(step)
+                    })
+                    .sum::<DVector<f64>>();
+
+                let variances_new = old_cov_weight * cov.variances()
+                    + c1 * self.path_c.component_mul(&self.path_c)
+                    + cmu * rank_mu_update;
+
+                cov.set_variances(variances_new)?;
+            }
+            Covariance::LowRank(cov) => {
+                // The directions are learned from the weighted mean of the selected steps in the
+                // isotropic space
+                let zw = cov.inverse_transform(&yw);
+                cov.update(&zw, mu_eff)?;
+            }
         }
 
         self.generation += 1;
@@ -143,37 +227,65 @@ impl State {
         &self.mean
     }
 
-    pub fn cov(&self) -> &SquareMatrix<f64> {
-        self.cov.cov()
+    pub fn cov(&self) -> &Covariance {
+        &self.cov
     }
 
-    pub fn cov_eigenvectors(&self) -> &SquareMatrix<f64> {
-        self.cov.eigenvectors()
+    /// Returns the current axis ratio of the distribution (an upper bound for the low-rank model)
+    pub fn axis_ratio(&self) -> f64 {
+        let (min, max) = self.cov.axis_scale_range();
+        max / min
     }
 
-    pub fn cov_sqrt_eigenvalues(&self) -> &SquareMatrix<f64> {
-        self.cov.sqrt_eigenvalues()
+    pub fn sigma(&self) -> f64 {
+        self.sigma
     }
 
-    /// Returns the transform of the covariance matrix (`B * D`)
-    pub fn cov_transform(&self) -> &SquareMatrix<f64> {
-        self.cov.transform()
+    /// Returns the point at the unscaled step `y` from the mean
+    pub fn to_point(&self, y: &DVector<f64>) -> DVector<f64> {
+        match &self.margin_scale {
+            Some(scale) => &self.mean + self.sigma * y.component_mul(scale),
+            None => &self.mean + self.sigma * y,
+        }
     }
 
-    /// Returns the current axis ratio of the distribution
-    pub fn axis_ratio(&self) -> f64 {
-        let diag = self.cov.sqrt_eigenvalues().diagonal();
-        diag.max() / diag.min()
+    /// Returns the unscaled step from the mean to `point`, the inverse of `to_point`
+    pub fn to_step(&self, point: &DVector<f64>) -> DVector<f64> {
+        let step = (point - &self.mean) / self.sigma;
+        match &self.margin_scale {
+            Some(scale) => step.component_div(scale),
+            None => step,
+        }
     }
 
-    pub fn sigma(&self) -> f64 {
-        self.sigma
+    /// Moves the mean and scales the integer coordinates to keep the margin. Requires the margin
+    /// scaling to be enabled with `with_margin`
+    pub fn apply_margin(&mut self, margin: &Margin) {
+        let scale = self.margin_scale.as_mut().expect("margin scaling is not enabled");
+        margin.correct(&mut self.mean, scale, self.sigma, &self.cov);
+    }
+
+    pub fn margin_scale(&self) -> Option<&DVector<f64>> {
+        self.margin_scale.as_ref()
+    }
+
+    /// Multiplies the step size by `factor`
+    pub fn scale_sigma(&mut self, factor: f64) {
+        self.sigma *= factor;
     }
 
     pub fn path_c(&self) -> &DVector<f64> {
         &self.path_c
     }
 
+    pub fn path_sigma(&self) -> &DVector<f64> {
+        &self.path_sigma
+    }
+
+    pub fn last_eigen_update_evals(&self) -> usize {
+        self.last_eigen_update_evals
+    }
+
     /// Returns how many function evals should pass before updating the eigendecomposition
     pub fn evals_per_eigen_update(&self, params: &Parameters) -> usize {
         (0.5 * params.dim() as f64 * params.lambda() as f64
@@ -189,7 +301,10 @@ impl State {
 
     #[cfg(test)]
     pub fn mut_cov(&mut self) -> &mut CovarianceMatrix {
-        &mut self.cov
+        match &mut self.cov {
+            Covariance::Full(cov) => cov,
+            _ => panic!("not a full covariance matrix"),
+        }
     }
 
     #[cfg(test)]
@@ -203,6 +318,15 @@ impl State {
     }
 }
 
+/// Returns the step of `individual` used in the rank-mu update with weight `w`
+fn update_step(individual: &EvaluatedPoint, w: f64) -> &DVector<f64> {
+    if w < 0.0 {
+        individual.negative_step()
+    } else {
+        individual.unscaled_step()
+    }
+}
+
 /// Calculates the rank-mu update term (ignoring cmu)
 fn rank_mu_update<F>(weights: &[f64], map_weights: F) -> SquareMatrix<f64>
 where
@@ -1,5 +1,7 @@
 //! Algorithm termination handling. See [`TerminationReason`] for full documentation.
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use statrs::statistics::{Data, Median};
 
 use std::collections::VecDeque;
@@ -16,6 +18,7 @@ use crate::{utils, MAX_HISTORY_LENGTH};
 /// instability, while `Tol*` are problem-dependent parameters and `Max*` are for bounding
 /// iteration.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TerminationReason {
     /// The maximum number of objective function evaluations has been reached.
     MaxFunctionEvals,
@@ -57,6 +60,9 @@ pub enum TerminationReason {
     TolConditionCov,
     /// The objective function has returned an invalid value (`NAN` or `-NAN`).
     InvalidFunctionValue,
+    /// The [`Observer`][crate::Observer] set with
+    /// [`CMAESOptions::observer`][crate::CMAESOptions::observer] asked to stop the run.
+    Stopped,
     /// The covariance matrix is not positive definite. If this is returned frequently, it probably
     /// indicates a bug in the library and can be reported [here][0]. Using
     /// [`Weights::Positive`][crate::parameters::Weights::Positive] should prevent this entirely in
@@ -83,6 +89,9 @@ pub(crate) struct TerminationCheck<'a> {
     pub history: &'a History,
     /// The current generation of individuals
     pub individuals: &'a [EvaluatedPoint],
+    /// Whether the uncertainty handling measured noise that dominates the ranking, in which case
+    /// the function values do not show whether the algorithm has converged
+    pub noisy: bool,
 }
 
 impl<'a> TerminationCheck<'a> {
@@ -104,8 +113,10 @@ impl<'a> TerminationCheck<'a> {
 
         let mean = self.state.mean();
         let cov = self.state.cov();
-        let cov_eigenvectors = self.state.cov_eigenvectors();
-        let cov_sqrt_eigenvalues = self.state.cov_sqrt_eigenvalues();
+        // Not available for the low-rank model, which is checked with bounds or one coordinate
+        // at a time instead
+        let cov_diagonal = cov.diagonal();
+        let (_, max_axis_scale) = cov.axis_scale_range();
         let sigma = self.state.sigma();
         let path_c = self.state.path_c();
 
@@ -142,9 +153,11 @@ impl<'a> TerminationCheck<'a> {
         }
 
         // Check TerminationReason::TolFun*
+        // Noisy function values stop improving before the algorithm converges, so the criteria based
+        // on them are not checked while the noise dominates
         let past_generations_a = 10 + (30.0 * dim as f64 / lambda as f64).ceil() as usize;
 
-        if self.history.best_function_values().len() >= past_generations_a {
+        if !self.noisy && self.history.best_function_values().len() >= past_generations_a {
             let range_history = utils::range(
                 self.history
                     .best_function_values()
@@ -177,7 +190,13 @@ impl<'a> TerminationCheck<'a> {
         }
 
         // Check TerminationReason::TolX
-        if (0..dim).all(|i| (sigma * cov[(i, i)]).abs() < tol_x) && path_c.iter().all(|x| (sigma * *x).abs() < tol_x) {
+        let variances_below_tol_x = match &cov_diagonal {
+            Some(diagonal) => diagonal.iter().all(|v| (sigma * v).abs() < tol_x),
+            // Every variance is at most the largest eigenvalue
+            None => (sigma * max_axis_scale.powi(2)).abs() < tol_x,
+        };
+
+        if variances_below_tol_x && path_c.iter().all(|x| (sigma * *x).abs() < tol_x) {
             result.push(TerminationReason::TolX);
         }
 
@@ -192,24 +211,28 @@ impl<'a> TerminationCheck<'a> {
         // Cycles from 0 to n-1 to avoid checking every column every iteration
         let index_to_check = self.state.generation() % dim;
 
-        let no_effect_axis_check = 0.1
-            * sigma
-            * cov_sqrt_eigenvalues[(index_to_check, index_to_check)]
-            * cov_eigenvectors.column(index_to_check);
+        let no_effect_axis_check = 0.1 * sigma * cov.axis(index_to_check);
 
         if mean == &(mean + no_effect_axis_check) {
             result.push(TerminationReason::NoEffectAxis);
         }
 
         // Check TerminationReason::NoEffectCoord
-        if (0..dim).any(|i| mean[i] == mean[i] + 0.2 * sigma * cov[(i, i)]) {
+        let no_effect_coord = |i: usize, variance: f64| mean[i] == mean[i] + 0.2 * sigma * variance;
+        let any_no_effect_coord = match &cov_diagonal {
+            Some(diagonal) => (0..dim).any(|i| no_effect_coord(i, diagonal[i])),
+            // Computing a variance takes O(rank * N), so one coordinate is checked per generation
+            None => no_effect_coord(index_to_check, cov.variance(index_to_check)),
+        };
+
+        if any_no_effect_coord {
             result.push(TerminationReason::NoEffectCoord);
         }
 
         // Check TerminationReason::TolStagnation
         let tol_stagnation_generations = get_tol_stagnation_generations(tol_stagnation_option, self.state.generation());
 
-        if let Some(tol_stagnation_generations) = tol_stagnation_generations {
+        if let Some(tol_stagnation_generations) = tol_stagnation_generations.filter(|_| !self.noisy) {
             if self.history.best_function_values().len() >= tol_stagnation_generations
                 && self.history.median_function_values().len() >= tol_stagnation_generations
             {
@@ -251,12 +274,7 @@ impl<'a> TerminationCheck<'a> {
         }
 
         // Check TerminationReason::TolXUp
-        let max_standard_deviation = sigma
-            * cov_sqrt_eigenvalues
-                .diagonal()
-                .iter()
-                .max_by(|a, b| utils::partial_cmp(**a, **b))
-                .unwrap();
+        let max_standard_deviation = sigma * max_axis_scale;
 
         if max_standard_deviation / initial_sigma > tol_x_up {
             result.push(TerminationReason::TolXUp);
@@ -296,7 +314,7 @@ mod tests {
     use std::time::Duration;
 
     use super::*;
-    use crate::matrix::SquareMatrix;
+    use crate::matrix::{Covariance, CovarianceMatrix, SquareMatrix};
     use crate::mode::Mode;
     use crate::parameters::TerminationParameters;
     use crate::state::State;
@@ -338,6 +356,7 @@ mod tests {
 
     /// Sets up the state and parameters using the map_* functions and checks the termination
     /// criteria
+    #[allow(clippy::too_many_arguments)]
     fn run_termination_test<S, H, P, R>(
         mode: Mode,
         time_created: Option<Instant>,
@@ -357,7 +376,11 @@ mod tests {
         let initial_sigma = initial_sigma.unwrap_or(DEFAULT_INITIAL_SIGMA);
         let initial_mean = DVector::from(vec![0.0; DIM]);
 
-        let mut state = State::new(initial_mean.clone(), initial_sigma);
+        let mut state = State::new(
+            initial_mean.clone(),
+            initial_sigma,
+            Covariance::Full(CovarianceMatrix::new(DIM)),
+        );
         map_state(&mut state);
 
         let mut history = History::new();
@@ -380,6 +403,7 @@ mod tests {
             state: &state,
             history: &history,
             individuals: &get_dummy_generation(current_generation_function_value),
+            noisy: false,
         }
         .check_termination_criteria();
 
@@ -0,0 +1,396 @@
+//! Uncertainty handling for noisy objective functions (UH-CMA-ES). See [`UncertaintyHandling`] for
+//! full documentation.
+
+use nalgebra::DVector;
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::mode::Mode;
+use crate::sampling::Candidate;
+
+/// Options of the uncertainty handling of UH-CMA-ES, enabled with
+/// [`CMAESOptions::uncertainty_handling`][crate::CMAESOptions::uncertainty_handling].
+///
+/// CMA-ES only uses the ranking of the points of each generation, so noise in the objective
+/// function values does no harm as long as it rarely changes the ranking. Once the differences
+/// between the values become as small as the noise, the ranking becomes random and the
+/// distribution stops improving, while the flat history of function values looks like
+/// convergence to the `Tol*` termination criteria.
+///
+/// With uncertainty handling, a few points of each generation are evaluated a second time, and the
+/// change of their ranks between the two evaluations measures how much the noise affects the
+/// ranking. While the noise dominates, the step size is increased so that the points are spread
+/// far enough apart to rank them reliably, or each point is evaluated several times and its values
+/// are averaged (see [`NoiseTreatment`]). Reevaluated points are ranked by the average of both
+/// evaluations, and every evaluation counts towards
+/// [`max_function_evals`][crate::CMAESOptions::max_function_evals].
+///
+/// Based on Hansen, Niederberger, Guzzella and Koumoutsakos, "A Method for Handling Uncertainty in
+/// Evolutionary Optimization With an Application to Feedback Control of Combustion", 2009.
+///
+/// # Examples
+///
+/// ```
+/// use cmaes::{CMAESOptions, DVector, NoiseTreatment, UncertaintyHandling};
+/// use rand::Rng;
+///
+/// let noisy_sphere = |x: &DVector<f64>| x.norm_squared() + 1e-4 * rand::thread_rng().gen::<f64>();
+///
+/// let mut cmaes = CMAESOptions::new(vec![1.0; 4], 1.0)
+///     .uncertainty_handling(UncertaintyHandling {
+///         treatment: NoiseTreatment::Evaluations { max_evaluations: 10 },
+///         ..Default::default()
+///     })
+///     .max_function_evals(10_000)
+///     .build(noisy_sphere)
+///     .unwrap();
+///
+/// let result = cmaes.run();
+/// let uncertainty = result.uncertainty.unwrap();
+/// println!("noise level {}, {} evaluations per point", uncertainty.level, uncertainty.evaluations);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UncertaintyHandling {
+    /// Average number of points reevaluated each generation (`lambda_reev`). The fractional part
+    /// is the probability of reevaluating one more point. Must be positive and at most the
+    /// population size. Default value is `1.5 + lambda / 20` if `None`.
+    pub reevaluations: Option<f64>,
+    /// How the algorithm responds to noise that dominates the ranking. Default value is
+    /// [`NoiseTreatment::StepSize`].
+    pub treatment: NoiseTreatment,
+    /// The fraction of rank changes expected from noise alone that is tolerated (`theta`). Must be
+    /// between `0.0` and `1.0`. Default value is `0.2`.
+    pub theta: f64,
+    /// The learning rate of the smoothed noise level. Must be between `0.0` and `1.0`. Default
+    /// value is `0.3`.
+    pub cs: f64,
+}
+
+impl Default for UncertaintyHandling {
+    fn default() -> Self {
+        Self {
+            reevaluations: None,
+            treatment: NoiseTreatment::StepSize,
+            theta: 0.2,
+            cs: 0.3,
+        }
+    }
+}
+
+/// How the algorithm responds to noise that dominates the ranking of the points. See
+/// [`UncertaintyHandling`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NoiseTreatment {
+    /// Increases the step size by a factor of `1 + 2 / (N + 10)` each generation. Suited to noise
+    /// that does not shrink with the distance to the optimum, where larger steps make the
+    /// differences between the function values larger than the noise.
+    StepSize,
+    /// Evaluates each point several times and averages its values, which reduces the standard
+    /// deviation of independent noise by the square root of the number of evaluations. The number
+    /// of evaluations is increased by a factor of `1.5` each generation while the noise dominates,
+    /// up to `max_evaluations`, after which the step size is increased as in `StepSize`. It is
+    /// slowly decreased again once the ranking is reliable.
+    Evaluations { max_evaluations: usize },
+}
+
+/// The state of the uncertainty handling, returned in
+/// [`TerminationData::uncertainty`][crate::TerminationData::uncertainty] and stored in
+/// [`Snapshot`][crate::Snapshot]s.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UncertaintyState {
+    /// The smoothed measure of how much the noise changes the ranking (`s`). Positive values mean
+    /// that the noise dominates the ranking, in which case the noise treatment is applied and the
+    /// termination criteria based on function values are not checked.
+    pub level: f64,
+    /// The number of times each point is evaluated, rounded to the nearest integer when sampling.
+    /// Only changed by [`NoiseTreatment::Evaluations`].
+    pub evaluations: f64,
+    /// The total number of points that were reevaluated.
+    pub reevaluations: usize,
+}
+
+impl UncertaintyState {
+    fn new() -> Self {
+        Self {
+            level: 0.0,
+            evaluations: 1.0,
+            reevaluations: 0,
+        }
+    }
+}
+
+/// How the points of a generation are evaluated
+#[derive(Clone, Copy, Debug)]
+pub struct EvaluationPlan {
+    /// The number of points in the generation
+    points: usize,
+    /// The number of times each point is evaluated
+    evaluations: usize,
+    /// The number of points (from the start of the generation) that are evaluated again
+    reevaluated: usize,
+}
+
+impl EvaluationPlan {
+    /// Returns the plan that evaluates each of `points` points once
+    pub fn single(points: usize) -> Self {
+        Self {
+            points,
+            evaluations: 1,
+            reevaluated: 0,
+        }
+    }
+
+    /// Returns the points to evaluate in order: every candidate `evaluations` times, then every
+    /// reevaluated candidate `evaluations` times
+    pub fn points<'a>(&self, candidates: &'a [Candidate]) -> Vec<&'a DVector<f64>> {
+        let reevaluated = &candidates[..self.reevaluated];
+
+        candidates
+            .iter()
+            .chain(reevaluated)
+            .flat_map(|candidate| std::iter::repeat_n(candidate.point(), self.evaluations))
+            .collect()
+    }
+
+    /// Returns the number of function evaluations in the plan
+    pub fn len(&self) -> usize {
+        (self.points + self.reevaluated) * self.evaluations
+    }
+}
+
+/// Measures the effect of noise on the ranking and adapts the evaluations or step size to it
+#[derive(Clone, Debug)]
+pub struct UncertaintyHandler {
+    options: UncertaintyHandling,
+    /// The resolved average number of reevaluated points per generation
+    reevaluations: f64,
+    /// The factor by which the step size is increased
+    alpha_sigma: f64,
+    state: UncertaintyState,
+    /// Whether the noise level was measured since the last adaptation
+    measured: bool,
+}
+
+/// The factor by which the number of evaluations per point is increased
+const ALPHA_EVALUATIONS: f64 = 1.5;
+
+impl UncertaintyHandler {
+    pub fn new(options: UncertaintyHandling, dim: usize, lambda: usize) -> Self {
+        let reevaluations = options.reevaluations.unwrap_or(1.5 + lambda as f64 / 20.0);
+
+        Self {
+            options,
+            reevaluations,
+            alpha_sigma: 1.0 + 2.0 / (dim as f64 + 10.0),
+            state: UncertaintyState::new(),
+            measured: false,
+        }
+    }
+
+    pub fn state(&self) -> &UncertaintyState {
+        &self.state
+    }
+
+    /// Replaces the state, or resets it if `state` is `None`
+    pub fn restore(&mut self, state: Option<UncertaintyState>) {
+        self.state = state.unwrap_or_else(UncertaintyState::new);
+        self.measured = false;
+    }
+
+    /// Returns the plan for a generation of `points` points, drawing the number of reevaluated
+    /// points from `rng`
+    pub fn plan<R: Rng>(&self, points: usize, rng: &mut R) -> EvaluationPlan {
+        let whole = self.reevaluations.floor();
+        let extra = rng.gen::<f64>() < self.reevaluations - whole;
+
+        EvaluationPlan {
+            points,
+            evaluations: (self.state.evaluations.round() as usize).max(1),
+            reevaluated: (whole as usize + extra as usize).min(points),
+        }
+    }
+
+    /// Returns the value of each point from the function `values` of the evaluations in `plan`,
+    /// and measures the rank changes of the reevaluated points
+    pub fn combine(&mut self, plan: EvaluationPlan, values: &[f64], mode: Mode) -> Vec<f64> {
+        let mut means = values
+            .chunks(plan.evaluations)
+            .map(|chunk| chunk.iter().sum::<f64>() / plan.evaluations as f64)
+            .collect::<Vec<_>>();
+        let reevaluated = means.split_off(plan.points);
+
+        if !reevaluated.is_empty() {
+            let s = rank_change(&means, &reevaluated, mode, self.options.theta);
+            self.state.level = (1.0 - self.options.cs) * self.state.level + self.options.cs * s;
+            self.state.reevaluations += reevaluated.len();
+            self.measured = true;
+
+            for (value, new_value) in means.iter_mut().zip(reevaluated) {
+                *value = (*value + new_value) / 2.0;
+            }
+        }
+
+        means
+    }
+
+    /// Applies the noise treatment after the distribution was updated with a measured generation.
+    /// Returns the factor by which to multiply the step size
+    pub fn adapt(&mut self) -> f64 {
+        if !std::mem::take(&mut self.measured) {
+            return 1.0;
+        }
+
+        let noisy = self.state.level > 0.0;
+        match self.options.treatment {
+            NoiseTreatment::StepSize if noisy => self.alpha_sigma,
+            NoiseTreatment::StepSize => 1.0,
+            NoiseTreatment::Evaluations { max_evaluations } => {
+                let max_evaluations = max_evaluations as f64;
+                let evaluations = &mut self.state.evaluations;
+
+                if noisy && *evaluations >= max_evaluations {
+                    self.alpha_sigma
+                } else {
+                    *evaluations = if noisy {
+                        (*evaluations * ALPHA_EVALUATIONS).min(max_evaluations)
+                    } else {
+                        (*evaluations / ALPHA_EVALUATIONS.sqrt()).max(1.0)
+                    };
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether the options are valid for a population of `lambda` points
+pub(crate) fn is_valid(options: &UncertaintyHandling, lambda: usize) -> bool {
+    let in_unit_interval = |x: f64| x > 0.0 && x <= 1.0;
+    let reevaluations_valid = options.reevaluations.is_none_or(|r| r > 0.0 && r <= lambda as f64);
+    let treatment_valid = match options.treatment {
+        NoiseTreatment::StepSize => true,
+        NoiseTreatment::Evaluations { max_evaluations } => max_evaluations >= 1,
+    };
+
+    reevaluations_valid && treatment_valid && in_unit_interval(options.theta) && in_unit_interval(options.cs)
+}
+
+/// Returns the measure of rank changes between the first `values` and their `new_values`, which
+/// is positive if the changes are larger than expected from random ranks with probability
+/// `1 - theta`
+fn rank_change(values: &[f64], new_values: &[f64], mode: Mode, theta: f64) -> f64 {
+    let all = values.iter().chain(new_values).cloned().collect::<Vec<_>>();
+    let n = all.len();
+
+    // Ranks start at 1
+    let mut order = (0..n).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| mode.sort_cmp(all[a], all[b]));
+    let mut ranks = vec![0; n];
+    for (rank, i) in order.into_iter().enumerate() {
+        ranks[i] = rank as i64 + 1;
+    }
+
+    // The `theta / 2` quantile of the rank change of a value of rank `r` if its new rank among the
+    // other values is uniformly random
+    let limit = |r: i64| {
+        let mut changes = (1..n as i64).map(|other| (other - r).abs() as f64).collect::<Vec<_>>();
+        changes.sort_by(f64::total_cmp);
+        quantile(&changes, theta / 2.0)
+    };
+
+    let sum = (0..new_values.len())
+        .map(|i| {
+            let (old, new) = (ranks[i], ranks[values.len() + i]);
+            // The difference of the ranks of two distinct values is at least 1
+            let change = ((new - old).abs() - 1) as f64;
+            // The ranks among the other values
+            let new_among_others = new - (new > old) as i64;
+            let old_among_others = old - (old > new) as i64;
+
+            2.0 * change - limit(new_among_others) - limit(old_among_others)
+        })
+        .sum::<f64>();
+
+    sum / new_values.len() as f64
+}
+
+/// Returns the `q` quantile of `sorted` with linear interpolation between ranks
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (rank - low as f64) * (sorted[high] - sorted[low])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_change() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+
+        // Exact reevaluations never change the ranking
+        assert!(rank_change(&values, &values[..2], Mode::Minimize, 0.2) < 0.0);
+        assert!(rank_change(&values, &values[..2], Mode::Maximize, 0.2) < 0.0);
+
+        // The best and worst points swap places
+        assert!(rank_change(&values, &[8.5, 0.5], Mode::Minimize, 0.2) > 0.0);
+    }
+
+    #[test]
+    fn test_plan() {
+        let candidates = (0..3)
+            .map(|i| Candidate::new(DVector::from_element(1, i as f64), DVector::zeros(1)))
+            .collect::<Vec<_>>();
+        let plan = EvaluationPlan {
+            points: 3,
+            evaluations: 2,
+            reevaluated: 1,
+        };
+
+        let points = plan.points(&candidates).into_iter().map(|p| p[0]).collect::<Vec<_>>();
+        assert_eq!(vec![0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 0.0, 0.0], points);
+        assert_eq!(8, plan.len());
+    }
+
+    #[test]
+    fn test_evaluations_treatment() {
+        let options = UncertaintyHandling {
+            treatment: NoiseTreatment::Evaluations { max_evaluations: 2 },
+            cs: 1.0,
+            ..Default::default()
+        };
+        let mut handler = UncertaintyHandler::new(options, 4, 8);
+        let plan = EvaluationPlan {
+            points: 3,
+            evaluations: 1,
+            reevaluated: 1,
+        };
+
+        // Noisy: the evaluations increase up to the maximum, then the step size
+        let values = handler.combine(plan, &[1.0, 2.0, 3.0, 4.0], Mode::Minimize);
+        assert_eq!(vec![2.5, 2.0, 3.0], values);
+        assert!(handler.state().level > 0.0);
+        assert_eq!(1.0, handler.adapt());
+        assert_eq!(1.5, handler.state().evaluations);
+        // Not measured again
+        assert_eq!(1.0, handler.adapt());
+
+        handler.combine(plan, &[1.0, 2.0, 3.0, 4.0], Mode::Minimize);
+        handler.adapt();
+        handler.combine(plan, &[1.0, 2.0, 3.0, 4.0], Mode::Minimize);
+        assert_eq!(2.0, handler.state().evaluations);
+        assert!(handler.adapt() > 1.0);
+
+        // Exact
+        handler.combine(plan, &[1.0, 2.0, 3.0, 1.0], Mode::Minimize);
+        assert!(handler.state().level < 0.0);
+        assert_eq!(1.0, handler.adapt());
+        assert!(handler.state().evaluations < 2.0);
+        assert_eq!(4, handler.state().reevaluations);
+    }
+}
@@ -0,0 +1,78 @@
+//! Tests for the ask-and-tell interface
+
+use cmaes::{CMAESOptions, CovarianceModel, TerminationReason};
+use nalgebra::DVector;
+
+fn rosenbrock(x: &DVector<f64>) -> f64 {
+    (0..x.len() - 1)
+        .map(|i| 100.0 * (x[i + 1] - x[i].powi(2)).powi(2) + (1.0 - x[i]).powi(2))
+        .sum()
+}
+
+fn options(model: CovarianceModel) -> CMAESOptions {
+    CMAESOptions::new(vec![0.5; 6], 0.3)
+        .covariance_model(model)
+        .fun_target(1e-10)
+        .max_generations(5000)
+        .seed(1)
+}
+
+#[test]
+fn test_ask_tell_matches_next() {
+    for model in [
+        CovarianceModel::Full,
+        CovarianceModel::Separable,
+        CovarianceModel::LowRank { rank: 3 },
+    ] {
+        let mut cmaes = options(model).build(rosenbrock).unwrap();
+        let expected = cmaes.run();
+
+        let mut ask_tell = options(model).build(()).unwrap();
+        let result = loop {
+            let points = ask_tell.ask();
+            assert_eq!(ask_tell.parameters().lambda(), points.len());
+
+            let values = points.iter().map(rosenbrock).collect::<Vec<_>>();
+            if let Some(result) = ask_tell.tell(&values) {
+                break result;
+            }
+        };
+
+        assert_eq!(expected.reasons, result.reasons);
+        assert_eq!(cmaes.function_evals(), ask_tell.function_evals());
+        assert_eq!(cmaes.generation(), ask_tell.generation());
+        assert_eq!(expected.final_mean, result.final_mean);
+        assert_eq!(expected.overall_best.unwrap().value, result.overall_best.unwrap().value);
+    }
+}
+
+#[test]
+fn test_invalid_function_value() {
+    let mut cmaes = options(CovarianceModel::Full).build(()).unwrap();
+    let mut values = vec![1.0; cmaes.ask().len()];
+    values[2] = f64::NAN;
+
+    let result = cmaes.tell(&values).unwrap();
+
+    assert_eq!(vec![TerminationReason::InvalidFunctionValue], result.reasons);
+    assert_eq!(0, cmaes.function_evals());
+}
+
+#[test]
+#[should_panic(expected = "without a preceding call to `ask`")]
+fn test_tell_without_ask() {
+    let mut cmaes = options(CovarianceModel::Full).build(()).unwrap();
+    let values = vec![1.0; cmaes.ask().len()];
+
+    let _ = cmaes.tell(&values);
+    let _ = cmaes.tell(&values);
+}
+
+#[test]
+#[should_panic(expected = "values for")]
+fn test_tell_wrong_number_of_values() {
+    let mut cmaes = options(CovarianceModel::Full).build(()).unwrap();
+    let points = cmaes.ask();
+
+    let _ = cmaes.tell(&vec![1.0; points.len() - 1]);
+}
@@ -0,0 +1,117 @@
+//! Tests for the boundary handling modes
+
+use cmaes::options::InvalidOptionsError;
+use cmaes::{BoundaryHandling, CMAESOptions, CovarianceModel, TerminationReason};
+use nalgebra::DVector;
+
+const DIM: usize = 8;
+
+fn repair_modes() -> [BoundaryHandling; 3] {
+    [
+        BoundaryHandling::Reflect,
+        BoundaryHandling::Project,
+        BoundaryHandling::Penalty,
+    ]
+}
+
+fn in_bounds(x: &DVector<f64>) -> bool {
+    x.iter().all(|xi| (-1.0..=1.0).contains(xi))
+}
+
+// The unconstrained optimum lies outside of the bounds, so the constrained optimum is on the
+// upper bound in every coordinate
+fn shifted_sphere(x: &DVector<f64>) -> f64 {
+    assert!(in_bounds(x), "{:?} is out of bounds", x);
+    x.iter().map(|xi| (xi - 2.0).powi(2)).sum()
+}
+
+fn options(boundary_handling: BoundaryHandling, seed: u64) -> CMAESOptions {
+    CMAESOptions::new(vec![0.0; DIM], 0.5)
+        .bounds(vec![-1.0; DIM], vec![1.0; DIM])
+        .boundary_handling(boundary_handling)
+        .max_function_evals(20_000)
+        .seed(seed)
+}
+
+#[test]
+fn test_optimum_on_bounds() {
+    for mode in repair_modes() {
+        for model in [CovarianceModel::Full, CovarianceModel::Separable] {
+            let mut cmaes = options(mode, 1).covariance_model(model).build(shifted_sphere).unwrap();
+            let result = cmaes.run();
+            let best = result.overall_best.unwrap();
+
+            assert!(!result.reasons.contains(&TerminationReason::PosDefCov), "{:?}", mode);
+            assert!(in_bounds(&best.point));
+            for xi in best.point.iter() {
+                assert!((xi - 1.0).abs() < 1e-4, "{:?} {:?}: {:?}", mode, model, best.point);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_ask_tell_points_in_bounds() {
+    for mode in repair_modes() {
+        let mut cmaes = options(mode, 2).initial_step_size(5.0).build(()).unwrap();
+
+        for _ in 0..20 {
+            let points = cmaes.ask();
+            assert!(points.iter().all(in_bounds));
+
+            let values = points.iter().map(shifted_sphere).collect::<Vec<_>>();
+            if cmaes.tell(&values).is_some() {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resample_unchanged() {
+    // Resampling is still the default and evaluates points outside of the bounds once
+    // `max_resamples` is hit
+    let mut outside = 0;
+    let function = |x: &DVector<f64>| {
+        if !in_bounds(x) {
+            outside += 1;
+        }
+        x.iter().map(|xi| (xi - 2.0).powi(2)).sum::<f64>()
+    };
+    let mut cmaes = CMAESOptions::new(vec![0.0; DIM], 5.0)
+        .bounds(vec![-1.0; DIM], vec![1.0; DIM])
+        .max_resamples(Some(0))
+        .max_generations(5)
+        .seed(3)
+        .build(function)
+        .unwrap();
+    cmaes.run();
+    drop(cmaes);
+
+    assert!(outside > 0);
+}
+
+#[test]
+fn test_invalid_bounds() {
+    let function = |x: &DVector<f64>| x.magnitude();
+
+    assert!(matches!(
+        CMAESOptions::new(vec![0.0; DIM], 0.5)
+            .boundary_handling(BoundaryHandling::Reflect)
+            .build(function),
+        Err(InvalidOptionsError::Bounds)
+    ));
+    assert!(matches!(
+        CMAESOptions::new(vec![0.0; DIM], 0.5)
+            .bounds(vec![-1.0; DIM - 1], vec![1.0; DIM - 1])
+            .build(function),
+        Err(InvalidOptionsError::Bounds)
+    ));
+    assert!(matches!(
+        CMAESOptions::new(vec![0.0; DIM], 0.5)
+            .bounds(vec![1.0; DIM], vec![-1.0; DIM])
+            .boundary_handling(BoundaryHandling::Project)
+            .build(function),
+        Err(InvalidOptionsError::Bounds)
+    ));
+}
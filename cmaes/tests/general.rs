@@ -1,7 +1,7 @@
 //! General tests
 
 use assert_approx_eq::assert_approx_eq;
-use cmaes::{CMAESOptions, Mode, ObjectiveFunction, TerminationReason, Weights, CMAES};
+use cmaes::{CMAESOptions, CovarianceModel, Mode, ObjectiveFunction, TerminationReason, Weights, CMAES};
 use nalgebra::DVector;
 
 use std::collections::HashMap;
@@ -56,7 +56,7 @@ fn run_test<F: ObjectiveFunction + Clone + 'static>(
         }
 
         // Check that the target function value was reached
-        if !result.reasons.iter().any(|r| *r == TerminationReason::FunTarget) {
+        if !result.reasons.contains(&TerminationReason::FunTarget) {
             failures.push((result.reasons, overall_best.value));
         }
 
@@ -112,6 +112,27 @@ fn test_ellipsoid() {
     run_test_ellipsoid(30, 1, 29000, 0, Weights::Negative);
 }
 
+#[test]
+fn test_covariance_models() {
+    let run_test_model = |function: fn(&DVector<f64>) -> f64, dim, max_avg_evals, max_failures, model| {
+        let options = CMAESOptions::new(vec![0.1; dim], 0.1).covariance_model(model);
+
+        run_test(function, Mode::Minimize, options, max_avg_evals, max_failures);
+    };
+    let low_rank = |dim| CovarianceModel::LowRank {
+        rank: CovarianceModel::default_rank(dim),
+    };
+
+    run_test_model(sphere, 10, 1500, 0, CovarianceModel::Separable);
+    run_test_model(sphere, 100, 12800, 0, CovarianceModel::Separable);
+    run_test_model(ellipsoid, 10, 2300, 0, CovarianceModel::Separable);
+    run_test_model(ellipsoid, 100, 30000, 0, CovarianceModel::Separable);
+    run_test_model(cigar, 100, 24500, 0, CovarianceModel::Separable);
+    run_test_model(sphere, 10, 2000, 0, low_rank(10));
+    run_test_model(sphere, 100, 12200, 0, low_rank(100));
+    run_test_model(rosenbrock, 10, 11000, 3, low_rank(10));
+}
+
 fn run_test_rosenbrock(
     mode: Mode,
     dim: usize,
@@ -347,7 +368,7 @@ mod consistent {
         let static_cmaes = CMAESOptions::new(vec![0.0; 5], 1.0)
             .build(Box::new(static_function) as _)
             .unwrap();
-        StaticContainer(static_cmaes);
+        let _ = StaticContainer(static_cmaes);
 
         // Storing a CMAES with any lifetime
         struct NonStaticContainer<F: ObjectiveFunction>(CMAES<F>);
@@ -360,7 +381,7 @@ mod consistent {
         let non_static_cmaes = CMAESOptions::new(vec![0.0; 5], 1.0)
             .build(&mut non_static_function)
             .unwrap();
-        NonStaticContainer(non_static_cmaes);
+        let _ = NonStaticContainer(non_static_cmaes);
     }
 
     #[test]
@@ -375,13 +396,13 @@ mod consistent {
 
 // N-dimensional sphere function
 fn sphere(x: &DVector<f64>) -> f64 {
-    assert!(x.len() >= 1);
+    assert!(!x.is_empty());
     x.iter().map(|xi| xi.powi(2)).sum::<f64>()
 }
 
 // N-dimensional ellipsoid function
 fn ellipsoid(x: &DVector<f64>) -> f64 {
-    assert!(x.len() >= 1);
+    assert!(!x.is_empty());
     (0..x.len())
         .map(|i| 1e6f64.powf(i as f64 / x.len() as f64) * x[i].powi(2))
         .sum::<f64>()
@@ -406,6 +427,6 @@ fn rastrigin(x: &DVector<f64>) -> f64 {
 
 // N-dimensional cigar function
 fn cigar(x: &DVector<f64>) -> f64 {
-    assert!(x.len() >= 1);
+    assert!(!x.is_empty());
     x[0].powi(2) + 1e6 * x.iter().skip(1).map(|xi| xi.powi(2)).sum::<f64>()
 }
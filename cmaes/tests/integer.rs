@@ -0,0 +1,163 @@
+//! Tests for integer-valued coordinates
+
+use cmaes::options::InvalidOptionsError;
+use cmaes::restart::{RestartOptions, RestartStrategy, IPOP};
+use cmaes::{BoundaryHandling, CMAESOptions, CovarianceModel, IntegerCoordinates, TerminationReason};
+use nalgebra::DVector;
+
+const DIM: usize = 8;
+/// The last half of the coordinates are integers
+const INTEGERS: [usize; 4] = [4, 5, 6, 7];
+
+/// An ellipsoid whose optimum is between integers in the integer coordinates, so the best integer
+/// point is at `offset(i).round()` with value `integer_minimum()`
+fn ellipsoid(x: &DVector<f64>) -> f64 {
+    x.iter()
+        .enumerate()
+        .map(|(i, xi)| 10f64.powf(2.0 * i as f64 / (DIM - 1) as f64) * (xi - offset(i)).powi(2))
+        .sum()
+}
+
+fn offset(i: usize) -> f64 {
+    if INTEGERS.contains(&i) {
+        i as f64 - 2.3
+    } else {
+        0.0
+    }
+}
+
+fn integer_minimum() -> f64 {
+    ellipsoid(&DVector::from_fn(DIM, |i, _| offset(i).round()))
+}
+
+fn options(model: CovarianceModel, seed: u64) -> CMAESOptions {
+    CMAESOptions::new(vec![0.0; DIM], 1.0)
+        .covariance_model(model)
+        .integer_coordinates(IntegerCoordinates::new(INTEGERS.to_vec()))
+        .fun_target(integer_minimum() + 1e-10)
+        .max_function_evals(50_000)
+        .seed(seed)
+}
+
+fn is_integer(x: &DVector<f64>) -> bool {
+    INTEGERS.iter().all(|&i| x[i].fract() == 0.0)
+}
+
+#[test]
+fn test_ellipsoid() {
+    for model in [CovarianceModel::Full, CovarianceModel::Separable] {
+        let mut cmaes = options(model, 1).build(ellipsoid).unwrap();
+        let result = cmaes.run();
+
+        assert_eq!(vec![TerminationReason::FunTarget], result.reasons, "{:?}", model);
+        let best = result.overall_best.unwrap();
+        assert!(is_integer(&best.point));
+        for &i in &INTEGERS {
+            assert_eq!(offset(i).round(), best.point[i]);
+        }
+    }
+}
+
+#[test]
+fn test_points_are_integers() {
+    let mut cmaes = options(CovarianceModel::Full, 2).build(()).unwrap();
+
+    for _ in 0..200 {
+        let points = cmaes.ask();
+        assert!(points.iter().all(is_integer));
+
+        let values = points.iter().map(ellipsoid).collect::<Vec<_>>();
+        if cmaes.tell(&values).is_some() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_bounds() {
+    // The optimum of every integer coordinate is below the lowest integer in the bounds
+    let function = |x: &DVector<f64>| x.iter().map(|xi| (xi + 3.0).powi(2)).sum::<f64>();
+    let lower = vec![-0.5; DIM];
+    let upper = vec![10.5; DIM];
+
+    for boundary_handling in [
+        BoundaryHandling::Resample,
+        BoundaryHandling::Reflect,
+        BoundaryHandling::Penalty,
+    ] {
+        let mut cmaes = CMAESOptions::new(vec![5.0; DIM], 2.0)
+            .bounds(lower.clone(), upper.clone())
+            .boundary_handling(boundary_handling)
+            .integer_coordinates(IntegerCoordinates::new(INTEGERS.to_vec()))
+            .max_function_evals(20_000)
+            .seed(3)
+            .build(function)
+            .unwrap();
+        let result = cmaes.run();
+
+        let best = result.overall_best.unwrap();
+        for &i in &INTEGERS {
+            assert_eq!(0.0, best.point[i], "{:?}", boundary_handling);
+        }
+    }
+}
+
+#[test]
+fn test_restore_continues_run() {
+    let mut expected = options(CovarianceModel::Full, 4).build(ellipsoid).unwrap();
+    for _ in 0..50 {
+        let _ = expected.next();
+    }
+    let snapshot = expected.snapshot();
+    assert!(snapshot.margin_scale.is_some());
+    let expected_result = expected.run();
+
+    let mut restored = options(CovarianceModel::Full, 5).build(ellipsoid).unwrap();
+    restored.restore(snapshot).unwrap();
+    let result = restored.run();
+
+    assert_eq!(expected_result.final_mean, result.final_mean);
+    assert_eq!(expected.function_evals(), restored.function_evals());
+}
+
+#[test]
+fn test_restarts() {
+    let restarter = RestartOptions::new(DIM, -5.0..=5.0, RestartStrategy::IPOP(IPOP::default()))
+        .integer_coordinates(IntegerCoordinates::new(INTEGERS.to_vec()))
+        .fun_target(integer_minimum() + 1e-10)
+        .max_function_evals(100_000)
+        .seed(1)
+        .build()
+        .unwrap();
+    let results = restarter.run(|| ellipsoid);
+
+    let best = results.best.unwrap();
+    assert!(is_integer(&best.point));
+    assert!(best.value <= integer_minimum() + 1e-10);
+}
+
+#[test]
+fn test_invalid_options() {
+    let invalid = [
+        IntegerCoordinates::new(vec![DIM]),
+        IntegerCoordinates::new(vec![1, 1]),
+        IntegerCoordinates {
+            indices: vec![1],
+            margin: Some(0.5),
+        },
+    ];
+
+    for integers in invalid {
+        let result = CMAESOptions::new(vec![0.0; DIM], 1.0)
+            .integer_coordinates(integers)
+            .build(ellipsoid);
+        assert!(matches!(result, Err(InvalidOptionsError::IntegerCoordinates)));
+    }
+
+    // No integer between the bounds
+    let result = CMAESOptions::new(vec![0.3; DIM], 0.1)
+        .bounds(vec![0.2; DIM], vec![0.8; DIM])
+        .integer_coordinates(IntegerCoordinates::new(vec![1]))
+        .build(ellipsoid);
+    assert!(matches!(result, Err(InvalidOptionsError::IntegerCoordinates)));
+}
@@ -0,0 +1,136 @@
+//! Tests for MO-CMA-ES
+
+use cmaes::multi_objective::{InvalidMOOptionsError, MOCMAESOptions};
+use cmaes::{DVector, Mode, TerminationReason};
+
+const DIM: usize = 5;
+
+// The Pareto set is the line segment between (-1, ..., -1) and (1, ..., 1)
+fn two_spheres(x: &DVector<f64>) -> DVector<f64> {
+    DVector::from(vec![
+        x.add_scalar(-1.0).norm_squared(),
+        x.add_scalar(1.0).norm_squared(),
+    ])
+}
+
+fn options(seed: u64) -> MOCMAESOptions {
+    MOCMAESOptions::new(vec![0.5; DIM], 0.5)
+        .population_size(20)
+        .max_function_evals(20_000)
+        .seed(seed)
+}
+
+/// Distance of `x` from the Pareto set of `two_spheres`
+fn distance_to_pareto_set(x: &DVector<f64>) -> f64 {
+    let t = x.mean().clamp(-1.0, 1.0);
+    x.add_scalar(-t).norm()
+}
+
+#[test]
+fn test_two_spheres() {
+    let reference = DVector::from(vec![4.0 * DIM as f64 + 1.0; 2]);
+    // Hypervolume of the Pareto front with values (n(t - 1)^2, n(t + 1)^2) for t in [-1, 1]
+    let n = DIM as f64;
+    let optimal_hypervolume = reference[0] * reference[1] - 8.0 * n.powi(2) / 3.0;
+
+    let mut mocmaes = options(1).build(two_spheres).unwrap();
+    let result = mocmaes.run();
+
+    assert_eq!(vec![TerminationReason::MaxFunctionEvals], result.reasons);
+    assert!(result.pareto_front.len() >= 20);
+    // The archive also keeps points that are off the Pareto set but not dominated by any evaluated
+    // point, so only the population is expected to have converged
+    for individual in mocmaes.population() {
+        assert!(distance_to_pareto_set(&individual.point) < 1e-2);
+    }
+
+    // Both ends of the front are found
+    let best = |j: usize| {
+        result
+            .pareto_front
+            .iter()
+            .map(|individual| individual.values[j])
+            .fold(f64::INFINITY, f64::min)
+    };
+    assert!(best(0) < 1e-6 && best(1) < 1e-6);
+
+    let hypervolume = mocmaes.archive().hypervolume(&reference);
+    assert!(hypervolume > 0.99 * optimal_hypervolume, "{}", hypervolume);
+}
+
+#[test]
+fn test_hypervolume_increases() {
+    let reference = DVector::from(vec![50.0, 50.0]);
+    let mut mocmaes = options(2).build(two_spheres).unwrap();
+
+    let mut hypervolume = 0.0;
+    for _ in 0..100 {
+        assert!(mocmaes.next().is_none());
+
+        let new_hypervolume = mocmaes.archive().hypervolume(&reference);
+        assert!(new_hypervolume >= hypervolume);
+        hypervolume = new_hypervolume;
+    }
+}
+
+#[test]
+fn test_maximize() {
+    let negated = |x: &DVector<f64>| -two_spheres(x);
+    let mut mocmaes = options(3).mode(Mode::Maximize).build(negated).unwrap();
+    mocmaes.run();
+
+    for individual in mocmaes.population() {
+        assert!(distance_to_pareto_set(&individual.point) < 1e-2);
+    }
+}
+
+#[test]
+fn test_batch_matches_sequential() {
+    let mut sequential = options(4).build(two_spheres).unwrap();
+    let expected = sequential.run();
+
+    let batch_function = |xs: &[DVector<f64>]| xs.iter().map(two_spheres).collect::<Vec<_>>();
+    let mut batch = options(4).build(batch_function).unwrap();
+    let result = batch.run_batch();
+
+    assert_eq!(sequential.function_evals(), batch.function_evals());
+    assert_eq!(expected.pareto_front.len(), result.pareto_front.len());
+    for (a, b) in expected.pareto_front.iter().zip(&result.pareto_front) {
+        assert_eq!(a.point, b.point);
+    }
+}
+
+#[test]
+fn test_invalid_function_value() {
+    let function = |x: &DVector<f64>| DVector::from(vec![x[0], f64::NAN]);
+    let mut mocmaes = options(5).build(function).unwrap();
+    let result = mocmaes.run();
+
+    assert_eq!(vec![TerminationReason::InvalidFunctionValue], result.reasons);
+    assert!(result.pareto_front.is_empty());
+    assert_eq!(0, mocmaes.function_evals());
+}
+
+#[test]
+fn test_invalid_options() {
+    assert!(matches!(
+        MOCMAESOptions::new(vec![], 0.5).build(two_spheres),
+        Err(InvalidMOOptionsError::Dimensions)
+    ));
+    assert!(matches!(
+        MOCMAESOptions::new(vec![0.0; DIM], 0.5)
+            .population_size(0)
+            .build(two_spheres),
+        Err(InvalidMOOptionsError::PopulationSize)
+    ));
+    assert!(matches!(
+        MOCMAESOptions::new(vec![0.0; DIM], -1.0).build(two_spheres),
+        Err(InvalidMOOptionsError::InitialStepSize)
+    ));
+    assert!(matches!(
+        MOCMAESOptions::new(vec![0.0; DIM], 0.5)
+            .max_archive_size(0)
+            .build(two_spheres),
+        Err(InvalidMOOptionsError::ArchiveSize)
+    ));
+}
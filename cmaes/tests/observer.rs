@@ -0,0 +1,154 @@
+//! Tests for observing the progress of runs
+
+use cmaes::restart::{RestartOptions, RestartStrategy, IPOP};
+use cmaes::{CMAESOptions, Progress, TerminationReason};
+use nalgebra::DVector;
+
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+
+fn sphere(x: &DVector<f64>) -> f64 {
+    x.norm_squared()
+}
+
+fn options() -> CMAESOptions {
+    CMAESOptions::new(vec![1.0; 5], 0.5)
+        .fun_target(1e-10)
+        .max_generations(2000)
+        .seed(1)
+}
+
+/// Progress fields that don't borrow from the run
+#[derive(Clone, Debug, PartialEq)]
+struct Record {
+    generation: usize,
+    function_evals: usize,
+    sigma: f64,
+    best_value: f64,
+    median_value: f64,
+    overall_best_value: f64,
+}
+
+fn record(progress: &Progress) -> Record {
+    Record {
+        generation: progress.generation,
+        function_evals: progress.function_evals,
+        sigma: progress.sigma,
+        best_value: progress.best_value,
+        median_value: progress.median_value,
+        overall_best_value: progress.overall_best_value,
+    }
+}
+
+#[test]
+fn test_observes_every_generation() {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let observed = records.clone();
+    let mut cmaes = options()
+        .observer(move |progress: &Progress| {
+            assert_eq!(progress.best_value, sphere(progress.best_point));
+            observed.lock().unwrap().push(record(progress));
+            ControlFlow::Continue(())
+        })
+        .build(sphere)
+        .unwrap();
+    let result = cmaes.run();
+
+    let records = records.lock().unwrap();
+    assert_eq!(cmaes.generation(), records.len());
+    let lambda = cmaes.parameters().lambda();
+    for (i, record) in records.iter().enumerate() {
+        assert_eq!(i + 1, record.generation);
+        assert_eq!((i + 1) * lambda, record.function_evals);
+        assert!(record.best_value <= record.median_value);
+        assert!(record.overall_best_value <= record.best_value);
+    }
+
+    // The last call sees the final state
+    let last = records.last().unwrap();
+    assert_eq!(cmaes.sigma(), last.sigma);
+    assert_eq!(result.current_best.unwrap().value, last.best_value);
+    assert_eq!(result.overall_best.unwrap().value, last.overall_best_value);
+}
+
+#[test]
+fn test_observer_does_not_change_run() {
+    let expected = options().build(sphere).unwrap().run();
+    let result = options()
+        .observer(|_: &Progress| ControlFlow::Continue(()))
+        .build(sphere)
+        .unwrap()
+        .run();
+
+    assert_eq!(expected.reasons, result.reasons);
+    assert_eq!(expected.overall_best.unwrap().value, result.overall_best.unwrap().value);
+    assert_eq!(expected.final_mean, result.final_mean);
+}
+
+#[test]
+fn test_stop() {
+    let mut cmaes = options()
+        .observer(|progress: &Progress| {
+            if progress.generation < 10 {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })
+        .build(sphere)
+        .unwrap();
+    let result = cmaes.run();
+
+    assert_eq!(vec![TerminationReason::Stopped], result.reasons);
+    assert_eq!(10, cmaes.generation());
+}
+
+#[test]
+fn test_stop_ask_tell() {
+    let mut cmaes = options()
+        .observer(|progress: &Progress| {
+            if progress.function_evals < 50 {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })
+        .build(())
+        .unwrap();
+
+    let result = loop {
+        let values = cmaes.ask().iter().map(sphere).collect::<Vec<_>>();
+        if let Some(result) = cmaes.tell(&values) {
+            break result;
+        }
+    };
+
+    assert_eq!(vec![TerminationReason::Stopped], result.reasons);
+    assert!(cmaes.function_evals() >= 50);
+}
+
+#[test]
+fn test_stop_restarts() {
+    let generations = Arc::new(Mutex::new(0));
+    let observed = generations.clone();
+    let restarter = RestartOptions::new(5, -5.0..=5.0, RestartStrategy::IPOP(IPOP::default()))
+        .max_function_evals(100_000)
+        .seed(1)
+        .observer(move |_: &Progress| {
+            let mut generations = observed.lock().unwrap();
+            *generations += 1;
+            if *generations < 30 {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })
+        .build()
+        .unwrap();
+    let results = restarter.run(|| sphere);
+
+    let last_run = results.runs.last().unwrap();
+    assert_eq!(vec![TerminationReason::Stopped], last_run.termination_data.reasons);
+    assert_eq!(30, *generations.lock().unwrap());
+    assert_eq!(30, results.runs.iter().map(|run| run.generations).sum::<usize>());
+}
@@ -0,0 +1,126 @@
+//! Tests for the restart strategies
+
+use cmaes::restart::{RestartOptions, RestartStrategy, BIPOP, IPOP};
+use cmaes::TerminationReason;
+use nalgebra::DVector;
+
+use std::f64::consts::PI;
+
+fn rastrigin(x: &DVector<f64>) -> f64 {
+    10.0 * x.len() as f64
+        + x.iter()
+            .map(|xi| xi.powi(2) - 10.0 * (2.0 * PI * xi).cos())
+            .sum::<f64>()
+}
+
+fn strategies() -> Vec<RestartStrategy> {
+    vec![
+        RestartStrategy::IPOP(IPOP::default()),
+        RestartStrategy::BIPOP(BIPOP::default()),
+    ]
+}
+
+fn reached_target(runs: &[cmaes::restart::RunData]) -> bool {
+    runs.last()
+        .unwrap()
+        .termination_data
+        .reasons
+        .contains(&TerminationReason::FunTarget)
+}
+
+#[test]
+fn test_rastrigin() {
+    for strategy in strategies() {
+        let restarter = RestartOptions::new(4, -5.0..=5.0, strategy)
+            .fun_target(1e-8)
+            .max_function_evals(200_000)
+            .seed(1)
+            .build()
+            .unwrap();
+
+        let results = restarter.run(|| rastrigin);
+
+        assert!(reached_target(&results.runs), "{:?}", results.runs);
+        assert!(results.best.unwrap().value < 1e-8);
+        assert_eq!(
+            results.function_evals,
+            results.runs.iter().map(|run| run.function_evals).sum::<usize>()
+        );
+    }
+}
+
+#[test]
+fn test_run_parallel_and_batch() {
+    for strategy in strategies() {
+        let options = RestartOptions::new(4, -5.0..=5.0, strategy)
+            .fun_target(1e-8)
+            .max_function_evals(200_000)
+            .seed(2);
+        let restarter = options.build().unwrap();
+
+        let sequential = restarter.run(|| rastrigin);
+        let parallel = restarter.run_parallel(|| rastrigin);
+        let batch = restarter.run_batch(|| |xs: &[DVector<f64>]| xs.iter().map(rastrigin).collect::<Vec<_>>());
+
+        // Every variant evaluates the same points given the same seed
+        for results in [&parallel, &batch] {
+            assert!(reached_target(&results.runs));
+            assert_eq!(sequential.function_evals, results.function_evals);
+            assert_eq!(
+                sequential.best.as_ref().unwrap().value,
+                results.best.as_ref().unwrap().value
+            );
+        }
+    }
+}
+
+#[test]
+fn test_population_size_increases() {
+    let restarter = RestartOptions::new(4, -5.0..=5.0, RestartStrategy::IPOP(IPOP::default()))
+        .tol_fun_hist(1e-6)
+        .max_generations_per_run(50)
+        .max_function_evals(20_000)
+        .seed(3)
+        .build()
+        .unwrap();
+
+    let results = restarter.run(|| rastrigin);
+    let population_sizes = results.runs.iter().map(|run| run.population_size).collect::<Vec<_>>();
+
+    assert!(population_sizes.len() > 1);
+    assert!(population_sizes.windows(2).all(|w| w[1] == w[0] * 2));
+}
+
+#[test]
+fn test_max_function_evals() {
+    for strategy in strategies() {
+        let restarter = RestartOptions::new(10, -5.0..=5.0, strategy)
+            .max_function_evals(5_000)
+            .seed(4)
+            .build()
+            .unwrap();
+
+        let results = restarter.run(|| rastrigin);
+
+        // Runs stop at the end of a generation, so they may overshoot by one population
+        let last_population_size = results.runs.last().unwrap().population_size;
+        assert!(results.function_evals >= 5_000);
+        assert!(results.function_evals < 5_000 + last_population_size);
+    }
+}
+
+#[test]
+fn test_initial_mean() {
+    let restarter = RestartOptions::new(3, -5.0..=5.0, RestartStrategy::BIPOP(BIPOP::default()))
+        .initial_mean(vec![1.0, 2.0, 3.0])
+        .initial_step_size(0.5)
+        .fun_target(1e-10)
+        .seed(5)
+        .build()
+        .unwrap();
+
+    let results = restarter.run(|| |x: &DVector<f64>| x.iter().map(|xi| xi.powi(2)).sum::<f64>());
+
+    assert!(reached_target(&results.runs));
+    assert_eq!(0.5, results.runs[0].initial_step_size);
+}
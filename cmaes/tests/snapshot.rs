@@ -0,0 +1,141 @@
+//! Tests for saving and restoring the state of a run
+
+use cmaes::snapshot::InvalidSnapshotError;
+use cmaes::{CMAESOptions, CovarianceModel, Snapshot, CMAES};
+use nalgebra::DVector;
+
+fn rosenbrock(x: &DVector<f64>) -> f64 {
+    (0..x.len() - 1)
+        .map(|i| 100.0 * (x[i + 1] - x[i].powi(2)).powi(2) + (1.0 - x[i]).powi(2))
+        .sum()
+}
+
+fn options(model: CovarianceModel, seed: u64) -> CMAESOptions {
+    CMAESOptions::new(vec![0.5; 8], 0.3).covariance_model(model).seed(seed)
+}
+
+fn models() -> Vec<CovarianceModel> {
+    vec![
+        CovarianceModel::Full,
+        CovarianceModel::Separable,
+        CovarianceModel::LowRank { rank: 3 },
+    ]
+}
+
+fn advance<F: FnMut(&DVector<f64>) -> f64>(cmaes: &mut CMAES<F>, generations: usize) {
+    for _ in 0..generations {
+        assert!(cmaes.next().is_none());
+    }
+}
+
+fn assert_same_state<F, G>(a: &CMAES<F>, b: &CMAES<G>) {
+    assert_eq!(a.generation(), b.generation());
+    assert_eq!(a.function_evals(), b.function_evals());
+    assert_eq!(a.mean(), b.mean());
+    assert_eq!(a.sigma(), b.sigma());
+    assert_eq!(a.covariance_matrix_dense(), b.covariance_matrix_dense());
+    assert_eq!(
+        a.overall_best_individual().unwrap().value,
+        b.overall_best_individual().unwrap().value
+    );
+}
+
+#[test]
+fn test_restore_continues_run() {
+    for model in models() {
+        let mut uninterrupted = options(model, 1).build(rosenbrock).unwrap();
+        advance(&mut uninterrupted, 60);
+
+        let mut original = options(model, 1).build(rosenbrock).unwrap();
+        advance(&mut original, 25);
+
+        // A different seed is replaced by the RNG state of the snapshot
+        let mut restored = options(model, 2).build(rosenbrock).unwrap();
+        restored.restore(original.snapshot()).unwrap();
+        assert_same_state(&original, &restored);
+
+        advance(&mut restored, 35);
+        assert_same_state(&uninterrupted, &restored);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize_snapshot() {
+    for model in models() {
+        let mut original = options(model, 3).build(rosenbrock).unwrap();
+        advance(&mut original, 25);
+
+        let json = serde_json::to_string(&original.snapshot()).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+
+        let mut restored = options(model, 3).build(rosenbrock).unwrap();
+        restored.restore(snapshot).unwrap();
+
+        advance(&mut original, 10);
+        advance(&mut restored, 10);
+        assert_same_state(&original, &restored);
+    }
+}
+
+#[test]
+fn test_restore_invalid_snapshot() {
+    let mut cmaes = options(CovarianceModel::Full, 4).build(rosenbrock).unwrap();
+    advance(&mut cmaes, 5);
+    let snapshot = cmaes.snapshot();
+
+    let mut other_model = options(CovarianceModel::Separable, 4).build(rosenbrock).unwrap();
+    assert_eq!(
+        Err(InvalidSnapshotError::CovarianceModel),
+        other_model.restore(snapshot.clone())
+    );
+
+    let mut other_rank = options(CovarianceModel::LowRank { rank: 2 }, 4)
+        .build(rosenbrock)
+        .unwrap();
+    let low_rank = options(CovarianceModel::LowRank { rank: 3 }, 4)
+        .build(rosenbrock)
+        .unwrap()
+        .snapshot();
+    assert_eq!(Err(InvalidSnapshotError::CovarianceModel), other_rank.restore(low_rank));
+
+    let mut other_dimensions = CMAESOptions::new(vec![0.5; 4], 0.3).build(rosenbrock).unwrap();
+    assert_eq!(
+        Err(InvalidSnapshotError::Dimensions),
+        other_dimensions.restore(snapshot.clone())
+    );
+
+    let invalid_sigma = Snapshot { sigma: 0.0, ..snapshot };
+    assert_eq!(Err(InvalidSnapshotError::Sigma), cmaes.restore(invalid_sigma));
+}
+
+#[test]
+fn test_warm_start() {
+    // A new run from the final distribution of a previous run needs fewer evaluations than a run
+    // from scratch
+    let target = 1e-10;
+    let mut first = options(CovarianceModel::Full, 5)
+        .fun_target(1e-4)
+        .build(rosenbrock)
+        .unwrap();
+    first.run();
+    let snapshot = first.snapshot();
+    let first_evals = snapshot.function_evals;
+
+    let mut warm = options(CovarianceModel::Full, 5)
+        .fun_target(target)
+        .build(rosenbrock)
+        .unwrap();
+    warm.restore(snapshot).unwrap();
+    let warm_result = warm.run();
+
+    let mut cold = options(CovarianceModel::Full, 5)
+        .fun_target(target)
+        .build(rosenbrock)
+        .unwrap();
+    let cold_result = cold.run();
+
+    assert!(warm_result.overall_best.unwrap().value < target);
+    assert!(cold_result.overall_best.unwrap().value < target);
+    assert!(warm.function_evals() - first_evals < cold.function_evals());
+}
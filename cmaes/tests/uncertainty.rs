@@ -0,0 +1,188 @@
+//! Tests for the uncertainty handling of noisy objective functions
+
+use cmaes::{CMAESOptions, NoiseTreatment, TerminationReason, UncertaintyHandling, CMAES};
+use nalgebra::DVector;
+use rand::distributions::Distribution;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use statrs::distribution::Normal;
+
+const DIM: usize = 8;
+
+fn sphere(x: &DVector<f64>) -> f64 {
+    x.norm_squared()
+}
+
+// The noise does not shrink near the optimum, so its ranking eventually becomes random
+fn noisy_sphere(seed: u64) -> impl FnMut(&DVector<f64>) -> f64 {
+    let mut rng = ChaCha12Rng::seed_from_u64(seed);
+    let normal = Normal::new(0.0, 1e-3).unwrap();
+    move |x| sphere(x) + normal.sample(&mut rng)
+}
+
+fn options(treatment: Option<NoiseTreatment>, seed: u64) -> CMAESOptions {
+    let options = CMAESOptions::new(vec![1.0; DIM], 0.5)
+        .max_function_evals(20_000)
+        .seed(seed);
+
+    match treatment {
+        Some(treatment) => options.uncertainty_handling(UncertaintyHandling {
+            treatment,
+            ..Default::default()
+        }),
+        None => options,
+    }
+}
+
+fn evaluations_treatment() -> Option<NoiseTreatment> {
+    Some(NoiseTreatment::Evaluations { max_evaluations: 20 })
+}
+
+// The standard deviation in the longest principal axis
+fn max_standard_deviation<F>(cmaes: &CMAES<F>) -> f64 {
+    cmaes.sigma() * cmaes.eigenvalues().max().sqrt()
+}
+
+#[test]
+fn test_evaluations_treatment() {
+    let mut distance_without = 0.0;
+    let mut distance_with = 0.0;
+
+    for seed in 0..3 {
+        let mut cmaes = options(None, seed).build(noisy_sphere(seed)).unwrap();
+        let result = cmaes.run();
+        assert!(result.uncertainty.is_none());
+        distance_without += sphere(&result.final_mean);
+
+        let mut cmaes = options(evaluations_treatment(), seed)
+            .build(noisy_sphere(seed))
+            .unwrap();
+        let result = cmaes.run();
+        let uncertainty = result.uncertainty.unwrap();
+
+        // The noise is detected and the run is not stopped by the flat function values
+        assert_eq!(vec![TerminationReason::MaxFunctionEvals], result.reasons);
+        assert!(uncertainty.level > 0.0);
+        assert_eq!(20.0, uncertainty.evaluations);
+        assert!(uncertainty.reevaluations > 0);
+        distance_with += sphere(&result.final_mean);
+    }
+
+    // Averaging the evaluations gets closer to the optimum
+    assert!(
+        distance_with < distance_without,
+        "{} {}",
+        distance_with,
+        distance_without
+    );
+}
+
+#[test]
+fn test_step_size_treatment() {
+    for seed in 0..3 {
+        let mut cmaes = options(None, seed).build(noisy_sphere(seed)).unwrap();
+        cmaes.run();
+        assert!(max_standard_deviation(&cmaes) < 1e-3);
+
+        // The distribution stays wide enough for the function values to be ranked reliably
+        let mut cmaes = options(Some(NoiseTreatment::StepSize), seed)
+            .build(noisy_sphere(seed))
+            .unwrap();
+        cmaes.run();
+        assert!(max_standard_deviation(&cmaes) > 1e-3);
+        assert_eq!(1.0, cmaes.uncertainty().unwrap().evaluations);
+    }
+}
+
+#[test]
+fn test_noise_free() {
+    let mut cmaes = options(evaluations_treatment(), 1)
+        .fun_target(1e-10)
+        .build(sphere)
+        .unwrap();
+    let result = cmaes.run();
+    let uncertainty = result.uncertainty.unwrap();
+
+    // Exact reevaluations never change the ranking
+    assert_eq!(vec![TerminationReason::FunTarget], result.reasons);
+    assert!(uncertainty.level < 0.0);
+    assert_eq!(1.0, uncertainty.evaluations);
+
+    // Reevaluations count as function evaluations
+    let lambda = cmaes.parameters().lambda();
+    assert_eq!(
+        cmaes.generation() * lambda + uncertainty.reevaluations,
+        cmaes.function_evals()
+    );
+}
+
+#[test]
+fn test_ask_tell_matches_next() {
+    let mut cmaes = options(evaluations_treatment(), 1).build(noisy_sphere(1)).unwrap();
+    let expected = cmaes.run();
+
+    let mut ask_tell = options(evaluations_treatment(), 1).build(()).unwrap();
+    let mut function = noisy_sphere(1);
+    let result = loop {
+        let points = ask_tell.ask();
+        let values = points.iter().map(&mut function).collect::<Vec<_>>();
+        if let Some(result) = ask_tell.tell(&values) {
+            break result;
+        }
+    };
+
+    assert_eq!(expected.final_mean, result.final_mean);
+    assert_eq!(expected.uncertainty, result.uncertainty);
+    assert_eq!(cmaes.function_evals(), ask_tell.function_evals());
+}
+
+#[test]
+fn test_restore_continues_run() {
+    let advance = |cmaes: &mut CMAES<fn(&DVector<f64>) -> f64>, generations| {
+        for _ in 0..generations {
+            assert!(cmaes.next().is_none());
+        }
+    };
+    let build = |seed| {
+        options(evaluations_treatment(), seed)
+            .build(sphere as fn(&DVector<f64>) -> f64)
+            .unwrap()
+    };
+
+    let mut uninterrupted = build(1);
+    advance(&mut uninterrupted, 40);
+
+    let mut original = build(1);
+    advance(&mut original, 15);
+
+    let mut restored = build(2);
+    restored.restore(original.snapshot()).unwrap();
+    assert_eq!(original.uncertainty(), restored.uncertainty());
+
+    advance(&mut restored, 25);
+    assert_eq!(uninterrupted.mean(), restored.mean());
+    assert_eq!(uninterrupted.uncertainty(), restored.uncertainty());
+}
+
+#[test]
+fn test_invalid_options() {
+    for uncertainty_handling in [
+        UncertaintyHandling {
+            reevaluations: Some(0.0),
+            ..Default::default()
+        },
+        UncertaintyHandling {
+            cs: 1.5,
+            ..Default::default()
+        },
+        UncertaintyHandling {
+            treatment: NoiseTreatment::Evaluations { max_evaluations: 0 },
+            ..Default::default()
+        },
+    ] {
+        assert!(options(None, 1)
+            .uncertainty_handling(uncertainty_handling)
+            .build(sphere)
+            .is_err());
+    }
+}
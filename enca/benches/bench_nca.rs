@@ -1,8 +1,15 @@
 use criterion::{Criterion, criterion_group, criterion_main};
+#[cfg(feature = "gpu")]
+use enca::executors::gpu::PopNCAExecutorGpuBatch;
 use enca::{
     dataset::Dataset,
-    executors::{Backend, NCAExecutor, gpu::PopNCAExecutorGpuBatch},
+    executors::{
+        Backend, NCAExecutor,
+        cpu::{NCAExecutorCpu, PopNCAExecutorCpuBatch, simd::SimdIsa},
+    },
     nca::NCA,
+    neighborhood::Neighborhood,
+    substrate::SubstrateSpec,
 };
 use itertools::Itertools;
 use rand::SeedableRng;
@@ -11,26 +18,28 @@ use rand_chacha::ChaCha8Rng;
 fn single_grid(c: &mut Criterion) {
     let mut rng = ChaCha8Rng::seed_from_u64(1);
     let tasks_path = "./data/v1/arc-agi_training_challenges.json";
-    let dataset = Dataset::load(&tasks_path, None);
+    let dataset = Dataset::load(tasks_path, None);
 
     let grid_small = &dataset.get_task("794b24be").unwrap().train[0].input; // 3x3 grid
     let grid_large = &dataset.get_task("264363fd").unwrap().train[0].input; // 30x30 grid
 
-    let mut nca = NCA::new(100);
+    let mut nca = NCA::new(100, Neighborhood::default(), SubstrateSpec::default());
     nca.initialize_random(&mut rng);
 
+    let backend = Backend::default();
+
     let mut group = c.benchmark_group("nca_single_grid");
 
     group.bench_function("small", |b| {
         b.iter(|| {
-            let mut executor = NCAExecutor::new(nca.clone(), &grid_small, Backend::GPU);
+            let mut executor = NCAExecutor::new(nca.clone(), grid_small, backend.clone());
             executor.run();
         })
     });
 
     group.bench_function("large", |b| {
         b.iter(|| {
-            let mut executor = NCAExecutor::new(nca.clone(), &grid_large, Backend::GPU);
+            let mut executor = NCAExecutor::new(nca.clone(), grid_large, backend.clone());
             executor.run();
         })
     });
@@ -41,7 +50,7 @@ fn single_grid(c: &mut Criterion) {
 fn multi_grid(c: &mut Criterion) {
     let mut rng = ChaCha8Rng::seed_from_u64(1);
     let tasks_path = "./data/v2/arc-agi_evaluation_challenges.json";
-    let dataset = Dataset::load(&tasks_path, None);
+    let dataset = Dataset::load(tasks_path, None);
     let n_ncas = 10;
 
     let task = &dataset.get_task("36a08778").unwrap();
@@ -49,7 +58,7 @@ fn multi_grid(c: &mut Criterion) {
 
     let ncas = (0..n_ncas)
         .map(|_| {
-            let mut nca = NCA::new(100);
+            let mut nca = NCA::new(100, Neighborhood::default(), SubstrateSpec::default());
             nca.initialize_random(&mut rng);
             nca
         })
@@ -57,6 +66,7 @@ fn multi_grid(c: &mut Criterion) {
 
     let mut group = c.benchmark_group("nca_multi_grid");
 
+    #[cfg(feature = "gpu")]
     group.bench_function("gpu", |b| {
         b.iter(|| {
             let mut executor = PopNCAExecutorGpuBatch::new(ncas.clone(), &grids);
@@ -64,19 +74,57 @@ fn multi_grid(c: &mut Criterion) {
         })
     });
 
-    // group.bench_function("cpu", |b| {
-    //     b.iter(|| {
-    //         for nca in &ncas {
-    //             for grid in &grids {
-    //                 let mut executor = NCAExecutor::new(nca.clone(), grid, Backend::CPU);
-    //                 executor.run();
-    //             }
-    //         }
-    //     })
-    // });
+    group.bench_function("cpu", |b| {
+        b.iter(|| {
+            let mut executor = PopNCAExecutorCpuBatch::new(ncas.clone(), &grids);
+            executor.run();
+        })
+    });
+
+    group.bench_function("cpu_serial", |b| {
+        b.iter(|| {
+            for nca in &ncas {
+                for grid in &grids {
+                    let mut executor = NCAExecutor::new(nca.clone(), grid, Backend::CPU);
+                    executor.run();
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn cpu_kernel(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let tasks_path = "./data/v1/arc-agi_training_challenges.json";
+    let dataset = Dataset::load(tasks_path, None);
+
+    let grid = &dataset.get_task("264363fd").unwrap().train[0].input; // 30x30 grid
+
+    let mut nca = NCA::new(100, Neighborhood::default(), SubstrateSpec::default());
+    nca.initialize_random(&mut rng);
+
+    let mut group = c.benchmark_group("nca_cpu_kernel");
+
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            let mut executor = NCAExecutorCpu::with_isa(nca.clone(), grid, None);
+            executor.run();
+        })
+    });
+
+    if let Some(isa) = SimdIsa::detect() {
+        group.bench_function(format!("simd_{isa:?}").to_lowercase(), |b| {
+            b.iter(|| {
+                let mut executor = NCAExecutorCpu::with_isa(nca.clone(), grid, Some(isa));
+                executor.run();
+            })
+        });
+    }
 
     group.finish();
 }
 
-criterion_group!(benches, single_grid, multi_grid);
+criterion_group!(benches, single_grid, multi_grid, cpu_kernel);
 criterion_main!(benches);
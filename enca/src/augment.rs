@@ -3,17 +3,19 @@ use std::collections::HashSet;
 use indexmap::IndexMap;
 use itertools::Itertools;
 use rand::SeedableRng;
+use rand::seq::SliceRandom;
 use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     config::Config,
     constants::MAX_PERMUTATIONS,
-    dataset::Task,
+    dataset::{Task, TrainExample},
     executors::NCAExecutor,
     grid::Grid,
     nca::NCA,
-    transforms::{RemapColors, Transform},
+    shape::ShapeRule,
+    transforms::{GridTransform, RemapColors, Transform},
     utils::union_sets,
 };
 
@@ -44,7 +46,8 @@ pub fn augment(grid: &Grid, task: &Task, nca: NCA, seed: u64, config: &Config) -
     let k = grid_cols.len();
     let total = n_pk(n, k);
     let mut pred_grid_counts = IndexMap::<u64, (usize, RemapColors)>::new();
-    let empty_grid_hash = Grid::from_vec(vec![vec![0; grid.width()]; grid.height()]).get_hash();
+    let canvas = nca.shape_rule.canvas_or_input(grid);
+    let empty_grid_hash = Grid::from_vec(vec![vec![0; canvas.width]; canvas.height]).get_hash();
 
     for rank in floyd_unique_indices(total, MAX_PERMUTATIONS.min(config.max_fun_evals), &mut rng) {
         // Sample up to MAX_PERMUTATIONS unique color remappings and get majority vote
@@ -101,6 +104,121 @@ pub fn augment(grid: &Grid, task: &Task, nca: NCA, seed: u64, config: &Config) -
     aug_nca
 }
 
+/// Copies of `ncas` that run on each rotation and reflection of the input and revert their predictions, for voting on
+/// the predictions of all of them. Ordered by transform, so the copies that run on the untransformed input come first
+/// and win ties in `voting::vote`.
+pub fn dihedral_ensemble(ncas: &[NCA]) -> Vec<NCA> {
+    Transform::dihedral_group()
+        .into_iter()
+        .flat_map(|transform| {
+            ncas.iter().map(move |nca| {
+                let mut aug_nca = nca.clone();
+                aug_nca.transform_pipeline.steps.push(transform.clone());
+                aug_nca
+            })
+        })
+        .collect()
+}
+
+/// The train examples of `task` followed by augmented copies of them, for computing the fitness on. Copies are
+/// transformed jointly in input and output: by the seven other rotations and reflections with `config.train_dihedral`,
+/// and by `config.train_color_permutations` random permutations of the colors that the task never changes.
+///
+/// A copy is only added if it keeps the task semantics as far as the examples tell: `shape_rule` must predict the size
+/// of its output, and no example may have its input with a different output. Duplicate copies are skipped.
+pub fn augment_examples(task: &Task, shape_rule: ShapeRule, config: &Config, seed: u64) -> Vec<TrainExample> {
+    let mut examples = task.train.clone();
+    let mut transforms = Vec::new();
+
+    if config.train_dihedral {
+        transforms.extend(Transform::dihedral_group().into_iter().skip(1));
+    }
+
+    if config.train_color_permutations > 0 {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let permutations = color_permutations(task, shape_rule, config.train_color_permutations, &mut rng);
+        transforms.extend(permutations.into_iter().map(Transform::RemapColors));
+    }
+
+    for transform in &transforms {
+        for example in &task.train {
+            let mut input = example.input.clone();
+            let mut output = example.output.clone();
+            transform.apply(&mut input);
+            transform.apply(&mut output);
+
+            if shape_rule.predict(&input) != Some(output.shape()) {
+                continue;
+            }
+
+            // An example with the same input either duplicates or contradicts the copy
+            if examples.iter().any(|other| other.input.data() == input.data()) {
+                continue;
+            }
+
+            examples.push(TrainExample { input, output });
+        }
+    }
+
+    examples
+}
+
+/// Up to `n` distinct random permutations of the colors that no train example creates or removes, see
+/// `passive_colors`. The color `shape_rule` crops to is kept in place.
+fn color_permutations(task: &Task, shape_rule: ShapeRule, n: usize, rng: &mut ChaCha8Rng) -> Vec<RemapColors> {
+    let colors = passive_colors(task)
+        .into_iter()
+        .filter(|&color| shape_rule != ShapeRule::ColorBBox { color })
+        .collect_vec();
+
+    if colors.len() < 2 {
+        return Vec::new();
+    }
+
+    let mut seen = HashSet::from([colors.clone()]);
+    let mut permutations = Vec::with_capacity(n);
+
+    // Few colors allow fewer than `n` permutations, so the number of attempts is limited
+    for _ in 0..10 * n {
+        if permutations.len() == n {
+            break;
+        }
+
+        let mut permuted = colors.clone();
+        permuted.shuffle(rng);
+        if !seen.insert(permuted.clone()) {
+            continue;
+        }
+
+        let mut permutation = RemapColors::new();
+        for (&color, &mapped) in colors.iter().zip(&permuted) {
+            permutation.map(color, mapped);
+        }
+        permutations.push(permutation);
+    }
+
+    permutations
+}
+
+/// Colors other than the background that are in the same cells of the input and output of every train example.
+/// Examples whose output differs in size from their input must not contain the color at all. Includes the colors that
+/// no train example uses.
+fn passive_colors(task: &Task) -> Vec<u8> {
+    (1..10)
+        .filter(|&color| {
+            task.train.iter().all(|example| {
+                let (input, output) = (&example.input, &example.output);
+                if input.shape() != output.shape() {
+                    return !input.colors().contains(&color) && !output.colors().contains(&color);
+                }
+
+                let (height, width) = input.shape();
+                (0..height).all(|y| (0..width).all(|x| (input[(y, x)] == color) == (output[(y, x)] == color)))
+            })
+        })
+        .collect()
+}
+
 /// The trained NCA and augmented NCAs for each test problem.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TaskNCAs {
@@ -5,7 +5,7 @@
  */
 
 use clap::Parser;
-use enca::{criteria::train_preserves_grid_size, dataset::Dataset};
+use enca::{criteria::train_preserves_grid_size, dataset::Dataset, shape::ShapeRule};
 
 /// If:
 /// - For every train example, the input size equals the output size.
@@ -50,6 +50,36 @@ fn assert_test_preserves_grid_size_when_train_does(dataset: &Dataset, verbose: b
     }
 }
 
+/// Not an assertion: output shape rules are inferred from the train examples and need not hold on the test examples.
+/// Reports how many tasks have a rule and how many test output sizes it predicts correctly.
+fn report_output_shape_rules(dataset: &Dataset, verbose: bool) {
+    let total = dataset.tasks.len();
+    let mut task_rule_count = 0;
+    let mut grids_rule_count = 0;
+    let mut grids_correct_count = 0;
+
+    for (task, solution) in dataset.tasks.iter().zip(dataset.solutions.as_ref().unwrap()) {
+        let Some(rule) = ShapeRule::infer(task) else {
+            continue;
+        };
+        task_rule_count += 1;
+        grids_rule_count += task.test.len();
+
+        for (input, output) in task.test.iter().map(|x| &x.input).zip(&solution.outputs) {
+            if rule.predict(input) == Some(output.shape()) {
+                grids_correct_count += 1;
+            }
+        }
+    }
+
+    if verbose {
+        let pct = (task_rule_count as f64 * 100.0) / (total as f64);
+        println!("Output shape rule found for {task_rule_count}/{total} tasks ({pct:.1}%)");
+        let pct = (grids_correct_count as f64 * 100.0) / (grids_rule_count as f64);
+        println!("Output shape rule correct for {grids_correct_count}/{grids_rule_count} test grids ({pct:.1}%)");
+    }
+}
+
 #[derive(Parser, Debug)]
 struct Args {
     #[arg()]
@@ -101,6 +131,7 @@ fn main() {
             println!("========{name}=========");
         }
         assert_test_preserves_grid_size_when_train_does(dataset, args.verbose);
+        report_output_shape_rules(dataset, args.verbose);
 
         if args.verbose {
             println!("======================")
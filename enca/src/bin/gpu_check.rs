@@ -1,7 +1,10 @@
 use enca::{
+    color::ColorEncoding,
     dataset::Dataset,
-    executors::{Backend, NCAExecutor, gpu::PopNCAExecutorGpuBatch},
+    executors::{Backend, NCAExecutor, cpu::PopNCAExecutorCpuBatch, gpu::PopNCAExecutorGpuBatch},
     nca::NCA,
+    neighborhood::Neighborhood,
+    substrate::SubstrateSpec,
 };
 use itertools::Itertools;
 use rand::{Rng, SeedableRng};
@@ -14,14 +17,25 @@ fn main() {
     let pop_size = 4;
 
     for task in &train_dataset.tasks {
-        let max_steps = rng.random_range(1..=120);
-        let pop_ncas = (0..pop_size).map(|_| random_nca(&mut rng, max_steps)).collect_vec();
+        let neighborhood = random_neighborhood(&mut rng);
+        let spec = random_substrate_spec(&mut rng);
+        let pop_ncas = (0..pop_size)
+            .map(|_| {
+                // Individuals of a batch may run for different numbers of steps
+                let max_steps = rng.random_range(1..=120);
+                random_nca(&mut rng, max_steps, neighborhood, spec)
+            })
+            .collect_vec();
 
         let mut pop_gpu_executor =
             PopNCAExecutorGpuBatch::new(pop_ncas.clone(), &task.train_inputs().into_iter().collect_vec());
 
         pop_gpu_executor.run();
 
+        let mut pop_cpu_executor = PopNCAExecutorCpuBatch::new(pop_ncas.clone(), &task.train_inputs());
+
+        pop_cpu_executor.run();
+
         for (ind_idx, nca) in pop_ncas.iter().enumerate() {
             for (idx, input) in task.train_inputs().iter().enumerate() {
                 let backend = Backend::CPU;
@@ -31,6 +45,15 @@ fn main() {
                 let gpu_hash = pop_gpu_executor.individuals[ind_idx].substrates[idx]
                     .to_grid()
                     .get_hash();
+                let cpu_batch_substrate = &pop_cpu_executor.individuals[ind_idx].substrates[idx];
+                let gpu_substrate = &pop_gpu_executor.individuals[ind_idx].substrates[idx];
+
+                if cpu_batch_substrate.data != gpu_substrate.data {
+                    panic!(
+                        "Substrate mismatch between CPU batch and GPU on task {}, ind idx {}, input grid id {}",
+                        task.id, ind_idx, idx,
+                    );
+                }
 
                 if cpu_hash != gpu_hash {
                     panic!(
@@ -51,8 +74,30 @@ fn main() {
     println!("GPU and CPU results match exactly!")
 }
 
-fn random_nca(rng: &mut impl Rng, max_steps: usize) -> NCA {
-    let mut nca = NCA::new(max_steps);
+fn random_neighborhood(rng: &mut impl Rng) -> Neighborhood {
+    let radius = rng.random_range(1..=2);
+    if rng.random_bool(0.5) {
+        Neighborhood::VonNeumann { radius }
+    } else {
+        Neighborhood::Moore { radius }
+    }
+}
+
+fn random_substrate_spec(rng: &mut impl Rng) -> SubstrateSpec {
+    let encoding = if rng.random_bool(0.5) {
+        ColorEncoding::Binary
+    } else {
+        ColorEncoding::OneHot
+    };
+
+    SubstrateSpec {
+        hid_chs: rng.random_range(0..=4),
+        encoding,
+    }
+}
+
+fn random_nca(rng: &mut impl Rng, max_steps: usize, neighborhood: Neighborhood, spec: SubstrateSpec) -> NCA {
+    let mut nca = NCA::new(max_steps, neighborhood, spec);
     nca.initialize_random(rng);
     nca
 }
@@ -1,4 +1,7 @@
-use enca::{dataset::Dataset, executors::gpu::PopNCAExecutorGpuBatch, nca::NCA};
+use enca::{
+    dataset::Dataset, executors::gpu::PopNCAExecutorGpuBatch, nca::NCA, neighborhood::Neighborhood,
+    substrate::SubstrateSpec,
+};
 use itertools::Itertools;
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
@@ -6,7 +9,7 @@ use rand_chacha::ChaCha8Rng;
 fn main() {
     let mut rng = ChaCha8Rng::seed_from_u64(1);
     let tasks_path = "./data/v2/arc-agi_evaluation_challenges.json";
-    let dataset = Dataset::load(&tasks_path, None);
+    let dataset = Dataset::load(tasks_path, None);
     let n_ncas = 10;
 
     let task = &dataset.get_task("36a08778").unwrap();
@@ -14,7 +17,7 @@ fn main() {
 
     let ncas = (0..n_ncas)
         .map(|_| {
-            let mut nca = NCA::new(100);
+            let mut nca = NCA::new(100, Neighborhood::default(), SubstrateSpec::default());
             nca.initialize_random(&mut rng);
             nca
         })
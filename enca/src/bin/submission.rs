@@ -1,14 +1,13 @@
 use std::time::Instant;
 
 use clap::Parser;
-use enca::augment::augment;
+use enca::augment::{augment, dihedral_ensemble};
 use enca::config::Config;
-use enca::criteria::train_preserves_grid_size;
-use enca::dataset::{Submission, TestSubmissionOutput};
+use enca::dataset::{Submission, Task, TestSubmissionOutput};
 use enca::env::inference;
-use enca::executors::Backend;
-use enca::executors::gpu::CUDA;
+use enca::scheduler::{TimeBudget, run_jobs};
 use enca::serde_utils::JSONReadWrite;
+use enca::shape::ShapeRule;
 use enca::utils::mean;
 use enca::voting::vote;
 use enca::{dataset::Dataset, solver::train};
@@ -27,6 +26,9 @@ struct Args {
     /// Config file path
     #[arg(short = 'c', long)]
     config_path: Option<String>,
+    /// Number of tasks solved at once. The threads are split evenly between them
+    #[arg(short = 'j', long, default_value_t = 1)]
+    jobs: usize,
 }
 
 fn main() {
@@ -42,16 +44,19 @@ fn main() {
         Config::default()
     };
 
-    // Initialize GPUs
-    if config.backend == Backend::GPU {
-        _ = &*CUDA;
-    }
+    config.validate().unwrap_or_else(|e| panic!("Invalid config: {e}"));
+
+    // Initialize GPUs if the GPU backend is selected
+    config.backend.init();
 
     println!("Loaded tasks from '{}': tasks={}", tasks_path, dataset.tasks.len());
 
     let start = Instant::now();
 
     let tasks = dataset.tasks;
+    // Only the tasks that are trained share the total time budget
+    let n_trained = tasks.iter().filter(|task| ShapeRule::infer(task).is_some()).count();
+    let budget = TimeBudget::new(n_trained, args.jobs);
 
     let pb = ProgressBar::new(tasks.len() as u64);
     pb.set_style(
@@ -62,57 +67,67 @@ fn main() {
         .progress_chars("##-"),
     );
 
-    let results: Vec<(String, Vec<TestSubmissionOutput>)> = tasks
-        .iter()
-        .map(|task| {
-            pb.inc(1);
-            let default_output = (task.id.clone(), vec![TestSubmissionOutput::default(); task.test.len()]);
-            if !train_preserves_grid_size(task) {
-                return default_output;
-            }
-
-            let train_result = train(task, false, &config, seed);
-
-            let mut test_submission_outputs: Vec<TestSubmissionOutput> = Vec::with_capacity(task.test.len());
-
-            let solved_train = train_result
-                .clone()
-                .into_iter()
-                .filter(|result| mean(&result.train_accs) == 1.0)
+    let solve_task = |task: &Task| {
+        let default_output = (task.id.clone(), vec![TestSubmissionOutput::default(); task.test.len()]);
+        if ShapeRule::infer(task).is_none() {
+            return default_output;
+        }
+
+        // Tasks that start after the total time budget is used up are not trained
+        let Some(task_config) = budget.task_config(&config) else {
+            return default_output;
+        };
+
+        let train_result = train(task, false, &task_config, seed);
+
+        let mut test_submission_outputs: Vec<TestSubmissionOutput> = Vec::with_capacity(task.test.len());
+
+        let solved_train = train_result
+            .clone()
+            .into_iter()
+            .filter(|result| mean(&result.train_accs) == 1.0)
+            .collect_vec();
+
+        if solved_train.is_empty() {
+            return default_output;
+        }
+
+        let selected_train = if solved_train.is_empty() {
+            train_result
+        } else {
+            solved_train
+        };
+
+        for input in task.test_inputs() {
+            let mut aug_enca = selected_train
+                .iter()
+                .map(|result| augment(input, task, result.nca.clone(), seed, &config))
                 .collect_vec();
-
-            if solved_train.is_empty() {
-                return default_output;
+            if config.dihedral_ensemble {
+                aug_enca = dihedral_ensemble(&aug_enca);
             }
+            let top_k_aug_ncas = vote(input, &aug_enca, 2, false, config.backend.clone());
 
-            let selected_train = if solved_train.is_empty() {
-                train_result
+            let pred_grid = inference(input, &top_k_aug_ncas[0], config.backend.clone());
+
+            let attempt_1 = pred_grid.data().clone();
+            let attempt_2 = if top_k_aug_ncas.len() >= 2 {
+                let pred_grid = inference(input, &top_k_aug_ncas[1], config.backend.clone());
+                pred_grid.data().clone()
             } else {
-                solved_train
+                attempt_1.clone()
             };
 
-            for input in task.test_inputs() {
-                let aug_enca = selected_train
-                    .iter()
-                    .map(|result| augment(input, task, result.nca.clone(), seed, &config))
-                    .collect_vec();
-                let top_k_aug_ncas = vote(input, &aug_enca, 2, false, config.backend.clone());
-
-                let pred_grid = inference(input, &top_k_aug_ncas[0], config.backend.clone());
-
-                let attempt_1 = pred_grid.data().clone();
-                let attempt_2 = if top_k_aug_ncas.len() >= 2 {
-                    let pred_grid = inference(input, &top_k_aug_ncas[1], config.backend.clone());
-                    pred_grid.data().clone()
-                } else {
-                    attempt_1.clone()
-                };
+            test_submission_outputs.push(TestSubmissionOutput { attempt_1, attempt_2 });
+        }
+        (task.id.clone(), test_submission_outputs)
+    };
 
-                test_submission_outputs.push(TestSubmissionOutput { attempt_1, attempt_2 });
-            }
-            (task.id.clone(), test_submission_outputs)
-        })
-        .collect();
+    let results: Vec<(String, Vec<TestSubmissionOutput>)> = run_jobs(&tasks, args.jobs, |task| {
+        let output = solve_task(task);
+        pb.inc(1);
+        output
+    });
 
     pb.finish_and_clear();
     let total_elapsed_ms = start.elapsed().as_millis();
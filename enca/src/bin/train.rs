@@ -1,17 +1,18 @@
 use std::fs;
+use std::sync::Mutex;
 use std::time::Instant;
 
 use clap::Parser;
-use enca::augment::{TaskNCAs, augment};
+use enca::augment::{TaskNCAs, augment, dihedral_ensemble};
 use enca::config::Config;
-use enca::criteria::train_preserves_grid_size;
-use enca::executors::Backend;
-use enca::executors::gpu::CUDA;
-use enca::metrics::{OverallSummary, TaskReport, TrainOutput};
+use enca::dataset::{Solution, Task};
+use enca::metrics::{OverallSummary, RunInfo, TaskReport, TrainOutput};
+use enca::scheduler::{TimeBudget, run_jobs};
 use enca::serde_utils::JSONReadWrite;
+use enca::shape::ShapeRule;
 use enca::utils::{mean, timestamp_for_dir};
 use enca::voting::vote;
-use enca::{dataset::Dataset, env::eval, solver::train};
+use enca::{dataset::Dataset, env::eval, solver::train_with_history};
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 
@@ -34,12 +35,18 @@ struct Args {
     /// Run output directory. Defaults to a timestamped directory in runs/
     #[arg(short = 'r', long)]
     out_dir: Option<String>,
+    /// Output directory of an interrupted run to resume. Tasks with metrics are not trained again
+    #[arg(long, conflicts_with = "out_dir")]
+    resume: Option<String>,
     /// Seed for reproducibility
     #[arg(short = 's', long)]
     seed: Option<u64>,
     /// Config file path
     #[arg(short = 'c', long)]
     config_path: Option<String>,
+    /// Number of tasks trained at once. The threads are split evenly between them
+    #[arg(short = 'j', long, default_value_t = 1)]
+    jobs: usize,
 }
 
 fn main() {
@@ -47,23 +54,57 @@ fn main() {
     let tasks_path = args.tasks_path;
     let solutions_path = args.solutions_path;
     let verbose = args.id.is_some();
-    let config = if let Some(config_path) = args.config_path {
+    let resume = args.resume.is_some();
+    let arg_config = args.config_path.map(|config_path| {
         Config::read_json(&config_path)
             .unwrap_or_else(|e| panic!("Failed to read config file '{}': {}", &config_path, e))
+    });
+
+    let (out_dir, config, seed, resumed_ms) = if let Some(out_dir) = args.resume {
+        // The resumed tasks must be trained exactly as the completed ones
+        let config_path = format!("{out_dir}/config.json");
+        let config = Config::read_json(&config_path)
+            .unwrap_or_else(|e| panic!("Failed to read config file '{}' of resumed run: {}", config_path, e));
+        let run_info_path = format!("{out_dir}/run.json");
+        let run_info = RunInfo::read_json(&run_info_path)
+            .unwrap_or_else(|e| panic!("Failed to read run info '{}' of resumed run: {}", run_info_path, e));
+
+        if let Some(arg_config) = &arg_config
+            && *arg_config != config
+        {
+            panic!(
+                "Config {:?} does not match config {:?} of resumed run",
+                arg_config, config
+            );
+        }
+
+        if let Some(seed) = args.seed
+            && seed != run_info.seed
+        {
+            panic!("Seed {} does not match seed {} of resumed run", seed, run_info.seed);
+        }
+
+        println!("Resuming run in '{}'", out_dir);
+        (out_dir, config, run_info.seed, run_info.elapsed_ms)
     } else {
-        Config::default()
+        let out_dir = if let Some(out_dir) = args.out_dir {
+            out_dir
+        } else {
+            let timestamp = timestamp_for_dir();
+            format!("runs/{timestamp}")
+        };
+        let seed = if let Some(seed) = args.seed {
+            seed
+        } else {
+            rand::random()
+        };
+        (out_dir, arg_config.unwrap_or_default(), seed, 0)
     };
 
-    // Initialize GPUs
-    if config.backend == Backend::GPU {
-        _ = &*CUDA;
-    }
+    config.validate().unwrap_or_else(|e| panic!("Invalid config: {e}"));
 
-    let seed = if let Some(seed) = args.seed {
-        seed
-    } else {
-        rand::random()
-    };
+    // Initialize GPUs if the GPU backend is selected
+    config.backend.init();
 
     let dataset = Dataset::load(&tasks_path, Some(&solutions_path));
     println!(
@@ -73,13 +114,6 @@ fn main() {
         dataset.tasks.len()
     );
 
-    let out_dir = if let Some(out_dir) = args.out_dir {
-        out_dir
-    } else {
-        let timestamp = timestamp_for_dir();
-        format!("runs/{timestamp}")
-    };
-
     let metrics_dir = format!("{out_dir}/metrics");
     let model_dir = format!("{out_dir}/models");
 
@@ -88,6 +122,19 @@ fn main() {
         .unwrap_or_else(|e| panic!("Failed to create metrics_dir '{}': {}", metrics_dir, e));
     fs::create_dir_all(&model_dir).unwrap_or_else(|e| panic!("Failed to create model_dir: {}", e));
 
+    // Written up front so the run can be resumed if it is interrupted
+    let config_path = format!("{out_dir}/config.json");
+    config
+        .write_json(&config_path)
+        .unwrap_or_else(|e| panic!("Failed to create config file '{}': {}", config_path, e));
+    let run_info_path = format!("{out_dir}/run.json");
+    let write_run_info = |elapsed_ms| {
+        RunInfo { seed, elapsed_ms }
+            .write_json(&run_info_path)
+            .unwrap_or_else(|e| panic!("Failed to create run info file '{}': {}", run_info_path, e));
+    };
+    write_run_info(resumed_ms);
+
     if let Some(id) = &args.id {
         println!("Running train for task with id : {}", id);
     }
@@ -118,7 +165,26 @@ fn main() {
 
     let start = Instant::now();
 
+    // Metrics are written last, so a task with readable metrics has completed. Unreadable metrics are from a task
+    // interrupted while writing them and it is trained again.
+    let completed_report = |task: &Task| {
+        resume
+            .then(|| TaskReport::read_json(&format!("{metrics_dir}/{}.json", task.id)).ok())
+            .flatten()
+    };
+
+    // The NCA runs on the predicted output canvas, so tasks need a shape rule consistent with every train example.
+    // See `assertions.rs` for how often the rule also holds on the test examples.
+    let trains = |task: &Task| ShapeRule::infer(task).is_some();
+
+    // Only the tasks that are trained share the total time budget
+    let n_trained = tasks
+        .iter()
+        .filter(|task| trains(task) && completed_report(task).is_none())
+        .count();
+
     let total = tasks.len() as u64;
+    let budget = TimeBudget::new(n_trained, args.jobs);
 
     let pb = ProgressBar::new(total);
     pb.set_style(
@@ -129,110 +195,140 @@ fn main() {
         .progress_chars("##-"),
     );
 
-    let results: Vec<TestOutcome> = tasks_and_solutions
-        .iter()
-        .map(|(task, solution)| {
-            let start = Instant::now();
-            if !verbose {
-                pb.inc(1);
-            }
+    let train_task = |(task, solution): &(&Task, &Solution)| {
+        let start = Instant::now();
 
-            let task_id = &task.id;
+        let task_id = &task.id;
+        let metrics_path = format!("{metrics_dir}/{task_id}.json");
 
-            let default_outcome = TestOutcome {
-                count: task.test.len(),
-                correct: 0,
+        if let Some(report) = completed_report(task) {
+            return TestOutcome {
+                count: report.n_examples_test,
+                correct: report.test_accs.iter().filter(|acc| **acc == 1.0).count(),
             };
-
-            // Test task io shapes match when train task shapes are all the same.
-            // We check this property for all data in `assertions.rs`
-            if !train_preserves_grid_size(task) {
-                return default_outcome;
-            }
-
-            let train_result = train(task, verbose, &config, seed);
-
-            let best_train_result = train_result[0].clone();
-            let TrainOutput { train_accs, .. } = best_train_result;
-
-            let mut test_ncas = Vec::with_capacity(task.test.len());
-
-            let mut test_accs = Vec::with_capacity(solution.outputs.len());
-            let solved_train = train_result
-                .clone()
-                .into_iter()
-                .filter(|result| mean(&result.train_accs) == 1.0)
+        }
+
+        let default_outcome = TestOutcome {
+            count: task.test.len(),
+            correct: 0,
+        };
+
+        if !trains(task) {
+            return default_outcome;
+        }
+
+        // Tasks that start after the total time budget is used up are not trained
+        let Some(task_config) = budget.task_config(&config) else {
+            return default_outcome;
+        };
+
+        let (train_result, history) = train_with_history(task, verbose, &task_config, seed);
+
+        let best_train_result = train_result[0].clone();
+        let TrainOutput { train_accs, .. } = best_train_result;
+
+        let mut test_ncas = Vec::with_capacity(task.test.len());
+
+        let mut test_accs = Vec::with_capacity(solution.outputs.len());
+        let solved_train = train_result
+            .clone()
+            .into_iter()
+            .filter(|result| mean(&result.train_accs) == 1.0)
+            .collect_vec();
+
+        let selected_train = if solved_train.is_empty() {
+            train_result
+        } else {
+            solved_train
+        };
+
+        for (input, output) in task.test_inputs().iter().zip(&solution.outputs) {
+            let mut aug_ncas = selected_train
+                .iter()
+                .map(|result| augment(input, task, result.nca.clone(), seed, &config))
                 .collect_vec();
-
-            let selected_train = if solved_train.is_empty() {
-                train_result
-            } else {
-                solved_train
-            };
-
-            for (input, output) in task.test_inputs().iter().zip(&solution.outputs) {
-                let aug_ncas = selected_train
-                    .iter()
-                    .map(|result| augment(input, task, result.nca.clone(), seed, &config))
-                    .collect_vec();
-                let top_k_aug_ncas = vote(input, &aug_ncas, 2, verbose, config.backend.clone());
-
-                let top_aug_nca = if top_k_aug_ncas.len() >= 2 {
-                    let attempt_1_acc = eval(input, output, &top_k_aug_ncas[0], config.backend.clone());
-                    let attempt_2_acc = eval(input, output, &top_k_aug_ncas[1], config.backend.clone());
-                    if attempt_1_acc > attempt_2_acc {
-                        &top_k_aug_ncas[0]
-                    } else {
-                        &top_k_aug_ncas[1]
-                    }
-                } else {
-                    &top_k_aug_ncas[0]
-                };
-
-                test_accs.push(eval(input, output, top_aug_nca, config.backend.clone()));
-                test_ncas.push(top_aug_nca.clone());
-            }
-
-            let elapsed = start.elapsed().as_millis();
-
-            let task_ncas = TaskNCAs {
-                train: best_train_result.nca,
-                test: test_ncas,
-            };
-
-            let train_mean = mean(&train_accs);
-            let test_mean = mean(&test_accs);
-
-            if verbose {
-                println!("\n==> Task {}", task_id);
-                println!("train_accs(%)={:?} | mean={:.5}", &train_accs, train_mean);
-                println!("test_accs(%)={:?} | mean={:.5}", test_accs, test_mean);
+            if config.dihedral_ensemble {
+                aug_ncas = dihedral_ensemble(&aug_ncas);
             }
+            let top_k_aug_ncas = vote(input, &aug_ncas, 2, verbose, config.backend.clone());
 
-            let nca_path = format!("{model_dir}/{task_id}.json");
-            task_ncas.write_json(&nca_path).unwrap();
-
-            let report = TaskReport {
-                task_id: task_id.clone(),
-                n_examples_train: task.train.len(),
-                n_examples_test: task.test.len(),
-                train_accs: train_accs.clone(),
-                test_accs: test_accs.clone(),
-                duration_ms: Some(elapsed as usize),
+            let top_aug_nca = if top_k_aug_ncas.len() >= 2 {
+                let attempt_1_acc = eval(input, output, &top_k_aug_ncas[0], config.backend.clone());
+                let attempt_2_acc = eval(input, output, &top_k_aug_ncas[1], config.backend.clone());
+                if attempt_1_acc > attempt_2_acc {
+                    &top_k_aug_ncas[0]
+                } else {
+                    &top_k_aug_ncas[1]
+                }
+            } else {
+                &top_k_aug_ncas[0]
             };
-            let metrics_path = format!("{metrics_dir}/{task_id}.json");
-            report
-                .write_json(&metrics_path)
-                .unwrap_or_else(|e| panic!("Failed to create metrics file '{}': {}", metrics_path, e));
 
-            let test_correct = test_accs.iter().filter(|acc| **acc == 1.0).collect_vec().len();
+            test_accs.push(eval(input, output, top_aug_nca, config.backend.clone()));
+            test_ncas.push(top_aug_nca.clone());
+        }
+
+        let elapsed = start.elapsed().as_millis();
+
+        let task_ncas = TaskNCAs {
+            train: best_train_result.nca,
+            test: test_ncas,
+        };
+
+        let train_mean = mean(&train_accs);
+        let test_mean = mean(&test_accs);
+
+        if verbose {
+            println!("\n==> Task {}", task_id);
+            println!("train_accs(%)={:?} | mean={:.5}", &train_accs, train_mean);
+            println!("test_accs(%)={:?} | mean={:.5}", test_accs, test_mean);
+        }
+
+        let nca_path = format!("{model_dir}/{task_id}.json");
+        task_ncas
+            .write_json(&nca_path)
+            .unwrap_or_else(|e| panic!("Failed to create model file '{}': {}", nca_path, e));
+
+        let history_path = format!("{metrics_dir}/{task_id}.history.json");
+        history
+            .write_json(&history_path)
+            .unwrap_or_else(|e| panic!("Failed to create history file '{}': {}", history_path, e));
+
+        let report = TaskReport {
+            task_id: task_id.clone(),
+            n_examples_train: task.train.len(),
+            n_examples_test: task.test.len(),
+            train_accs: train_accs.clone(),
+            test_accs: test_accs.clone(),
+            duration_ms: Some(elapsed as usize),
+        };
+        report
+            .write_json(&metrics_path)
+            .unwrap_or_else(|e| panic!("Failed to create metrics file '{}': {}", metrics_path, e));
+
+        let test_correct = test_accs.iter().filter(|acc| **acc == 1.0).collect_vec().len();
+
+        TestOutcome {
+            count: task.test.len(),
+            correct: test_correct,
+        }
+    };
 
-            TestOutcome {
-                count: task.test.len(),
-                correct: test_correct,
-            }
-        })
-        .collect();
+    // Serializes the updates of the elapsed time in `run.json` between jobs
+    let run_info_lock = Mutex::new(());
+
+    let results: Vec<TestOutcome> = run_jobs(&tasks_and_solutions, args.jobs, |task_and_solution| {
+        let outcome = train_task(task_and_solution);
+        {
+            let _guard = run_info_lock.lock().unwrap();
+            write_run_info(resumed_ms + start.elapsed().as_millis());
+        }
+        // Counted on completion so the bar stays correct when several tasks run at once
+        if !verbose {
+            pb.inc(1);
+        }
+        outcome
+    });
 
     if !verbose {
         pb.finish();
@@ -242,7 +338,7 @@ fn main() {
     let test_correct: usize = results.iter().map(|r| r.correct).sum();
     let n_tasks: usize = results.len();
 
-    let total_elapsed_ms = start.elapsed().as_millis();
+    let total_elapsed_ms = resumed_ms + start.elapsed().as_millis();
     let test_accuracy = test_correct as f32 / count as f32 * 100.0;
 
     let summary = OverallSummary {
@@ -259,11 +355,6 @@ fn main() {
         .write_json(&summary_path)
         .unwrap_or_else(|e| panic!("Failed to create summary file '{}': {}", summary_path, e));
 
-    let config_path = format!("{out_dir}/config.json");
-    config
-        .write_json(&config_path)
-        .unwrap_or_else(|e| panic!("Failed to create config file '{}': {}", config_path, e));
-
     println!("==== Overall Summary ====");
     println!("tasks={}, total_test_grids={}", n_tasks, count);
     println!("total_test_correct={}", test_correct);
@@ -1,4 +1,39 @@
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// How colors are encoded in the visible channels of a substrate.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub enum ColorEncoding {
+    /// 4-channel code from `ENCODING`
+    #[default]
+    Binary,
+    /// One channel per color from `ONE_HOT_ENCODING`
+    OneHot,
+}
+
+impl ColorEncoding {
+    /// Number of channels used to encode a color
+    pub fn n_chs(&self) -> usize {
+        match self {
+            ColorEncoding::Binary => ENCODING[0].len(),
+            ColorEncoding::OneHot => ONE_HOT_ENCODING[0].len(),
+        }
+    }
+
+    pub fn encode(&self, color: u8) -> &'static [f32] {
+        match self {
+            ColorEncoding::Binary => &ENCODING[color as usize],
+            ColorEncoding::OneHot => &ONE_HOT_ENCODING[color as usize],
+        }
+    }
+
+    pub fn decode(&self, encoded: &[f32]) -> u8 {
+        match self {
+            ColorEncoding::Binary => decode_color(encoded, &ENCODING),
+            ColorEncoding::OneHot => decode_color(encoded, &ONE_HOT_ENCODING),
+        }
+    }
+}
 
 pub const ENCODING: [[f32; 4]; 10] = [
     [0., 0., 0., 0.],
@@ -13,13 +48,24 @@ pub const ENCODING: [[f32; 4]; 10] = [
     [0., 0., 1., 1.],
 ];
 
+pub const ONE_HOT_ENCODING: [[f32; 10]; 10] = {
+    let mut encoding = [[0.; 10]; 10];
+    let mut i = 0;
+    while i < 10 {
+        encoding[i][i] = 1.;
+        i += 1;
+    }
+    encoding
+};
+
+/// Returns the color whose prototype best matches the thresholded `encoded` channels.
 #[inline]
-pub fn decode_color(encoded: &[f32]) -> u8 {
+pub fn decode_color<const N: usize>(encoded: &[f32], prototypes: &[[f32; N]; 10]) -> u8 {
     let encoded = encoded.iter().map(|v| if *v > 0.5 { 1.0 } else { 0.0 }).collect_vec();
 
     let mut best_idx: u8 = 0;
     let mut best_dot = f32::NEG_INFINITY;
-    for (i, proto) in ENCODING.iter().enumerate() {
+    for (i, proto) in prototypes.iter().enumerate() {
         let dot = proto.iter().zip(encoded.iter()).map(|(a, b)| a * b).sum::<f32>();
         if dot > best_dot {
             best_dot = dot;
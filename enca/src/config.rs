@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::executors::Backend;
+use crate::{executors::Backend, neighborhood::Neighborhood, substrate::SubstrateSpec, transforms::Transform};
 
 /// Hyperparameters for the ENCA algorithm
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Config {
     /// Number of epochs for the evolutionary loop
     pub epochs: usize,
@@ -22,8 +22,219 @@ pub struct Config {
     pub initial_sigma: f64,
     /// L2 weight decay coefficient
     pub l2_coeff: f64,
-    /// Inference backend; GPU or CPU
+    /// Inference backend; GPU or CPU. Defaults to GPU only when built with the `gpu` feature.
     pub backend: Backend,
+    /// Neighborhood of the trained NCAs
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+    /// Hidden channel count and color encoding of the trained NCAs
+    #[serde(default)]
+    pub substrate_spec: SubstrateSpec,
+    /// Wall-clock budget for training one task in seconds. Unlimited when not set
+    #[serde(default)]
+    pub task_time_budget_secs: Option<f64>,
+    /// Wall-clock budget for all tasks of a run in seconds, shared between the tasks that are still to run.
+    /// Unlimited when not set
+    #[serde(default)]
+    pub total_time_budget_secs: Option<f64>,
+    /// Restart strategy for the CMA-ES run of each individual. A stagnated run restarts from the individual's
+    /// parameters with the remaining evaluations instead of stopping. Single run when not set
+    #[serde(default)]
+    pub restarts: Option<Restarts>,
+    /// Covariance matrix representation used by CMA-ES. Separable and low-rank scale to a `subset_size` covering
+    /// all parameters of the NCA
+    #[serde(default)]
+    pub covariance_model: CovarianceModel,
+    /// Continue the CMA-ES run of each individual from the distribution of its previous epoch while the runs end on
+    /// the `max_fun_evals` limit, keeping the same parameter subset. Ignored with `restarts`
+    #[serde(default)]
+    pub warm_start: bool,
+    /// Bound on the magnitude of every parameter searched by CMA-ES. Parameters that start out of bounds are clamped
+    /// into them. Unbounded when not set
+    #[serde(default)]
+    pub param_bound: Option<f64>,
+    /// How CMA-ES handles sampled parameters outside of `param_bound`
+    #[serde(default)]
+    pub boundary_handling: BoundaryHandling,
+    /// Objectives optimized jointly by MO-CMA-ES instead of the error plus `l2_coeff` times the mean squared weight.
+    /// Each run keeps the Pareto front of the objectives and returns its point that is best in the first objective.
+    /// Single-objective when empty. `restarts`, `warm_start` and `param_bound` do not apply
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+    /// Evolution strategy that optimizes the parameter subset of each individual. `restarts`, `covariance_model`,
+    /// `warm_start` and `boundary_handling` only apply to CMA-ES; the other strategies clamp to `param_bound`
+    #[serde(default)]
+    pub optimizer: Optimizer,
+    /// Lowest and highest number of NCA steps. When set, CMA-ES searches the step count of each individual as an
+    /// integer together with its parameters, starting from `max_steps`. Only applies to single-objective CMA-ES;
+    /// every NCA runs `max_steps` steps otherwise
+    #[serde(default)]
+    pub step_range: Option<(usize, usize)>,
+    /// Run every trained NCA on the eight rotations and reflections of each test input and vote on the reverted
+    /// predictions, so predictions that agree across orientations get more votes
+    #[serde(default)]
+    pub dihedral_ensemble: bool,
+    /// Add the rotations and reflections of each train example to the examples the fitness is computed on. Copies
+    /// whose output size the shape rule does not predict, or whose input has another output, are left out. The
+    /// acceptance of new parameters still uses the accuracy on the original examples
+    #[serde(default)]
+    pub train_dihedral: bool,
+    /// Number of random permutations of the colors that no train example changes, each adding a recolored copy of
+    /// every train example to the examples the fitness is computed on
+    #[serde(default)]
+    pub train_color_permutations: usize,
+    /// Short training runs that choose the orientation each task is trained in. Tasks are trained in their given
+    /// orientation when not set
+    #[serde(default)]
+    pub orientation_probe: Option<OrientationProbe>,
+    /// Transforms applied to the input canvas and target output of every example before the orientation, and
+    /// reverted on the predictions. `CropToBoundingBox`, which is made for a single grid, and `Trim` are rejected by
+    /// `validate`. The GPU backend runs grids of at most 1024 cells, which upscaling and tiling can exceed
+    #[serde(default)]
+    pub transforms: Vec<Transform>,
+}
+
+/// Trains a probe population on each of the eight rotations and reflections of a task, then trains the task in the
+/// orientation whose probe reached the lowest fitness. The orientation is stored in the transform pipeline of the
+/// trained NCAs, so inference applies it to the input and reverts it on the prediction
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct OrientationProbe {
+    /// Epochs of each probe
+    pub epochs: usize,
+    /// Population size of each probe
+    pub pop: usize,
+}
+
+/// Evolution strategies for the parameters of each individual, see `optimizer`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum Optimizer {
+    /// CMA-ES
+    #[default]
+    CMAES,
+    /// (1+1)-ES with the 1/5th success rule
+    OnePlusOne,
+    /// OpenAI-style natural evolution strategy with mirrored sampling, a fixed step size of `initial_sigma` and Adam
+    NES { population: usize, learning_rate: f64 },
+    /// DE/rand/1/bin differential evolution
+    DE {
+        population: usize,
+        differential_weight: f64,
+        crossover_rate: f64,
+    },
+}
+
+/// CMA-ES covariance matrix representations, see `cmaes::CovarianceModel`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum CovarianceModel {
+    /// Full covariance matrix; quadratic memory and cubic eigendecomposition cost in the number of parameters
+    #[default]
+    Full,
+    /// Diagonal covariance matrix (sep-CMA-ES)
+    Separable,
+    /// Rank-one modifications of the identity (LM-MA-ES) with the recommended rank
+    LowRank,
+}
+
+impl CovarianceModel {
+    /// The `cmaes` model for a search over `dimensions` parameters
+    pub fn to_cmaes(self, dimensions: usize) -> cmaes::CovarianceModel {
+        match self {
+            CovarianceModel::Full => cmaes::CovarianceModel::Full,
+            CovarianceModel::Separable => cmaes::CovarianceModel::Separable,
+            CovarianceModel::LowRank => cmaes::CovarianceModel::LowRank {
+                rank: cmaes::CovarianceModel::default_rank(dimensions),
+            },
+        }
+    }
+}
+
+/// CMA-ES boundary handling modes, see `cmaes::BoundaryHandling`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum BoundaryHandling {
+    /// Mirrors out of bounds parameters back into the bounds
+    #[default]
+    Reflect,
+    /// Clamps out of bounds parameters to the bounds
+    Project,
+    /// Clamps out of bounds parameters and adds an adaptive quadratic penalty on the distance to the loss
+    Penalty,
+    /// Resamples out of bounds parameters a limited number of times
+    Resample,
+}
+
+impl BoundaryHandling {
+    pub fn to_cmaes(self) -> cmaes::BoundaryHandling {
+        match self {
+            BoundaryHandling::Reflect => cmaes::BoundaryHandling::Reflect,
+            BoundaryHandling::Project => cmaes::BoundaryHandling::Project,
+            BoundaryHandling::Penalty => cmaes::BoundaryHandling::Penalty,
+            BoundaryHandling::Resample => cmaes::BoundaryHandling::Resample,
+        }
+    }
+}
+
+/// Objectives of multi-objective training, see `cmaes::multi_objective`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Objective {
+    /// Mean squared error of the visible channels on the train examples
+    Error,
+    /// Mean squared weight
+    L2,
+    /// Mean absolute weight, which favors sparse weights
+    L1,
+}
+
+/// CMA-ES restart strategies, see `cmaes::restart`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Restarts {
+    /// Doubles the population size with each restart
+    IPOP,
+    /// Alternates between growing population sizes and small populations with smaller step sizes
+    BIPOP,
+}
+
+impl Config {
+    /// Checks the fields that would otherwise only fail inside an optimizer partway through training, and names the
+    /// first invalid one.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.optimizer {
+            Optimizer::NES { population, .. } if population < 2 || !population.is_multiple_of(2) => {
+                return Err(format!(
+                    "optimizer: NES population must be even and at least 2, got {population}"
+                ));
+            }
+            Optimizer::DE { population, .. } if population < 4 => {
+                return Err(format!("optimizer: DE population must be at least 4, got {population}"));
+            }
+            _ => {}
+        }
+
+        if let Some((lowest, highest)) = self.step_range
+            && lowest > highest
+        {
+            return Err(format!("step_range: lowest {lowest} is greater than highest {highest}"));
+        }
+
+        for (i, transform) in self.transforms.iter().enumerate() {
+            transform
+                .validate_for_config()
+                .map_err(|e| format!("transforms[{i}]: {e}"))?;
+        }
+
+        if let Some(bound) = self.param_bound
+            && (bound.is_nan() || bound < 0.0)
+        {
+            return Err(format!("param_bound: must be at least 0, got {bound}"));
+        }
+
+        Ok(())
+    }
+
+    /// The range of step counts searched together with the parameters, see `step_range`
+    pub fn evolved_step_range(&self) -> Option<(usize, usize)> {
+        let evolved = self.optimizer == Optimizer::CMAES && self.objectives.is_empty();
+        self.step_range.filter(|_| evolved)
+    }
 }
 
 impl Default for Config {
@@ -37,7 +248,116 @@ impl Default for Config {
             max_fun_evals: 5000,
             initial_sigma: 0.2,
             l2_coeff: 1e-4,
-            backend: Backend::GPU,
+            backend: Backend::default(),
+            neighborhood: Neighborhood::default(),
+            substrate_spec: SubstrateSpec::default(),
+            task_time_budget_secs: None,
+            total_time_budget_secs: None,
+            restarts: None,
+            covariance_model: CovarianceModel::default(),
+            warm_start: false,
+            param_bound: None,
+            boundary_handling: BoundaryHandling::default(),
+            objectives: Vec::new(),
+            optimizer: Optimizer::default(),
+            step_range: None,
+            dihedral_ensemble: false,
+            train_dihedral: false,
+            train_color_permutations: 0,
+            orientation_probe: None,
+            transforms: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transforms::{Identity, Pad};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        let invalid = [
+            Config {
+                optimizer: Optimizer::NES {
+                    population: 3,
+                    learning_rate: 0.01,
+                },
+                ..Config::default()
+            },
+            Config {
+                optimizer: Optimizer::DE {
+                    population: 3,
+                    differential_weight: 0.5,
+                    crossover_rate: 0.9,
+                },
+                ..Config::default()
+            },
+            Config {
+                step_range: Some((10, 5)),
+                ..Config::default()
+            },
+            Config {
+                param_bound: Some(-1.0),
+                ..Config::default()
+            },
+        ];
+        for (config, field) in invalid
+            .iter()
+            .zip(["optimizer", "optimizer", "step_range", "param_bound"])
+        {
+            let err = config.validate().unwrap_err();
+            assert!(err.starts_with(field), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_validate_transforms() {
+        let with_transform = |json: &str| Config {
+            transforms: vec![
+                Transform::Pad(Pad {
+                    top: 1,
+                    bottom: 1,
+                    left: 1,
+                    right: 1,
+                    color: 0,
+                }),
+                serde_json::from_str(json).unwrap(),
+            ],
+            ..Config::default()
+        };
+
+        let valid = [
+            r#"{"Tile": {"rows": 2, "cols": 1}}"#,
+            r#"{"UpscaleNearest": {"height_factor": 1, "width_factor": 3}}"#,
+            r#"{"Downscale": {"height_factor": 2, "width_factor": 2}}"#,
+        ];
+        for json in valid {
+            assert_eq!(with_transform(json).validate(), Ok(()), "{json}");
         }
+
+        let invalid = [
+            r#"{"Tile": {"rows": 0, "cols": 2}}"#,
+            r#"{"UpscaleNearest": {"height_factor": 2, "width_factor": 0}}"#,
+            r#"{"Downscale": {"height_factor": 0, "width_factor": 2}}"#,
+            r#"{"Pad": {"top": 1, "bottom": 0, "left": 0, "right": 0, "color": 10}}"#,
+            r#"{"Trim": {"top": 1, "bottom": 0, "left": 0, "right": 0, "color": 0}}"#,
+            r#"{"CropToBoundingBox": {"background": 0, "y": 0, "x": 0, "height": 1, "width": 1, "full_height": 2,
+                "full_width": 2}}"#,
+        ];
+        for json in invalid {
+            let err = with_transform(json).validate().unwrap_err();
+            assert!(err.starts_with("transforms[1]"), "{err}");
+        }
+
+        // The config is saved with each run
+        let custom = Config {
+            transforms: vec![Transform::Custom(Arc::new(Identity {}))],
+            ..Config::default()
+        };
+        assert!(custom.validate().unwrap_err().starts_with("transforms[0]"));
     }
 }
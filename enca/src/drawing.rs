@@ -1,11 +1,4 @@
-use crate::{
-    constants::{HID_CHS, INP_DIM, OUT_CHS, VIS_CHS},
-    dataset::Dataset,
-    grid::Grid,
-    metrics::TaskReport,
-    nca::NCA,
-    substrate::Substrate,
-};
+use crate::{dataset::Dataset, grid::Grid, metrics::TaskReport, nca::NCA, substrate::Substrate};
 use indexmap::IndexMap;
 use itertools::Itertools;
 use macroquad::prelude::*;
@@ -66,8 +59,8 @@ impl Substrate {
 
     pub fn display_channels_panel(&self, start_x: f32, start_y: f32, w: f32, h: f32) {
         // Show only non-frozen channels: RW visible + hidden
-        let tot_rw_chs = VIS_CHS + HID_CHS;
-        let start = VIS_CHS;
+        let tot_rw_chs = self.spec.out_chs();
+        let start = self.spec.vis_chs();
 
         if tot_rw_chs == 0 || w <= 0.0 || h <= 0.0 {
             return;
@@ -102,7 +95,9 @@ impl Substrate {
 pub fn draw_params(x: f32, y: f32, w: f32, h: f32, nca: &NCA) {
     draw_rectangle_lines(x, y, w, h, 1.0, WHITE.with_alpha(0.5));
 
-    let shape = (OUT_CHS, INP_DIM);
+    let inp_dim = nca.inp_dim();
+    let out_chs = nca.substrate_spec.out_chs();
+    let shape = (out_chs, inp_dim);
     draw_text(
         &format!(
             "weights={}, biases={}, shape={:?}",
@@ -120,14 +115,14 @@ pub fn draw_params(x: f32, y: f32, w: f32, h: f32, nca: &NCA) {
     let b_max = nca.biases.iter().copied().fold(f32::NEG_INFINITY, f32::max);
     let param_max = w_max.max(b_max).max(1e-5);
 
-    let n_rows = OUT_CHS;
-    let n_cols = INP_DIM;
+    let n_rows = out_chs;
+    let n_cols = inp_dim;
     let p_h = if n_rows > 0 { h / n_rows as f32 } else { h };
     let p_w = if n_cols > 0 { w / n_cols as f32 } else { w };
 
     for yi in 0..n_rows {
         for xi in 0..n_cols {
-            let idx = yi * INP_DIM + xi;
+            let idx = yi * inp_dim + xi;
             if idx >= nca.weights.len() {
                 continue;
             }
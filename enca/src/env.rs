@@ -1,12 +1,11 @@
+#[cfg(not(feature = "gpu"))]
+use crate::executors::GPU_UNAVAILABLE;
+#[cfg(feature = "gpu")]
+use crate::executors::gpu::PopNCAExecutorGpuBatch;
 use crate::{
-    config::Config,
-    constants::{RO_CH_RNG, RW_CH_RNG},
+    config::{Config, Objective},
     dataset::TrainExample,
-    executors::{
-        Backend, NCAExecutor,
-        cpu::NCAExecutorCpu,
-        gpu::{Individual, PopNCAExecutorGpuBatch},
-    },
+    executors::{Backend, NCAExecutor, cpu::PopNCAExecutorCpuBatch},
     grid::Grid,
     nca::NCA,
     substrate::Substrate,
@@ -15,67 +14,97 @@ use crate::{
 use itertools::Itertools;
 use ndarray::s;
 
+/// The terms the fitness of an NCA is made of, which are optimized separately in multi-objective training
+pub struct FitnessTerms {
+    /// Mean squared error of the visible channels, summed over the examples
+    error_sum: f64,
+    n_examples: usize,
+    /// Mean squared weight
+    l2: f64,
+    /// Mean absolute weight
+    l1: f64,
+}
+
+impl FitnessTerms {
+    /// The error plus `l2_coeff` times the mean squared weight, averaged over the examples
+    pub fn fitness(&self, l2_coeff: f64) -> f64 {
+        (self.error_sum + l2_coeff * self.l2) / self.n_examples as f64
+    }
+
+    pub fn objective(&self, objective: Objective) -> f64 {
+        match objective {
+            Objective::Error => self.error_sum / self.n_examples as f64,
+            Objective::L2 => self.l2,
+            Objective::L1 => self.l1,
+        }
+    }
+}
+
 pub fn compute_fitness_pop(examples: &[TrainExample], ncas: Vec<NCA>, config: &Config) -> Vec<f64> {
+    compute_fitness_terms_pop(examples, ncas, config)
+        .iter()
+        .map(|terms| terms.fitness(config.l2_coeff))
+        .collect()
+}
+
+pub fn compute_fitness_terms_pop(examples: &[TrainExample], ncas: Vec<NCA>, config: &Config) -> Vec<FitnessTerms> {
     let pop_size = ncas.len();
     let grids = examples.iter().map(|example| &example.input).collect_vec();
 
     let population = match config.backend {
         Backend::CPU => {
-            let mut population = Vec::with_capacity(pop_size);
-            for nca in ncas {
-                let substrates = examples
-                    .iter()
-                    .map(|example| {
-                        let mut executor = NCAExecutorCpu::new(nca.clone(), &example.input);
-                        executor.run();
-                        executor.substrate
-                    })
-                    .collect_vec();
-                population.push(Individual { nca, substrates });
-            }
-            population
+            let mut executor = PopNCAExecutorCpuBatch::new(ncas, &grids);
+            executor.run();
+            executor.individuals
         }
+        #[cfg(feature = "gpu")]
         Backend::GPU => {
             let mut executor = PopNCAExecutorGpuBatch::new(ncas, &grids);
             executor.run();
             executor.individuals
         }
+        #[cfg(not(feature = "gpu"))]
+        Backend::GPU => panic!("{GPU_UNAVAILABLE}"),
     };
 
-    let mut fitnesses = Vec::with_capacity(pop_size);
+    let mut terms = Vec::with_capacity(pop_size);
 
     for individual in population {
-        let mut fitness = 0.0f64;
+        let mut error_sum = 0.0f64;
         let pred_substrates = individual.substrates;
         let nca = individual.nca;
 
         for (example, pred_substrate) in examples.iter().zip(pred_substrates) {
             // The RW channels contain the output of the executor
-            let pred_vis_slice = s![.., .., RW_CH_RNG];
+            let pred_vis_slice = s![.., .., nca.substrate_spec.rw_ch_rng()];
             // The RO channels of the tgt substrate contain the ground truth.
-            let tgt_vis_slice = s![.., .., RO_CH_RNG];
+            let tgt_vis_slice = s![.., .., nca.substrate_spec.ro_ch_rng()];
 
             let pred_vis_chs = pred_substrate.data.slice(pred_vis_slice);
 
             let mut tgt_grid = example.output.clone();
             nca.transform_pipeline.apply(&mut tgt_grid);
-            let tgt_substrate = Substrate::from_grid(&tgt_grid);
+            let tgt_substrate = Substrate::from_grid(&tgt_grid, nca.substrate_spec);
             let out_vis_chs = tgt_substrate.data.slice(tgt_vis_slice);
 
             let diff = &pred_vis_chs - &out_vis_chs;
             let err = diff.mapv(f64::from).pow2().mean().unwrap();
 
-            fitness += err
+            error_sum += err
         }
 
-        let l2_weight_cost = mean(&nca.weights.iter().map(|w| (*w as f64) * (*w as f64)).collect_vec());
+        let l2 = mean(&nca.weights.iter().map(|w| (*w as f64) * (*w as f64)).collect_vec());
+        let l1 = mean(&nca.weights.iter().map(|w| (*w as f64).abs()).collect_vec());
 
-        fitness += config.l2_coeff * l2_weight_cost;
-
-        fitnesses.push(fitness / examples.len() as f64)
+        terms.push(FitnessTerms {
+            error_sum,
+            n_examples: examples.len(),
+            l2,
+            l1,
+        })
     }
 
-    fitnesses
+    terms
 }
 
 #[inline]
@@ -96,6 +125,7 @@ pub fn eval(input: &Grid, output: &Grid, nca: &NCA, backend: Backend) -> f32 {
     compute_accuracy(&pred_grid, output)
 }
 
+/// Fraction of cells where the prediction matches the target. A prediction of another size scores 0.0
 fn compute_accuracy(pred_grid: &Grid, target_grid: &Grid) -> f32 {
     if pred_grid.shape() != target_grid.shape() {
         return 0.0;
@@ -120,3 +150,25 @@ fn compute_accuracy(pred_grid: &Grid, target_grid: &Grid) -> f32 {
 
     correct as f32 / total as f32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_accuracy() {
+        let target = Grid::from_vec(vec![vec![1, 2], vec![3, 4]]);
+
+        assert_eq!(compute_accuracy(&target, &target), 1.0);
+        assert_eq!(
+            compute_accuracy(&Grid::from_vec(vec![vec![1, 2], vec![3, 0]]), &target),
+            0.75
+        );
+        // The overlapping cells match, but the size is wrong
+        assert_eq!(compute_accuracy(&Grid::from_vec(vec![vec![1, 2]]), &target), 0.0);
+        assert_eq!(
+            compute_accuracy(&Grid::from_vec(vec![vec![1, 2, 0], vec![3, 4, 0]]), &target),
+            0.0
+        );
+    }
+}
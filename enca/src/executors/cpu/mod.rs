@@ -0,0 +1,299 @@
+use crate::{executors::Individual, grid::Grid, nca::NCA, substrate::Substrate};
+use itertools::Itertools;
+use ndarray::Array3;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use simd::{SimdIsa, SimdSubstrate};
+
+pub mod simd;
+
+/// Handles NCA step updates and stores execution state
+#[derive(Clone)]
+pub struct NCAExecutorCpu {
+    pub nca: NCA,
+    pub steps: usize,
+    /// Substrate after the latest step. The SIMD path reloads its planes from it before stepping and writes them back
+    /// after, so changes made to the writable channels between steps are kept. The RO channels hold the input and
+    /// must not be changed.
+    pub substrate: Substrate,
+    state: CpuState,
+}
+
+#[derive(Clone)]
+enum CpuState {
+    /// Prepared kernel and back buffer for the scalar path
+    Scalar { kernel: ScalarKernel, next: Array3<f32> },
+    /// Prepared kernel and planes for the SIMD path
+    Simd(Box<SimdSubstrate>),
+}
+
+impl NCAExecutorCpu {
+    /// Executor using the widest instruction set the CPU supports.
+    pub fn new(nca: NCA, grid: &Grid) -> Self {
+        Self::with_isa(nca, grid, SimdIsa::detect())
+    }
+
+    /// Executor using `isa` for updates; `None` selects the scalar path. `isa` must be supported by the CPU.
+    pub fn with_isa(nca: NCA, grid: &Grid, isa: Option<SimdIsa>) -> Self {
+        let substrate = Substrate::from_grid(&nca.input_grid(grid), nca.substrate_spec);
+        let state = match isa {
+            Some(isa) => CpuState::Simd(Box::new(SimdSubstrate::new(isa, &nca, &substrate))),
+            None => CpuState::Scalar {
+                kernel: ScalarKernel::new(&nca),
+                next: substrate.data.clone(),
+            },
+        };
+
+        Self {
+            nca,
+            steps: 0,
+            substrate,
+            state,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let remaining = self.nca.max_steps.saturating_sub(self.steps);
+        match &mut self.state {
+            // Step the planes for the whole run instead of converting them every step
+            CpuState::Simd(planes) => {
+                planes.load(&self.substrate);
+                planes.run(remaining);
+                planes.store(&mut self.substrate);
+                self.steps += remaining;
+            }
+            CpuState::Scalar { .. } => while !self.step() {},
+        }
+    }
+
+    /// Executes one iteration step.
+    pub fn step(&mut self) -> bool {
+        if self.steps >= self.nca.max_steps {
+            return true;
+        }
+        self.update();
+        self.steps += 1;
+        false
+    }
+
+    /// Updates the substrate using the NCA
+    pub fn update(&mut self) {
+        let substrate = &mut self.substrate;
+
+        match &mut self.state {
+            CpuState::Simd(planes) => {
+                planes.load(substrate);
+                planes.run(1);
+                planes.store(substrate);
+            }
+            CpuState::Scalar { kernel, next } => {
+                // The RO channels are never written, so the back buffer keeps them across swaps
+                kernel.update(
+                    substrate.data.as_slice().unwrap(),
+                    next.as_slice_mut().unwrap(),
+                    substrate.height,
+                    substrate.width,
+                );
+
+                std::mem::swap(&mut substrate.data, next);
+            }
+        }
+    }
+}
+
+/// Executes a population of NCAs on a batch of grids on the CPU. Mirrors `PopNCAExecutorGpuBatch`: individuals
+/// and their grids are spread over the rayon thread pool, and each substrate is stepped with a pair of preallocated
+/// buffers that are swapped every step. Uses the SIMD path when the CPU supports it.
+#[derive(Clone)]
+pub struct PopNCAExecutorCpuBatch {
+    pub individuals: Vec<Individual>,
+}
+
+impl PopNCAExecutorCpuBatch {
+    pub fn new(ncas: Vec<NCA>, grids: &[&Grid]) -> Self {
+        let individuals = ncas
+            .into_iter()
+            .map(|nca| {
+                let substrates = grids
+                    .iter()
+                    .map(|grid| Substrate::from_grid(&nca.input_grid(grid), nca.substrate_spec))
+                    .collect_vec();
+                Individual { nca, substrates }
+            })
+            .collect();
+
+        Self { individuals }
+    }
+
+    pub fn run(&mut self) {
+        let isa = SimdIsa::detect();
+        self.individuals.par_iter_mut().for_each(|individual| {
+            let nca = &individual.nca;
+            match isa {
+                Some(isa) => individual
+                    .substrates
+                    .par_iter_mut()
+                    .for_each(|substrate| simd::run(isa, nca, substrate, nca.max_steps)),
+                None => {
+                    let kernel = ScalarKernel::new(nca);
+                    individual
+                        .substrates
+                        .par_iter_mut()
+                        .for_each(|substrate| run_double_buffered(&kernel, substrate, nca.max_steps));
+                }
+            }
+        });
+    }
+}
+
+/// Runs `n_steps` updates of `kernel` on `substrate`, alternating between the substrate data and a single back
+/// buffer.
+fn run_double_buffered(kernel: &ScalarKernel, substrate: &mut Substrate, n_steps: usize) {
+    let (height, width) = (substrate.height, substrate.width);
+    // The RO channels are never written, so both buffers must start with them
+    let mut back = substrate.data.clone();
+
+    let front = substrate.data.as_slice_mut().unwrap();
+    let back = back.as_slice_mut().unwrap();
+
+    let (mut src, mut dst) = (front, back);
+    for _ in 0..n_steps {
+        kernel.update(src, dst, height, width);
+        std::mem::swap(&mut src, &mut dst);
+    }
+
+    // After an odd number of steps the latest state is in the back buffer
+    if n_steps % 2 == 1 {
+        dst.copy_from_slice(src);
+    }
+}
+
+/// Weights and neighbor offsets of an NCA in the layout of the scalar update. Built once per NCA and shared by every
+/// step of every substrate it runs on.
+#[derive(Clone)]
+pub struct ScalarKernel {
+    /// Weights transposed to `[inp_dim][out_pad]`
+    weights_t: Vec<f32>,
+    /// Biases padded to `out_pad`
+    biases: Vec<f32>,
+    /// `(dx, dy)` offset of each neighbor
+    offsets: Vec<(i32, i32)>,
+    inp_chs: usize,
+    vis_chs: usize,
+    out_chs: usize,
+    /// Number of output channels accumulated in registers at once
+    out_block: usize,
+    /// Output channels rounded up to a multiple of `out_block`
+    out_pad: usize,
+}
+
+impl ScalarKernel {
+    pub fn new(nca: &NCA) -> Self {
+        nca.check_shape();
+        let spec = nca.substrate_spec;
+        let out_block = out_block(spec.out_chs());
+        let out_pad = spec.out_chs().div_ceil(out_block) * out_block;
+        let (weights_t, biases) = transpose_params(nca, out_pad);
+
+        Self {
+            weights_t,
+            biases,
+            offsets: nca.neighborhood.offsets(),
+            inp_chs: spec.inp_chs(),
+            vis_chs: spec.vis_chs(),
+            out_chs: spec.out_chs(),
+            out_block,
+            out_pad,
+        }
+    }
+
+    /// Computes one NCA step from `src` and writes the clamped result to the writable channels of `dst`. Both
+    /// buffers are `(height, width, inp_chs)` in row-major order. The RO channels of `dst` are left untouched.
+    ///
+    /// The accumulation order matches `nca_update` in `kernel.cu` so results are identical across backends.
+    #[inline]
+    pub fn update(&self, src: &[f32], dst: &mut [f32], height: usize, width: usize) {
+        // Monomorphize the accumulator block so it stays in registers
+        match self.out_block {
+            4 => self.update_blocked::<4>(src, dst, height, width),
+            6 => self.update_blocked::<6>(src, dst, height, width),
+            8 => self.update_blocked::<8>(src, dst, height, width),
+            block => unreachable!("Unsupported output block size {block}"),
+        }
+    }
+
+    fn update_blocked<const N: usize>(&self, src: &[f32], dst: &mut [f32], height: usize, width: usize) {
+        let w = width as i32;
+        let h = height as i32;
+        let nhbd = &self.offsets;
+        let (inp_chs, vis_chs, out_chs, out_pad) = (self.inp_chs, self.vis_chs, self.out_chs, self.out_pad);
+        assert!(src.len() == height * width * inp_chs && dst.len() == src.len());
+
+        for y in 0..height {
+            for x in 0..width {
+                for block in (0..out_pad).step_by(N) {
+                    let mut out_buf: [f32; N] = self.biases[block..block + N].try_into().unwrap();
+
+                    for (ni, (dx, dy)) in nhbd.iter().enumerate() {
+                        let nx = x as i32 + dx;
+                        let ny = y as i32 + dy;
+                        if nx < 0 || nx >= w || ny < 0 || ny >= h {
+                            // Out of bounds
+                            continue;
+                        };
+
+                        let nbase = (ny as usize * width + nx as usize) * inp_chs;
+
+                        for inp_ch_idx in 0..inp_chs {
+                            let neighbor_val = unsafe { *src.get_unchecked(nbase + inp_ch_idx) };
+
+                            // Alive masking
+                            if neighbor_val < 0.5 {
+                                continue;
+                            }
+
+                            let col_idx = inp_ch_idx * nhbd.len() + ni;
+                            let w_col = unsafe { self.weights_t.get_unchecked(col_idx * out_pad + block..) };
+
+                            for (out, w) in out_buf.iter_mut().zip(&w_col[..N]) {
+                                *out = f32::mul_add(neighbor_val, *w, *out);
+                            }
+                        }
+                    }
+
+                    // Update only writable channels.
+                    let base = (y * width + x) * inp_chs + vis_chs + block;
+                    let n_valid = N.min(out_chs - block);
+                    for (ch, out) in out_buf[..n_valid].iter().enumerate() {
+                        *unsafe { dst.get_unchecked_mut(base + ch) } = out.clamp(0.0, 1.0)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Number of output channels accumulated in registers at once. Prefers the largest block that wastes the fewest
+/// accumulators.
+fn out_block(out_chs: usize) -> usize {
+    [8, 6, 4]
+        .into_iter()
+        .min_by_key(|block| out_chs.div_ceil(*block) * block)
+        .unwrap()
+}
+
+/// Returns the weights transposed to `[inp_dim][out_pad]` as in `kernel.cu`, and the biases, both zero-padded from
+/// `out_chs` to `out_pad` outputs.
+fn transpose_params(nca: &NCA, out_pad: usize) -> (Vec<f32>, Vec<f32>) {
+    let inp_dim = nca.inp_dim();
+    let mut weights_t = vec![0.0; inp_dim * out_pad];
+    for out in 0..nca.substrate_spec.out_chs() {
+        for col in 0..inp_dim {
+            weights_t[col * out_pad + out] = nca.weights[out * inp_dim + col];
+        }
+    }
+
+    let mut biases = nca.biases.clone();
+    biases.resize(out_pad, 0.0);
+
+    (weights_t, biases)
+}
@@ -0,0 +1,712 @@
+//! SIMD NCA update for the CPU backend.
+//!
+//! The substrate is stored channel-major: one zero-padded `(height + 2 * pad, width + 2 * pad)` plane per input
+//! channel, so every neighbor of a run of consecutive cells in a row is itself a contiguous load. Weights are
+//! transposed to `[inp_dim][out_chs]` as in `kernel.cu`, and each vector of cells keeps a block of output
+//! accumulators in registers. Alive masking is a compare-and-select instead of a branch, and the zero border replaces
+//! bounds checks.
+//!
+//! The accumulation order matches `ScalarKernel::update` so the SIMD and scalar paths produce the same values.
+
+use super::{out_block, transpose_params};
+use crate::{nca::NCA, substrate::Substrate};
+
+/// SIMD instruction sets supported by the CPU backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimdIsa {
+    #[cfg(target_arch = "x86_64")]
+    Avx512,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl SimdIsa {
+    /// Returns the widest instruction set supported by the running CPU, or `None` if the scalar path must be used.
+    pub fn detect() -> Option<Self> {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                return Some(SimdIsa::Avx512);
+            }
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                return Some(SimdIsa::Avx2);
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return Some(SimdIsa::Neon);
+            }
+        }
+        None
+    }
+}
+
+/// Runs `n_steps` NCA updates on `substrate` using `isa`.
+pub fn run(isa: SimdIsa, nca: &NCA, substrate: &mut Substrate, n_steps: usize) {
+    if n_steps == 0 {
+        return;
+    }
+
+    let mut planes = SimdSubstrate::new(isa, nca, substrate);
+    planes.run(n_steps);
+    planes.store(substrate);
+}
+
+/// A substrate converted to the SIMD layout, so it can be stepped repeatedly and converted back only when it is read.
+#[derive(Clone)]
+pub struct SimdSubstrate {
+    isa: SimdIsa,
+    kernel: Kernel,
+    front: Vec<f32>,
+    back: Vec<f32>,
+}
+
+impl SimdSubstrate {
+    pub fn new(isa: SimdIsa, nca: &NCA, substrate: &Substrate) -> Self {
+        let kernel = Kernel::new(nca, substrate.height, substrate.width);
+        let front = kernel.load_planes(substrate.data.as_slice().unwrap());
+        // The RO channels and the zero border are never written, so both buffers must start with them
+        let back = front.clone();
+
+        Self {
+            isa,
+            kernel,
+            front,
+            back,
+        }
+    }
+
+    /// Runs `n_steps` NCA updates.
+    pub fn run(&mut self, n_steps: usize) {
+        for _ in 0..n_steps {
+            // SAFETY: `isa` was produced by `SimdIsa::detect` so the CPU supports it, and both buffers were built by
+            // `kernel.load_planes`
+            unsafe { self.kernel.step(self.isa, &self.front, &mut self.back) };
+            std::mem::swap(&mut self.front, &mut self.back);
+        }
+    }
+
+    /// Reloads the planes from `substrate`, which must have the size and spec it was converted from. The RO channels
+    /// of the back buffer keep the values they were converted from.
+    pub fn load(&mut self, substrate: &Substrate) {
+        self.kernel
+            .write_planes(substrate.data.as_slice().unwrap(), &mut self.front);
+    }
+
+    /// Writes the current state to `substrate`, which must have the size and spec it was converted from.
+    pub fn store(&self, substrate: &mut Substrate) {
+        self.kernel
+            .store_planes(&self.front, substrate.data.as_slice_mut().unwrap());
+    }
+}
+
+/// Weights and plane geometry for one substrate size.
+#[derive(Clone)]
+struct Kernel {
+    /// Weights transposed to `[inp_dim][out_pad]`
+    weights_t: Vec<f32>,
+    /// Biases padded to `out_pad`
+    biases: Vec<f32>,
+    inp_chs: usize,
+    vis_chs: usize,
+    /// Number of output channels accumulated in registers at once
+    out_block: usize,
+    /// Output channels rounded up to a multiple of `out_block`. Outputs past `out_chs` have zero weights and are
+    /// written to scratch planes after the input channels.
+    out_pad: usize,
+    /// Offset of each neighbor relative to the cell in a padded plane
+    offsets: Vec<isize>,
+    height: usize,
+    width: usize,
+    /// Width of the zero border around each plane
+    pad: usize,
+    /// Padded plane width
+    pw: usize,
+    /// Padded plane length
+    plane_len: usize,
+}
+
+impl Kernel {
+    fn new(nca: &NCA, height: usize, width: usize) -> Self {
+        nca.check_shape();
+        let spec = nca.substrate_spec;
+        let out_block = out_block(spec.out_chs());
+        let out_pad = spec.out_chs().div_ceil(out_block) * out_block;
+        let (weights_t, biases) = transpose_params(nca, out_pad);
+
+        let pad = nca.neighborhood.radius();
+        let pw = width + 2 * pad;
+        let ph = height + 2 * pad;
+        let offsets = nca
+            .neighborhood
+            .offsets()
+            .into_iter()
+            .map(|(dx, dy)| dy as isize * pw as isize + dx as isize)
+            .collect();
+
+        Self {
+            weights_t,
+            biases,
+            inp_chs: spec.inp_chs(),
+            vis_chs: spec.vis_chs(),
+            out_block,
+            out_pad,
+            offsets,
+            height,
+            width,
+            pad,
+            pw,
+            plane_len: pw * ph,
+        }
+    }
+
+    /// Number of planes: the input channels followed by scratch planes for the padded outputs
+    fn n_planes(&self) -> usize {
+        self.vis_chs + self.out_pad
+    }
+
+    /// Converts `(height, width, inp_chs)` data to zero-padded channel-major planes.
+    fn load_planes(&self, data: &[f32]) -> Vec<f32> {
+        let mut planes = vec![0.0; self.n_planes() * self.plane_len];
+        self.write_planes(data, &mut planes);
+        planes
+    }
+
+    /// Writes `(height, width, inp_chs)` data to the cells of channel-major planes, leaving the zero border and the
+    /// scratch planes as they are.
+    fn write_planes(&self, data: &[f32], planes: &mut [f32]) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.cell_idx(y, x);
+                let cell = &data[(y * self.width + x) * self.inp_chs..][..self.inp_chs];
+                for (ch, v) in cell.iter().enumerate() {
+                    planes[ch * self.plane_len + idx] = *v;
+                }
+            }
+        }
+    }
+
+    /// Writes channel-major planes back to `(height, width, inp_chs)` data.
+    fn store_planes(&self, planes: &[f32], data: &mut [f32]) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.cell_idx(y, x);
+                let cell = &mut data[(y * self.width + x) * self.inp_chs..][..self.inp_chs];
+                for (ch, v) in cell.iter_mut().enumerate() {
+                    *v = planes[ch * self.plane_len + idx];
+                }
+            }
+        }
+    }
+
+    /// Index of cell `(y, x)` within a padded plane.
+    #[inline(always)]
+    fn cell_idx(&self, y: usize, x: usize) -> usize {
+        (y + self.pad) * self.pw + x + self.pad
+    }
+
+    /// Computes one NCA step from `src` into the writable channels of `dst`.
+    ///
+    /// # Safety
+    /// The CPU must support `isa`, and both buffers must hold `n_planes() * plane_len` values.
+    unsafe fn step(&self, isa: SimdIsa, src: &[f32], dst: &mut [f32]) {
+        assert!(src.len() == self.n_planes() * self.plane_len && dst.len() == src.len());
+        unsafe {
+            match isa {
+                #[cfg(target_arch = "x86_64")]
+                SimdIsa::Avx512 => step_avx512(self, src.as_ptr(), dst.as_mut_ptr()),
+                #[cfg(target_arch = "x86_64")]
+                SimdIsa::Avx2 => step_avx2(self, src.as_ptr(), dst.as_mut_ptr()),
+                #[cfg(target_arch = "aarch64")]
+                SimdIsa::Neon => step_neon(self, src.as_ptr(), dst.as_mut_ptr()),
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f,fma")]
+unsafe fn step_avx512(kernel: &Kernel, src: *const f32, dst: *mut f32) {
+    unsafe { step_generic::<x86::Avx512>(kernel, src, dst) }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn step_avx2(kernel: &Kernel, src: *const f32, dst: *mut f32) {
+    unsafe { step_generic::<x86::Avx2>(kernel, src, dst) }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn step_neon(kernel: &Kernel, src: *const f32, dst: *mut f32) {
+    unsafe { step_generic::<neon::Neon>(kernel, src, dst) }
+}
+
+/// Steps every row with full vectors of `V`. The tail of a row is covered by one more vector overlapping the previous
+/// one, which recomputes a few cells with identical results; rows narrower than `V` use `V::Half`.
+#[inline(always)]
+unsafe fn step_generic<V: Lanes>(kernel: &Kernel, src: *const f32, dst: *mut f32) {
+    // Monomorphize the accumulator block so it stays in registers
+    match kernel.out_block {
+        4 => unsafe { step_rows::<V, 4>(kernel, src, dst) },
+        6 => unsafe { step_rows::<V, 6>(kernel, src, dst) },
+        8 => unsafe { step_rows::<V, 8>(kernel, src, dst) },
+        block => unreachable!("Unsupported output block size {block}"),
+    }
+}
+
+#[inline(always)]
+unsafe fn step_rows<V: Lanes, const N: usize>(kernel: &Kernel, src: *const f32, dst: *mut f32) {
+    for y in 0..kernel.height {
+        unsafe { step_row::<V, N>(kernel, src, dst, kernel.cell_idx(y, 0)) };
+    }
+}
+
+#[inline(always)]
+unsafe fn step_row<V: Lanes, const N: usize>(kernel: &Kernel, src: *const f32, dst: *mut f32, row: usize) {
+    let width = kernel.width;
+    if width < V::LANES {
+        return unsafe { step_row::<V::Half, N>(kernel, src, dst, row) };
+    }
+
+    let mut x = 0;
+    while x + V::LANES <= width {
+        unsafe { update_cells::<V, N>(kernel, src, dst, row + x) };
+        x += V::LANES;
+    }
+    if x < width {
+        unsafe { update_cells::<V, N>(kernel, src, dst, row + width - V::LANES) };
+    }
+}
+
+/// Updates the `V::LANES` consecutive cells starting at padded index `idx`, `N` output channels at a time.
+#[inline(always)]
+unsafe fn update_cells<V: Lanes, const N: usize>(kernel: &Kernel, src: *const f32, dst: *mut f32, idx: usize) {
+    unsafe {
+        for block in (0..kernel.out_pad).step_by(N) {
+            let mut acc: [V; N] = std::array::from_fn(|out| V::splat(kernel.biases[block + out]));
+
+            for (ni, offset) in kernel.offsets.iter().enumerate() {
+                let nidx = idx.wrapping_add_signed(*offset);
+                for inp_ch in 0..kernel.inp_chs {
+                    let v = V::load(src.add(inp_ch * kernel.plane_len + nidx)).alive();
+                    let w_row = kernel
+                        .weights_t
+                        .as_ptr()
+                        .add((inp_ch * kernel.offsets.len() + ni) * kernel.out_pad + block);
+                    for (out, a) in acc.iter_mut().enumerate() {
+                        *a = v.mul_add(V::splat(*w_row.add(out)), *a);
+                    }
+                }
+            }
+
+            // Update only writable channels.
+            for (out, a) in acc.iter().enumerate() {
+                a.clamp01()
+                    .store(dst.add((kernel.vis_chs + block + out) * kernel.plane_len + idx));
+            }
+        }
+    }
+}
+
+/// A vector of `LANES` cells of one channel.
+trait Lanes: Copy {
+    const LANES: usize;
+    /// Narrower vector for rows shorter than `LANES`
+    type Half: Lanes;
+
+    unsafe fn splat(v: f32) -> Self;
+    unsafe fn load(ptr: *const f32) -> Self;
+    unsafe fn store(self, ptr: *mut f32);
+    /// Zeroes lanes below the alive threshold.
+    unsafe fn alive(self) -> Self;
+    /// Fused `self * w + acc`.
+    unsafe fn mul_add(self, w: Self, acc: Self) -> Self;
+    unsafe fn clamp01(self) -> Self;
+}
+
+/// Single cell, used for the narrowest rows.
+impl Lanes for f32 {
+    const LANES: usize = 1;
+    type Half = f32;
+
+    #[inline(always)]
+    unsafe fn splat(v: f32) -> Self {
+        v
+    }
+
+    #[inline(always)]
+    unsafe fn load(ptr: *const f32) -> Self {
+        unsafe { *ptr }
+    }
+
+    #[inline(always)]
+    unsafe fn store(self, ptr: *mut f32) {
+        unsafe { *ptr = self }
+    }
+
+    #[inline(always)]
+    unsafe fn alive(self) -> Self {
+        if self >= 0.5 { self } else { 0.0 }
+    }
+
+    #[inline(always)]
+    unsafe fn mul_add(self, w: Self, acc: Self) -> Self {
+        f32::mul_add(self, w, acc)
+    }
+
+    #[inline(always)]
+    unsafe fn clamp01(self) -> Self {
+        self.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::Lanes;
+    use std::arch::x86_64::*;
+
+    #[derive(Clone, Copy)]
+    pub struct Sse(__m128);
+
+    impl Lanes for Sse {
+        const LANES: usize = 4;
+        type Half = f32;
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn splat(v: f32) -> Self {
+            Self(_mm_set1_ps(v))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn load(ptr: *const f32) -> Self {
+            Self(unsafe { _mm_loadu_ps(ptr) })
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn store(self, ptr: *mut f32) {
+            unsafe { _mm_storeu_ps(ptr, self.0) }
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn alive(self) -> Self {
+            let mask = _mm_cmpge_ps(self.0, _mm_set1_ps(0.5));
+            Self(_mm_and_ps(self.0, mask))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn mul_add(self, w: Self, acc: Self) -> Self {
+            Self(_mm_fmadd_ps(self.0, w.0, acc.0))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn clamp01(self) -> Self {
+            Self(_mm_max_ps(_mm_min_ps(self.0, _mm_set1_ps(1.0)), _mm_setzero_ps()))
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct Avx2(__m256);
+
+    impl Lanes for Avx2 {
+        const LANES: usize = 8;
+        type Half = Sse;
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn splat(v: f32) -> Self {
+            Self(_mm256_set1_ps(v))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn load(ptr: *const f32) -> Self {
+            Self(unsafe { _mm256_loadu_ps(ptr) })
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn store(self, ptr: *mut f32) {
+            unsafe { _mm256_storeu_ps(ptr, self.0) }
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn alive(self) -> Self {
+            let mask = _mm256_cmp_ps::<_CMP_GE_OQ>(self.0, _mm256_set1_ps(0.5));
+            Self(_mm256_and_ps(self.0, mask))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn mul_add(self, w: Self, acc: Self) -> Self {
+            Self(_mm256_fmadd_ps(self.0, w.0, acc.0))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn clamp01(self) -> Self {
+            Self(_mm256_max_ps(
+                _mm256_min_ps(self.0, _mm256_set1_ps(1.0)),
+                _mm256_setzero_ps(),
+            ))
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct Avx512(__m512);
+
+    impl Lanes for Avx512 {
+        const LANES: usize = 16;
+        type Half = Avx2;
+
+        #[inline]
+        #[target_feature(enable = "avx512f,fma")]
+        unsafe fn splat(v: f32) -> Self {
+            Self(_mm512_set1_ps(v))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx512f,fma")]
+        unsafe fn load(ptr: *const f32) -> Self {
+            Self(unsafe { _mm512_loadu_ps(ptr) })
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx512f,fma")]
+        unsafe fn store(self, ptr: *mut f32) {
+            unsafe { _mm512_storeu_ps(ptr, self.0) }
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx512f,fma")]
+        unsafe fn alive(self) -> Self {
+            let mask = _mm512_cmp_ps_mask::<_CMP_GE_OQ>(self.0, _mm512_set1_ps(0.5));
+            Self(_mm512_maskz_mov_ps(mask, self.0))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx512f,fma")]
+        unsafe fn mul_add(self, w: Self, acc: Self) -> Self {
+            Self(_mm512_fmadd_ps(self.0, w.0, acc.0))
+        }
+
+        #[inline]
+        #[target_feature(enable = "avx512f,fma")]
+        unsafe fn clamp01(self) -> Self {
+            Self(_mm512_max_ps(
+                _mm512_min_ps(self.0, _mm512_set1_ps(1.0)),
+                _mm512_setzero_ps(),
+            ))
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::Lanes;
+    use std::arch::aarch64::*;
+
+    #[derive(Clone, Copy)]
+    pub struct Neon(float32x4_t);
+
+    impl Lanes for Neon {
+        const LANES: usize = 4;
+        type Half = f32;
+
+        #[inline]
+        #[target_feature(enable = "neon")]
+        unsafe fn splat(v: f32) -> Self {
+            Self(vdupq_n_f32(v))
+        }
+
+        #[inline]
+        #[target_feature(enable = "neon")]
+        unsafe fn load(ptr: *const f32) -> Self {
+            Self(unsafe { vld1q_f32(ptr) })
+        }
+
+        #[inline]
+        #[target_feature(enable = "neon")]
+        unsafe fn store(self, ptr: *mut f32) {
+            unsafe { vst1q_f32(ptr, self.0) }
+        }
+
+        #[inline]
+        #[target_feature(enable = "neon")]
+        unsafe fn alive(self) -> Self {
+            let mask = vcgeq_f32(self.0, vdupq_n_f32(0.5));
+            Self(vreinterpretq_f32_u32(vandq_u32(vreinterpretq_u32_f32(self.0), mask)))
+        }
+
+        #[inline]
+        #[target_feature(enable = "neon")]
+        unsafe fn mul_add(self, w: Self, acc: Self) -> Self {
+            Self(vfmaq_f32(acc.0, self.0, w.0))
+        }
+
+        #[inline]
+        #[target_feature(enable = "neon")]
+        unsafe fn clamp01(self) -> Self {
+            Self(vmaxq_f32(vminq_f32(self.0, vdupq_n_f32(1.0)), vdupq_n_f32(0.0)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::ColorEncoding,
+        executors::cpu::{NCAExecutorCpu, ScalarKernel, run_double_buffered},
+        grid::Grid,
+        neighborhood::Neighborhood,
+        substrate::SubstrateSpec,
+    };
+    use ndarray::{Array3, s};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    /// Every instruction set the running CPU supports, not only the widest one `SimdIsa::detect` picks
+    fn supported_isas() -> Vec<SimdIsa> {
+        let mut isas = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("fma") {
+                isas.push(SimdIsa::Avx512);
+            }
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                isas.push(SimdIsa::Avx2);
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                isas.push(SimdIsa::Neon);
+            }
+        }
+        isas
+    }
+
+    fn random_substrate(rng: &mut impl Rng, height: usize, width: usize, spec: SubstrateSpec) -> Substrate {
+        // Values on both sides of the alive threshold, including exact zeros
+        let data = Array3::from_shape_fn((height, width, spec.inp_chs()), |_| match rng.random_range(0..4) {
+            0 => 0.0,
+            _ => rng.random::<f32>(),
+        });
+        Substrate {
+            data,
+            width,
+            height,
+            spec,
+        }
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let isas = supported_isas();
+
+        let neighborhoods = [
+            Neighborhood::VonNeumann { radius: 1 },
+            Neighborhood::VonNeumann { radius: 2 },
+            Neighborhood::Moore { radius: 1 },
+            Neighborhood::Moore { radius: 2 },
+        ];
+        // 6, 8, 5 and 10 output channels; the last two are not a multiple of their output block of 6
+        let specs = [
+            SubstrateSpec::default(),
+            SubstrateSpec {
+                hid_chs: 4,
+                encoding: ColorEncoding::Binary,
+            },
+            SubstrateSpec {
+                hid_chs: 1,
+                encoding: ColorEncoding::Binary,
+            },
+            SubstrateSpec {
+                hid_chs: 0,
+                encoding: ColorEncoding::OneHot,
+            },
+        ];
+        // Below and above the widths of the SSE/NEON, AVX2 and AVX-512 vectors
+        let sizes = [(1, 1), (3, 2), (2, 5), (4, 8), (5, 13), (3, 16), (6, 21), (30, 30)];
+
+        for isa in isas {
+            for neighborhood in neighborhoods {
+                for spec in specs {
+                    for (height, width) in sizes {
+                        let mut nca = NCA::new(3, neighborhood, spec);
+                        nca.initialize_random(&mut rng);
+                        let substrate = random_substrate(&mut rng, height, width, spec);
+
+                        let mut scalar = substrate.clone();
+                        run_double_buffered(&ScalarKernel::new(&nca), &mut scalar, nca.max_steps);
+                        let mut simd = substrate;
+                        run(isa, &nca, &mut simd, nca.max_steps);
+
+                        let scalar_bits = scalar.data.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+                        let simd_bits = simd.data.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+                        assert_eq!(
+                            scalar_bits, simd_bits,
+                            "{isa:?} differs from the scalar path for {neighborhood:?}, {spec:?} and a {height}x{width} \
+                             substrate"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executors can be shared by reference between threads
+    const _: () = {
+        const fn assert_sync<T: Sync>() {}
+        assert_sync::<NCAExecutorCpu>();
+    };
+
+    #[test]
+    fn test_executor_steps_match_scalar() {
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        let grid = Grid::from_vec(
+            (0..7)
+                .map(|_| (0..19).map(|_| rng.random_range(0..10)).collect())
+                .collect(),
+        );
+        let mut nca = NCA::new(5, Neighborhood::Moore { radius: 1 }, SubstrateSpec::default());
+        nca.initialize_random(&mut rng);
+
+        for isa in supported_isas() {
+            let mut scalar = NCAExecutorCpu::with_isa(nca.clone(), &grid, None);
+            let mut simd = NCAExecutorCpu::with_isa(nca.clone(), &grid, Some(isa));
+            while !scalar.step() {
+                simd.step();
+                assert_eq!(
+                    scalar.substrate.data, simd.substrate.data,
+                    "{isa:?} differs after a step"
+                );
+            }
+            assert!(simd.step());
+
+            // Changes made to the writable channels between steps are kept
+            for executor in [&mut scalar, &mut simd] {
+                let rw = executor.nca.substrate_spec.vis_chs()..;
+                executor.substrate.data.slice_mut(s![.., .., rw]).fill(1.0);
+                executor.steps = 0;
+                executor.step();
+            }
+            assert_eq!(
+                scalar.substrate.data, simd.substrate.data,
+                "{isa:?} differs after changing the substrate"
+            );
+        }
+    }
+}
@@ -1,51 +1,40 @@
-static constexpr int NHBD_LEN = 5;
-static constexpr int VIS_CHS = 4;
-static constexpr int HID_CHS = 2;
-static constexpr int INP_CHS = VIS_CHS * 2 + HID_CHS;
-static constexpr int OUT_CHS = VIS_CHS + HID_CHS;
-static constexpr int INP_DIM = NHBD_LEN * INP_CHS;
-static constexpr int N_WEIGHTS = OUT_CHS * INP_DIM;
-static constexpr int N_BIASES = OUT_CHS;
-static constexpr int N_PARAMS = N_WEIGHTS + N_BIASES;
-__device__ __constant__ static constexpr int NHBD[NHBD_LEN][2] = {
-             { 0,-1},
-    {-1, 0}, { 0, 0}, {1, 0},
-             { 0, 1}
-};
+// Must match MAX_OUT_CHS in gpu/mod.rs
+static constexpr int MAX_OUT_CHS = 32;
 
+// weights_t is [nhbd_len * inp_chs][out_chs] and nhbd is [nhbd_len][2]
 extern "C" __device__ void nca_update(float *__restrict__ sub, const int height, const int width,
-                                      const float weights_t[INP_DIM][OUT_CHS], const float *__restrict__ biases) {
+                                      const float *__restrict__ weights_t, const float *__restrict__ biases,
+                                      const int *__restrict__ nhbd, const int nhbd_len, const int vis_chs,
+                                      const int inp_chs, const int out_chs) {
     const int x = threadIdx.x % width;
     const int y = threadIdx.x / width;
-    int base = threadIdx.x * INP_CHS;
+    int base = threadIdx.x * inp_chs;
 
-    float outBuf[OUT_CHS];
+    float outBuf[MAX_OUT_CHS];
 
-    for (int i = 0; i < OUT_CHS; i++) {
+    for (int i = 0; i < out_chs; i++) {
         outBuf[i] = biases[i];
     }
 
-    #pragma unroll
-    for (int ni = 0; ni < NHBD_LEN; ni++) {
-        const int nx = x + NHBD[ni][0];
-        const int ny = y + NHBD[ni][1];
+    for (int ni = 0; ni < nhbd_len; ni++) {
+        const int nx = x + nhbd[2 * ni];
+        const int ny = y + nhbd[2 * ni + 1];
 
         if ((unsigned)nx >= (unsigned)width || (unsigned)ny >= (unsigned)height) {
             continue;
         }
 
-        const int nbase = (ny * width + nx) * INP_CHS;
+        const int nbase = (ny * width + nx) * inp_chs;
 
-        #pragma unroll
-        for (int inCh = 0; inCh < INP_CHS; inCh++) {
+        for (int inCh = 0; inCh < inp_chs; inCh++) {
 
             const float neighVal = sub[nbase + inCh];
             // Alive masking
             const float mask = (neighVal >= 0.5f) ? 1.0f : 0.0f;
-            const int colIdx = inCh * NHBD_LEN + ni;
+            const int colIdx = inCh * nhbd_len + ni;
 
-            for (int outCh = 0; outCh < OUT_CHS; outCh++) {
-                outBuf[outCh] += (neighVal * mask) * weights_t[colIdx][outCh];
+            for (int outCh = 0; outCh < out_chs; outCh++) {
+                outBuf[outCh] += (neighVal * mask) * weights_t[colIdx * out_chs + outCh];
             }
         }
     }
@@ -53,8 +42,8 @@ extern "C" __device__ void nca_update(float *__restrict__ sub, const int height,
     __syncthreads();
 
     // Update only writable channels
-    for (int ch = 0; ch < OUT_CHS; ch++) {
-        sub[base + ch + VIS_CHS] = __saturatef(outBuf[ch]);
+    for (int ch = 0; ch < out_chs; ch++) {
+        sub[base + ch + vis_chs] = __saturatef(outBuf[ch]);
     }
 }
 
@@ -62,7 +51,9 @@ extern "C" __device__ void nca_update(float *__restrict__ sub, const int height,
 extern "C" __global__ void pop_nca_executor_run_batch(float *__restrict__ pop_subs,
                                                       const float *__restrict__ pop_params,
                                                       const int *__restrict__ heights, const int *__restrict__ widths,
-                                                      const int max_steps, const int max_grid_size) {
+                                                      const int *__restrict__ max_steps, const int max_grid_size,
+                                                      const int *__restrict__ nhbd, const int nhbd_len,
+                                                      const int vis_chs, const int hid_chs) {
     int height = heights[blockIdx.x];
     int width = widths[blockIdx.x];
     int size = height * width;
@@ -71,34 +62,48 @@ extern "C" __global__ void pop_nca_executor_run_batch(float *__restrict__ pop_su
         return;
     }
 
-    extern __shared__ float s_sub[];
-    __shared__ float s_weights_t[INP_DIM][OUT_CHS];
-    __shared__ float s_biases[N_BIASES];
+    const int inp_chs = 2 * vis_chs + hid_chs;
+    const int out_chs = vis_chs + hid_chs;
+    const int inp_dim = nhbd_len * inp_chs;
+    const int n_weights = out_chs * inp_dim;
+    const int n_params = n_weights + out_chs;
 
-    int grid_elem_base = (blockIdx.y * gridDim.x + blockIdx.x) * max_grid_size * INP_CHS;
+    // Dynamic shared memory layout: substrate, transposed weights, biases, neighborhood
+    extern __shared__ float s_mem[];
+    float *s_sub = s_mem;
+    float *s_weights_t = s_sub + max_grid_size * inp_chs;
+    float *s_biases = s_weights_t + n_weights;
+    int *s_nhbd = (int *)(s_biases + out_chs);
 
-    for (int i = 0; i < INP_CHS * size; i += size) {
+    int grid_elem_base = (blockIdx.y * gridDim.x + blockIdx.x) * max_grid_size * inp_chs;
+
+    for (int i = 0; i < inp_chs * size; i += size) {
         s_sub[threadIdx.x + i] = pop_subs[grid_elem_base + threadIdx.x + i];
     }
 
-    for (int i = threadIdx.x; i < N_WEIGHTS; i += size) {
-        int col = i % INP_DIM;
-        int row = i / INP_DIM;
-        s_weights_t[col][row] = pop_params[blockIdx.y * N_PARAMS + i];
+    for (int i = threadIdx.x; i < n_weights; i += size) {
+        int col = i % inp_dim;
+        int row = i / inp_dim;
+        s_weights_t[col * out_chs + row] = pop_params[blockIdx.y * n_params + i];
+    }
+
+    for (int i = threadIdx.x; i < out_chs; i += size) {
+        s_biases[i] = pop_params[blockIdx.y * n_params + n_weights + i];
     }
 
-    for (int i = threadIdx.x; i < N_BIASES; i += size) {
-        s_biases[i] = pop_params[blockIdx.y * N_PARAMS + N_WEIGHTS + i];
+    for (int i = threadIdx.x; i < 2 * nhbd_len; i += size) {
+        s_nhbd[i] = nhbd[i];
     }
 
     __syncthreads();
 
-    for (int i = 0; i < max_steps; i++) {
-        nca_update(s_sub, height, width, s_weights_t, s_biases);
+    // Every thread of the block belongs to the same individual, so all of them take the same number of steps
+    for (int i = 0; i < max_steps[blockIdx.y]; i++) {
+        nca_update(s_sub, height, width, s_weights_t, s_biases, s_nhbd, nhbd_len, vis_chs, inp_chs, out_chs);
         __syncthreads();
     }
 
-    for (int i = 0; i < INP_CHS * size; i += size) {
+    for (int i = 0; i < inp_chs * size; i += size) {
         pop_subs[grid_elem_base + threadIdx.x + i] = s_sub[threadIdx.x + i];
     }
 }
@@ -1,9 +1,15 @@
-use crate::constants::{N_PARAMS, N_WEIGHTS};
-use crate::{constants::INP_CHS, grid::Grid, nca::NCA, substrate::Substrate};
-use cudarc::driver::{CudaContext, CudaFunction, LaunchConfig, PushKernelArg};
+use crate::executors::Individual;
+use crate::{grid::Grid, nca::NCA, substrate::Substrate};
+use cudarc::driver::{
+    CudaContext, CudaFunction, LaunchConfig, PushKernelArg,
+    sys::{CUdevice_attribute, CUfunction_attribute},
+};
 use itertools::Itertools;
 use std::sync::{Arc, LazyLock};
 
+/// Size of the per-thread output buffer in `kernel.cu`
+const MAX_OUT_CHS: usize = 32;
+
 #[derive(Clone)]
 pub struct NCAExecutorGpu {
     inner: NCAExecutorGpuBatch,
@@ -51,12 +57,6 @@ impl NCAExecutorGpuBatch {
     }
 }
 
-#[derive(Clone)]
-pub struct Individual {
-    pub nca: NCA,
-    pub substrates: Vec<Substrate>,
-}
-
 #[derive(Clone)]
 pub struct PopNCAExecutorGpuBatch {
     pub individuals: Vec<Individual>,
@@ -69,11 +69,7 @@ impl PopNCAExecutorGpuBatch {
             .map(|nca| {
                 let substrates = grids
                     .iter()
-                    .map(|grid| {
-                        let mut grid = (*grid).clone();
-                        nca.transform_pipeline.apply(&mut grid);
-                        Substrate::from_grid(&grid)
-                    })
+                    .map(|grid| Substrate::from_grid(&nca.input_grid(grid), nca.substrate_spec))
                     .collect_vec();
                 Individual { nca, substrates }
             })
@@ -100,18 +96,53 @@ impl PopNCAExecutorGpuBatch {
             panic!("Grids with more than 1024 elements not supported.")
         }
 
-        let max_steps_all_equal = self.individuals.iter().map(|ind| ind.nca.max_steps).all_equal();
+        let nca_0 = &self.individuals[0].nca;
+        let neighborhood = nca_0.neighborhood;
+        let spec = nca_0.substrate_spec;
 
-        if !max_steps_all_equal {
-            panic!("Every individual in the population should have equal max_steps")
+        if !self.individuals.iter().all(|ind| ind.nca.neighborhood == neighborhood) {
+            panic!("Every individual in the population should have the same neighborhood")
+        }
+
+        if !self.individuals.iter().all(|ind| ind.nca.substrate_spec == spec) {
+            panic!("Every individual in the population should have the same substrate spec")
+        }
+
+        if spec.out_chs() > MAX_OUT_CHS {
+            panic!("{:?} has more than {} output channels", spec, MAX_OUT_CHS)
+        }
+
+        let inp_chs = spec.inp_chs();
+        let n_weights = nca_0.n_weights();
+        let n_params = nca_0.n_params();
+        let nhbd = neighborhood
+            .offsets()
+            .into_iter()
+            .flat_map(|(dx, dy)| [dx, dy])
+            .collect_vec();
+
+        let ctxs = &*CUDA;
+        // TODO: figure out a better way to distribute work
+        let (ctx, kernel) = &ctxs[rayon::current_thread_index().unwrap_or(0) % ctxs.len()];
+
+        // Substrate, transposed weights, biases and neighborhood all live in shared memory
+        let shared_mem_bytes =
+            (max_grid_size as usize * inp_chs + n_params) * size_of::<f32>() + nhbd.len() * size_of::<i32>();
+        let max_shared_mem_bytes = max_shared_mem_bytes(ctx);
+
+        if shared_mem_bytes > max_shared_mem_bytes {
+            panic!(
+                "{:?} with {:?} needs {} bytes of shared memory on {} element grids; the device supports {}",
+                neighborhood, spec, shared_mem_bytes, max_grid_size, max_shared_mem_bytes
+            )
         }
 
         let pop_size = self.individuals.len();
-        let sub_max_len = INP_CHS * max_grid_size as usize;
+        let sub_max_len = inp_chs * max_grid_size as usize;
         let ind_subs_total_len = sub_max_len * substrates_0.len();
         let pop_sub_total_len = ind_subs_total_len * pop_size;
         let mut pop_substrates = vec![0.0; pop_sub_total_len];
-        let mut pop_nca_params = vec![0.0; pop_size * N_PARAMS];
+        let mut pop_nca_params = vec![0.0; pop_size * n_params];
 
         for (ind_idx, ind) in self.individuals.iter().enumerate() {
             for (i, s) in ind.substrates.iter().enumerate() {
@@ -121,25 +152,32 @@ impl PopNCAExecutorGpuBatch {
             }
 
             let nca = &ind.nca;
-            let start = ind_idx * N_PARAMS;
+            nca.check_shape();
+            let start = ind_idx * n_params;
 
-            let dst_weights = &mut pop_nca_params[start..start + N_WEIGHTS];
+            let dst_weights = &mut pop_nca_params[start..start + n_weights];
             dst_weights.copy_from_slice(&nca.weights);
 
-            let dst_biases = &mut pop_nca_params[(start + N_WEIGHTS)..(start + N_PARAMS)];
+            let dst_biases = &mut pop_nca_params[(start + n_weights)..(start + n_params)];
             dst_biases.copy_from_slice(&nca.biases);
         }
 
-        let ctxs = &*CUDA;
-        // TODO: figure out a better way to distribute work
-        let (ctx, kernel) = &ctxs[rayon::current_thread_index().unwrap_or(0) % ctxs.len()];
         let stream = ctx.per_thread_stream();
 
         let mut d_pop_subs = stream.clone_htod(&pop_substrates).unwrap();
         let d_pop_nca_params = stream.clone_htod(&pop_nca_params).unwrap();
         let d_heights = stream.clone_htod(&heights).unwrap();
         let d_widths = stream.clone_htod(&widths).unwrap();
-        let max_steps = self.individuals[0].nca.max_steps as i32;
+        let d_nhbd = stream.clone_htod(&nhbd).unwrap();
+        let nhbd_len = neighborhood.size() as i32;
+        let vis_chs = spec.vis_chs() as i32;
+        let hid_chs = spec.hid_chs as i32;
+        let max_steps = self
+            .individuals
+            .iter()
+            .map(|ind| ind.nca.max_steps as i32)
+            .collect_vec();
+        let d_max_steps = stream.clone_htod(&max_steps).unwrap();
         let n_grids = substrates_0.len() as i32;
         let mut builder = stream.launch_builder(kernel);
 
@@ -147,13 +185,17 @@ impl PopNCAExecutorGpuBatch {
         builder.arg(&d_pop_nca_params);
         builder.arg(&d_heights);
         builder.arg(&d_widths);
-        builder.arg(&max_steps);
+        builder.arg(&d_max_steps);
         builder.arg(&max_grid_size);
+        builder.arg(&d_nhbd);
+        builder.arg(&nhbd_len);
+        builder.arg(&vis_chs);
+        builder.arg(&hid_chs);
 
         let lc = LaunchConfig {
             grid_dim: (n_grids as u32, pop_size as u32, 1),
             block_dim: (max_grid_size as u32, 1, 1),
-            shared_mem_bytes: (max_grid_size as usize * INP_CHS * core::mem::size_of::<f32>()) as u32,
+            shared_mem_bytes: shared_mem_bytes as u32,
         };
 
         unsafe { builder.launch(lc) }.unwrap();
@@ -196,6 +238,13 @@ pub static CUDA: LazyLock<T> = LazyLock::new(|| {
         let ctx = cudarc::driver::CudaContext::new(dev_ord).unwrap();
         let module = ctx.load_module(ptx.clone()).unwrap();
         let kernel = Arc::new(module.load_function("pop_nca_executor_run_batch").unwrap());
+        // Larger substrate specs and neighborhoods need more than the default 48KB of dynamic shared memory
+        kernel
+            .set_attribute(
+                CUfunction_attribute::CU_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES,
+                max_shared_mem_bytes(&ctx) as i32,
+            )
+            .unwrap();
 
         ctxs.push((ctx, kernel));
     }
@@ -203,3 +252,9 @@ pub static CUDA: LazyLock<T> = LazyLock::new(|| {
 
     ctxs
 });
+
+/// Maximum dynamic shared memory per block the device allows a kernel to opt in to
+fn max_shared_mem_bytes(ctx: &CudaContext) -> usize {
+    ctx.attribute(CUdevice_attribute::CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK_OPTIN)
+        .unwrap() as usize
+}
@@ -1,21 +1,54 @@
-use crate::{
-    executors::{cpu::NCAExecutorCpu, gpu::NCAExecutorGpu},
-    grid::Grid,
-    nca::NCA,
-    substrate::Substrate,
-};
+#[cfg(feature = "gpu")]
+use crate::executors::gpu::NCAExecutorGpu;
+use crate::{executors::cpu::NCAExecutorCpu, grid::Grid, nca::NCA, substrate::Substrate};
 use serde::{Deserialize, Serialize};
 pub mod cpu;
+#[cfg(feature = "gpu")]
 pub mod gpu;
 
+#[cfg(not(feature = "gpu"))]
+pub(crate) const GPU_UNAVAILABLE: &str = "GPU backend requested but enca was built without the `gpu` feature";
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum Backend {
     CPU,
     GPU,
 }
 
+impl Backend {
+    /// Eagerly initializes the backend so that setup cost and errors surface before training starts.
+    pub fn init(&self) {
+        match self {
+            Backend::CPU => {}
+            #[cfg(feature = "gpu")]
+            Backend::GPU => _ = &*gpu::CUDA,
+            #[cfg(not(feature = "gpu"))]
+            Backend::GPU => panic!("{GPU_UNAVAILABLE}"),
+        }
+    }
+}
+
+impl Default for Backend {
+    /// GPU when built with the `gpu` feature, CPU otherwise.
+    fn default() -> Self {
+        if cfg!(feature = "gpu") {
+            Backend::GPU
+        } else {
+            Backend::CPU
+        }
+    }
+}
+
+/// An NCA and the substrates it was executed on.
+#[derive(Clone)]
+pub struct Individual {
+    pub nca: NCA,
+    pub substrates: Vec<Substrate>,
+}
+
 enum NCAExecutorInner {
-    Cpu(NCAExecutorCpu),
+    Cpu(Box<NCAExecutorCpu>),
+    #[cfg(feature = "gpu")]
     Gpu(NCAExecutorGpu),
 }
 
@@ -27,17 +60,21 @@ impl NCAExecutor {
     pub fn new(nca: NCA, grid: &Grid, backend: Backend) -> Self {
         match backend {
             Backend::CPU => Self {
-                inner: NCAExecutorInner::Cpu(NCAExecutorCpu::new(nca, grid)),
+                inner: NCAExecutorInner::Cpu(Box::new(NCAExecutorCpu::new(nca, grid))),
             },
+            #[cfg(feature = "gpu")]
             Backend::GPU => Self {
                 inner: NCAExecutorInner::Gpu(NCAExecutorGpu::new(nca, grid)),
             },
+            #[cfg(not(feature = "gpu"))]
+            Backend::GPU => panic!("{GPU_UNAVAILABLE}"),
         }
     }
 
     pub fn run(&mut self) {
         match &mut self.inner {
             NCAExecutorInner::Cpu(cpu) => cpu.run(),
+            #[cfg(feature = "gpu")]
             NCAExecutorInner::Gpu(gpu) => gpu.run(),
         }
     }
@@ -45,6 +82,7 @@ impl NCAExecutor {
     pub fn substrate(&self) -> &Substrate {
         match &self.inner {
             NCAExecutorInner::Cpu(cpu) => &cpu.substrate,
+            #[cfg(feature = "gpu")]
             NCAExecutorInner::Gpu(gpu) => gpu.substrate(),
         }
     }
@@ -52,6 +90,7 @@ impl NCAExecutor {
     pub fn step(&mut self) -> bool {
         match &mut self.inner {
             NCAExecutorInner::Cpu(cpu) => cpu.step(),
+            #[cfg(feature = "gpu")]
             NCAExecutorInner::Gpu(_) => panic!("step() not implemented for GPU backend"),
         }
     }
@@ -59,6 +98,7 @@ impl NCAExecutor {
     pub fn steps(&mut self) -> usize {
         match &mut self.inner {
             NCAExecutorInner::Cpu(cpu) => cpu.steps,
+            #[cfg(feature = "gpu")]
             NCAExecutorInner::Gpu(_) => panic!("steps not implemented for GPU backend"),
         }
     }
@@ -66,6 +106,7 @@ impl NCAExecutor {
     pub fn nca(&self) -> &NCA {
         match &self.inner {
             NCAExecutorInner::Cpu(cpu) => &cpu.nca,
+            #[cfg(feature = "gpu")]
             NCAExecutorInner::Gpu(gpu) => gpu.nca(),
         }
     }
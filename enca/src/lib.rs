@@ -10,8 +10,12 @@ pub mod executors;
 pub mod grid;
 pub mod metrics;
 pub mod nca;
+pub mod neighborhood;
+pub mod optimizer;
+pub mod scheduler;
 pub mod selector;
 pub mod serde_utils;
+pub mod shape;
 pub mod solver;
 pub mod substrate;
 pub mod transforms;
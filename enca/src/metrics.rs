@@ -1,3 +1,4 @@
+use cmaes::TerminationReason;
 use serde::{Deserialize, Serialize};
 
 use crate::nca::NCA;
@@ -22,6 +23,69 @@ pub struct OverallSummary {
     pub seed: u64,
 }
 
+/// Written to the run directory when a train run starts so that an interrupted run can be resumed with the same seed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunInfo {
+    pub seed: u64,
+    /// Wall-clock time of the run and the runs it resumed, updated whenever a task completes. Time spent after the
+    /// latest completed task of an interrupted run is not counted
+    #[serde(default)]
+    pub elapsed_ms: u128,
+}
+
+/// Why `solver::train` stopped
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TrainStopReason {
+    /// All `config.epochs` epochs ran
+    Epochs,
+    /// Every individual solved the train examples
+    Solved,
+    /// The task time budget ran out
+    TimeBudget,
+}
+
+/// Outcome of the parameter search of one individual in one epoch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndividualEpoch {
+    /// Index of the individual in the population
+    pub individual: usize,
+    /// Best fitness found by the run
+    pub fitness: f64,
+    /// Mean train accuracy of the best NCA found by the run
+    pub mean_acc: f32,
+    /// Whether the run's NCA replaced the individual's NCA
+    pub accepted: bool,
+    /// Termination reasons of the optimizer
+    pub termination_reasons: Vec<TerminationReason>,
+    pub function_evals: usize,
+    pub final_sigma: f64,
+    /// Number of parameters optimized by the run
+    pub subset_size: usize,
+    /// Step count of the best NCA found by the run
+    pub max_steps: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochHistory {
+    pub epoch: usize,
+    pub duration_ms: u128,
+    /// Individuals whose search would have started after the deadline have no record
+    pub individuals: Vec<IndividualEpoch>,
+}
+
+/// Per-epoch record of `solver::train` for diagnosing training runs. Written to `metrics/<task>.history.json` by
+/// `bin/train`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainHistory {
+    pub task_id: String,
+    pub stop_reason: TrainStopReason,
+    pub epochs: Vec<EpochHistory>,
+    /// Best fitness of the probe of each orientation in `Transform::dihedral_group`. Empty unless
+    /// `config.orientation_probe` is set
+    #[serde(default)]
+    pub orientation_probe: Vec<f32>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TrainOutput {
     pub nca: NCA,
@@ -1,6 +1,5 @@
 use crate::{
-    constants::{INP_DIM, N_BIASES, N_WEIGHTS, OUT_CHS},
-    transforms::TransformPipeline,
+    grid::Grid, neighborhood::Neighborhood, shape::ShapeRule, substrate::SubstrateSpec, transforms::TransformPipeline,
 };
 use mimalloc::MiMalloc;
 use rand::Rng;
@@ -16,19 +15,57 @@ pub struct NCA {
     pub biases: Vec<f32>,
     pub max_steps: usize,
     pub transform_pipeline: TransformPipeline,
+    /// Models saved before the neighborhood was configurable use the default Von Neumann neighborhood
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+    /// Models saved before the channel layout was configurable use the default layout and binary color encoding
+    #[serde(default)]
+    pub substrate_spec: SubstrateSpec,
+    /// Output size of the task. Models saved before output shapes were predicted preserve the input size
+    #[serde(default)]
+    pub shape_rule: ShapeRule,
 }
 
 impl NCA {
-    pub fn new(max_steps: usize) -> Self {
-        let weights = vec![0.0; OUT_CHS * INP_DIM];
-        let biases = vec![0.0; OUT_CHS];
-
-        Self {
-            weights,
-            biases,
+    pub fn new(max_steps: usize, neighborhood: Neighborhood, substrate_spec: SubstrateSpec) -> Self {
+        let mut nca = Self {
+            weights: vec![],
+            biases: vec![],
             max_steps,
             transform_pipeline: TransformPipeline::default(),
-        }
+            neighborhood,
+            substrate_spec,
+            shape_rule: ShapeRule::default(),
+        };
+
+        nca.weights = vec![0.0; nca.n_weights()];
+        nca.biases = vec![0.0; nca.n_biases()];
+
+        nca
+    }
+
+    /// Input dimensions; one column per input channel of every neighbor
+    pub fn inp_dim(&self) -> usize {
+        self.neighborhood.size() * self.substrate_spec.inp_chs()
+    }
+
+    pub fn n_weights(&self) -> usize {
+        self.substrate_spec.out_chs() * self.inp_dim()
+    }
+
+    pub fn n_biases(&self) -> usize {
+        self.substrate_spec.out_chs()
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.n_weights() + self.n_biases()
+    }
+
+    /// Grid the NCA runs on for `input`: the predicted output canvas with the transform pipeline applied
+    pub fn input_grid(&self, input: &Grid) -> Grid {
+        let mut grid = self.shape_rule.canvas_or_input(input).apply(input);
+        self.transform_pipeline.apply(&mut grid);
+        grid
     }
 
     /// Initialize weights and biases with small random values
@@ -44,23 +81,40 @@ impl NCA {
         }
     }
 
-    pub fn from_vec(weights: &[f32], biases: &[f32], max_steps: usize) -> Self {
-        let mut nca = Self::new(max_steps);
-
-        if weights.len() != N_WEIGHTS {
-            panic!("Expected {} weights; found {}", N_WEIGHTS, weights.len())
-        }
-
-        if biases.len() != N_BIASES {
-            panic!("Expected {} biases; found {}", N_BIASES, biases.len());
-        }
+    pub fn from_vec(
+        weights: &[f32],
+        biases: &[f32],
+        max_steps: usize,
+        neighborhood: Neighborhood,
+        substrate_spec: SubstrateSpec,
+    ) -> Self {
+        let mut nca = Self::new(max_steps, neighborhood, substrate_spec);
 
         nca.weights = weights.to_vec();
         nca.biases = biases.to_vec();
+        nca.check_shape();
 
         nca
     }
 
+    /// Panics if the number of weights or biases does not match the neighborhood and substrate spec, e.g. in a
+    /// hand-edited model JSON
+    pub fn check_shape(&self) {
+        if self.weights.len() != self.n_weights() {
+            panic!(
+                "Expected {} weights for {:?} and {:?}; found {}",
+                self.n_weights(),
+                self.neighborhood,
+                self.substrate_spec,
+                self.weights.len()
+            )
+        }
+
+        if self.biases.len() != self.n_biases() {
+            panic!("Expected {} biases; found {}", self.n_biases(), self.biases.len());
+        }
+    }
+
     pub fn to_vec(&self) -> Vec<f32> {
         let mut out = Vec::with_capacity(self.weights.len() + self.biases.len());
         out.extend(self.weights.to_vec());
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// The cells an NCA reads when updating a cell. Together with the `SubstrateSpec` it determines the size of the
+/// NCA's weight matrix.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// Cells within Manhattan distance `radius`
+    VonNeumann { radius: usize },
+    /// Cells within Chebyshev distance `radius`
+    Moore { radius: usize },
+}
+
+impl Default for Neighborhood {
+    fn default() -> Self {
+        Neighborhood::VonNeumann { radius: 1 }
+    }
+}
+
+impl Neighborhood {
+    pub fn radius(&self) -> usize {
+        match *self {
+            Neighborhood::VonNeumann { radius } | Neighborhood::Moore { radius } => radius,
+        }
+    }
+
+    /// `(dx, dy)` offsets of the neighbors in row-major order. The order defines the weight layout: column
+    /// `inp_ch * size() + ni` of the weight matrix is input channel `inp_ch` of neighbor `ni`.
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        let r = self.radius() as i32;
+        (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+            .filter(|(dx, dy)| match self {
+                Neighborhood::VonNeumann { .. } => dx.abs() + dy.abs() <= r,
+                Neighborhood::Moore { .. } => true,
+            })
+            .collect()
+    }
+
+    /// Number of neighbors, including the cell itself
+    pub fn size(&self) -> usize {
+        let r = self.radius();
+        match self {
+            Neighborhood::VonNeumann { .. } => 2 * r * (r + 1) + 1,
+            Neighborhood::Moore { .. } => (2 * r + 1).pow(2),
+        }
+    }
+}
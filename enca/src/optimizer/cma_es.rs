@@ -0,0 +1,158 @@
+use super::{FUN_TARGET, ParamOptimizer, Search};
+use crate::config::{Config, Restarts};
+use cmaes::objective_function::BatchObjectiveFunction;
+use cmaes::restart::{BIPOP, IPOP, RestartOptions, RestartStrategy};
+use cmaes::snapshot::HistorySnapshot;
+use cmaes::{CMAESOptions, DVector, Individual, IntegerCoordinates, Snapshot, TerminationReason};
+use std::time::Instant;
+
+/// CMA-ES with the covariance model, restarts, bounds, warm starts and step range of the config
+pub struct CmaEs<'a> {
+    pub config: &'a Config,
+    /// Distribution of the previous epoch to continue from in a single run
+    pub warm_start: Option<Snapshot>,
+}
+
+impl ParamOptimizer for CmaEs<'_> {
+    /// Optimizes with a single CMA-ES run, or with restarts when `config.restarts` is set. Every run starts from
+    /// `initial_mean` and the runs share `config.max_fun_evals`.
+    ///
+    /// A single run continues from the step size, covariance and evolution paths of the warm start when given.
+    fn search<F: BatchObjectiveFunction + Clone>(
+        &self,
+        objective: F,
+        initial_mean: Vec<f64>,
+        seed: u64,
+        deadline: Option<Instant>,
+    ) -> Search {
+        let config = self.config;
+        let covariance_model = config.covariance_model.to_cmaes(initial_mean.len());
+        let step_range = config.evolved_step_range();
+        let n_params = initial_mean.len() - usize::from(step_range.is_some());
+        // Parameters are unbounded when only the step count is
+        let bounds = (config.param_bound.is_some() || step_range.is_some()).then(|| {
+            let bound = config.param_bound.unwrap_or(f64::INFINITY);
+            let (mut lower, mut upper) = (vec![-bound; n_params], vec![bound; n_params]);
+            if let Some((lowest, highest)) = step_range {
+                lower.push(lowest as f64);
+                upper.push(highest as f64);
+            }
+            (lower, upper)
+        });
+        let initial_mean = match &bounds {
+            Some((lower, upper)) => initial_mean
+                .into_iter()
+                .zip(lower.iter().zip(upper))
+                .map(|(x, (&lower, &upper))| x.clamp(lower, upper))
+                .collect(),
+            None => initial_mean,
+        };
+        // The step count is the last coordinate
+        let integers = step_range.map(|_| IntegerCoordinates::new(vec![n_params]));
+
+        let Some(restarts) = config.restarts else {
+            // Evaluations of a warm-started run count from the start of the first run
+            let start_evals = self.warm_start.as_ref().map_or(0, |snapshot| snapshot.function_evals);
+
+            let mut options = CMAESOptions::new(initial_mean, config.initial_sigma)
+                .covariance_model(covariance_model)
+                .tol_fun_hist(1e-12)
+                .fun_target(FUN_TARGET)
+                .seed(seed)
+                .max_function_evals(start_evals + config.max_fun_evals);
+
+            if let Some((lower, upper)) = bounds {
+                options = options
+                    .bounds(lower, upper)
+                    .boundary_handling(config.boundary_handling.to_cmaes());
+            }
+
+            if let Some(integers) = integers {
+                options = options.integer_coordinates(integers);
+            }
+
+            if let Some(deadline) = deadline {
+                options = options.max_time(deadline.saturating_duration_since(Instant::now()));
+            }
+
+            let mut es_state = options.build(objective).unwrap();
+
+            if let Some(mut snapshot) = self.warm_start.clone() {
+                // Search around the individual's current parameters with a fresh history, so the termination criteria
+                // only see this epoch
+                snapshot.mean = es_state.mean().clone();
+                snapshot.history = HistorySnapshot::default();
+                es_state
+                    .restore(snapshot)
+                    .unwrap_or_else(|e| panic!("Invalid CMA-ES snapshot: {e:?}"));
+            }
+
+            let results = es_state.run_batch();
+            let snapshot = (config.warm_start && results.reasons == [TerminationReason::MaxFunctionEvals])
+                .then(|| es_state.snapshot());
+
+            return Search {
+                best: results.overall_best.unwrap(),
+                termination_reasons: results.reasons,
+                function_evals: es_state.function_evals() - start_evals,
+                final_sigma: es_state.sigma(),
+                snapshot,
+            };
+        };
+
+        let strategy = match restarts {
+            Restarts::IPOP => RestartStrategy::IPOP(IPOP::default()),
+            Restarts::BIPOP => RestartStrategy::BIPOP(BIPOP::default()),
+        };
+
+        // The search range is unused since every run starts from the current parameters
+        let mut options = RestartOptions::new(initial_mean.len(), -1.0..=1.0, strategy)
+            .initial_mean(initial_mean.clone())
+            .initial_step_size(config.initial_sigma)
+            .covariance_model(covariance_model)
+            .tol_fun_hist(1e-12)
+            .fun_target(FUN_TARGET)
+            .seed(seed)
+            .max_function_evals(config.max_fun_evals);
+
+        if let Some((lower, upper)) = bounds {
+            options = options
+                .bounds(lower, upper)
+                .boundary_handling(config.boundary_handling.to_cmaes());
+        }
+
+        if let Some(integers) = integers {
+            options = options.integer_coordinates(integers);
+        }
+
+        if let Some(deadline) = deadline {
+            options = options.max_time(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        let restarter = options
+            .build()
+            .unwrap_or_else(|e| panic!("Invalid CMA-ES restart options: {e:?}"));
+        let results = restarter.run_batch(|| objective.clone());
+
+        // The deadline can pass before the first run starts
+        let Some(last_run) = results.runs.last() else {
+            let point = DVector::from(initial_mean);
+            let value = objective.evaluate_batch(std::slice::from_ref(&point))[0];
+            return Search {
+                best: Individual { point, value },
+                termination_reasons: vec![TerminationReason::MaxTime],
+                function_evals: 1,
+                final_sigma: config.initial_sigma,
+                snapshot: None,
+            };
+        };
+
+        Search {
+            best: results.best.clone().unwrap(),
+            termination_reasons: last_run.termination_data.reasons.clone(),
+            function_evals: results.function_evals,
+            final_sigma: last_run.final_step_size,
+            snapshot: None,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::{Budget, ParamOptimizer, Search};
+use crate::config::Config;
+use cmaes::DVector;
+use cmaes::objective_function::BatchObjectiveFunction;
+use rand::seq::index;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, StandardNormal};
+use std::time::Instant;
+
+/// DE/rand/1/bin differential evolution (Storn and Price, 1997). The population starts at the initial mean and points
+/// sampled around it with standard deviation `config.initial_sigma`. Each generation, every member is crossed over with
+/// the mutant `a + differential_weight * (b - c)` of three other random members and replaced by the trial point when
+/// it is no worse. The trial points of a generation are evaluated in one batch
+pub struct DifferentialEvolution<'a> {
+    pub config: &'a Config,
+    /// Number of members. Must be at least 4
+    pub population: usize,
+    pub differential_weight: f64,
+    /// Probability of taking each coordinate from the mutant
+    pub crossover_rate: f64,
+}
+
+impl ParamOptimizer for DifferentialEvolution<'_> {
+    fn search<F: BatchObjectiveFunction + Clone>(
+        &self,
+        objective: F,
+        initial_mean: Vec<f64>,
+        seed: u64,
+        deadline: Option<Instant>,
+    ) -> Search {
+        assert!(
+            self.population >= 4,
+            "DE population must be at least 4, got {}",
+            self.population
+        );
+
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut budget = Budget::new(objective, self.config, deadline);
+        let sigma = self.config.initial_sigma;
+        let mean = DVector::from(initial_mean);
+        let n = mean.len();
+
+        let mut members = (0..self.population)
+            .map(|i| match i {
+                0 => mean.clone(),
+                _ => &mean + sigma * DVector::from_fn(n, |_, _| StandardNormal.sample(&mut rng)),
+            })
+            .map(|x| budget.project(x))
+            .collect::<Vec<_>>();
+        let mut values = budget.evaluate(&members);
+
+        let reasons = loop {
+            let reasons = budget.termination_reasons();
+            if !reasons.is_empty() {
+                break reasons;
+            }
+
+            let trials = (0..self.population)
+                .map(|i| {
+                    let others = index::sample(&mut rng, self.population - 1, 3)
+                        .into_iter()
+                        .map(|j| if j >= i { j + 1 } else { j })
+                        .collect::<Vec<_>>();
+                    let (a, b, c) = (&members[others[0]], &members[others[1]], &members[others[2]]);
+                    let forced = rng.random_range(0..n);
+
+                    let trial = DVector::from_fn(n, |j, _| {
+                        if j == forced || rng.random::<f64>() < self.crossover_rate {
+                            a[j] + self.differential_weight * (b[j] - c[j])
+                        } else {
+                            members[i][j]
+                        }
+                    });
+                    budget.project(trial)
+                })
+                .collect::<Vec<_>>();
+            let trial_values = budget.evaluate(&trials);
+
+            for (i, (trial, value)) in trials.into_iter().zip(trial_values).enumerate() {
+                if value <= values[i] {
+                    members[i] = trial;
+                    values[i] = value;
+                }
+            }
+        };
+
+        budget.finish(reasons, spread(&members))
+    }
+}
+
+/// Mean standard deviation of the coordinates of `members`
+fn spread(members: &[DVector<f64>]) -> f64 {
+    let count = members.len() as f64;
+    let mean = members.iter().sum::<DVector<f64>>() / count;
+    let variance = members.iter().map(|x| (x - &mean).map(|d| d * d)).sum::<DVector<f64>>() / count;
+    variance.map(f64::sqrt).mean()
+}
@@ -0,0 +1,126 @@
+//! Evolution strategies that optimize the trained parameters of an individual in each epoch. `Config::optimizer` selects
+//! the strategy; all of them minimize the same batch objective.
+
+mod cma_es;
+mod differential_evolution;
+mod nes;
+mod one_plus_one;
+
+pub use cma_es::CmaEs;
+pub use differential_evolution::DifferentialEvolution;
+pub use nes::Nes;
+pub use one_plus_one::OnePlusOne;
+
+use crate::config::Config;
+use cmaes::objective_function::BatchObjectiveFunction;
+use cmaes::{DVector, Individual, Snapshot, TerminationReason};
+use std::time::Instant;
+
+/// Fitness at which a search stops, as the train examples are then solved
+pub const FUN_TARGET: f64 = 1e-7;
+
+/// Outcome of the search of one individual in one epoch
+pub struct Search {
+    pub best: Individual,
+    /// Termination reasons of the last run
+    pub termination_reasons: Vec<TerminationReason>,
+    /// Function evaluations over all runs
+    pub function_evals: usize,
+    /// Step size at the end of the last run
+    pub final_sigma: f64,
+    /// State of the run to warm-start the next epoch from. Only set by CMA-ES with `config.warm_start` when the run
+    /// ended on the evaluation limit, as a converged or stagnated run would stop again right away
+    pub snapshot: Option<Snapshot>,
+}
+
+/// An optimizer for the trained parameters of an individual
+pub trait ParamOptimizer {
+    /// Minimizes `objective` from `initial_mean` within `config.max_fun_evals` evaluations and until `deadline`,
+    /// stopping early once the fitness reaches `FUN_TARGET`.
+    fn search<F: BatchObjectiveFunction + Clone>(
+        &self,
+        objective: F,
+        initial_mean: Vec<f64>,
+        seed: u64,
+        deadline: Option<Instant>,
+    ) -> Search;
+}
+
+/// Evaluates the points of a search, keeps its best point and tells when the budget is used up. Shared by the optimizers
+/// other than CMA-ES
+struct Budget<F> {
+    objective: F,
+    max_fun_evals: usize,
+    deadline: Option<Instant>,
+    param_bound: Option<f64>,
+    function_evals: usize,
+    best: Option<Individual>,
+}
+
+impl<F: BatchObjectiveFunction> Budget<F> {
+    fn new(objective: F, config: &Config, deadline: Option<Instant>) -> Self {
+        Self {
+            objective,
+            max_fun_evals: config.max_fun_evals,
+            deadline,
+            param_bound: config.param_bound,
+            function_evals: 0,
+            best: None,
+        }
+    }
+
+    /// Clamps `x` into `config.param_bound`
+    fn project(&self, x: DVector<f64>) -> DVector<f64> {
+        match self.param_bound {
+            Some(bound) => x.map(|xi| xi.clamp(-bound, bound)),
+            None => x,
+        }
+    }
+
+    /// Evaluates `xs` in one batch. NaN values are replaced with infinity so they rank last
+    fn evaluate(&mut self, xs: &[DVector<f64>]) -> Vec<f64> {
+        let values = self
+            .objective
+            .evaluate_batch(xs)
+            .into_iter()
+            .map(|value| if value.is_nan() { f64::INFINITY } else { value })
+            .collect::<Vec<_>>();
+        self.function_evals += xs.len();
+
+        for (x, &value) in xs.iter().zip(&values) {
+            if self.best.as_ref().is_none_or(|best| value < best.value) {
+                self.best = Some(Individual {
+                    point: x.clone(),
+                    value,
+                });
+            }
+        }
+
+        values
+    }
+
+    /// The reasons to stop the search, empty while it continues
+    fn termination_reasons(&self) -> Vec<TerminationReason> {
+        let mut reasons = Vec::new();
+        if self.function_evals >= self.max_fun_evals {
+            reasons.push(TerminationReason::MaxFunctionEvals);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            reasons.push(TerminationReason::MaxTime);
+        }
+        if self.best.as_ref().is_some_and(|best| best.value <= FUN_TARGET) {
+            reasons.push(TerminationReason::FunTarget);
+        }
+        reasons
+    }
+
+    fn finish(self, reasons: Vec<TerminationReason>, final_sigma: f64) -> Search {
+        Search {
+            best: self.best.expect("The search evaluated no points"),
+            termination_reasons: reasons,
+            function_evals: self.function_evals,
+            final_sigma,
+            snapshot: None,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::{Budget, ParamOptimizer, Search};
+use crate::config::Config;
+use cmaes::DVector;
+use cmaes::objective_function::BatchObjectiveFunction;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, StandardNormal};
+use std::time::Instant;
+
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+/// Natural evolution strategy as in Salimans et al., "Evolution Strategies as a Scalable Alternative to Reinforcement
+/// Learning", 2017. Each step evaluates the mean and `population` points perturbed by mirrored pairs of Gaussian noise
+/// with the fixed step size `config.initial_sigma`, estimates the gradient from the centered ranks of their values and
+/// moves the mean with Adam
+pub struct Nes<'a> {
+    pub config: &'a Config,
+    /// Number of perturbed points per step. Must be even
+    pub population: usize,
+    pub learning_rate: f64,
+}
+
+impl ParamOptimizer for Nes<'_> {
+    fn search<F: BatchObjectiveFunction + Clone>(
+        &self,
+        objective: F,
+        initial_mean: Vec<f64>,
+        seed: u64,
+        deadline: Option<Instant>,
+    ) -> Search {
+        assert!(
+            self.population >= 2 && self.population.is_multiple_of(2),
+            "NES population must be even and at least 2, got {}",
+            self.population
+        );
+
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut budget = Budget::new(objective, self.config, deadline);
+        let sigma = self.config.initial_sigma;
+        let n = initial_mean.len();
+
+        let mut mean = budget.project(DVector::from(initial_mean));
+        let mut m = DVector::zeros(n);
+        let mut v = DVector::zeros(n);
+        let mut t = 0;
+
+        let reasons = loop {
+            let reasons = budget.termination_reasons();
+            if !reasons.is_empty() {
+                break reasons;
+            }
+
+            let noise = (0..self.population / 2)
+                .map(|_| DVector::from_fn(n, |_, _| StandardNormal.sample(&mut rng)))
+                .collect::<Vec<DVector<f64>>>();
+            let points = std::iter::once(mean.clone())
+                .chain(noise.iter().flat_map(|eps| [&mean + sigma * eps, &mean - sigma * eps]))
+                .map(|x| budget.project(x))
+                .collect::<Vec<_>>();
+            let values = budget.evaluate(&points);
+
+            // Lower values get higher utilities
+            let utilities = centered_ranks(&values[1..]);
+            let gradient = noise.iter().enumerate().fold(DVector::zeros(n), |acc, (i, eps)| {
+                acc + (utilities[2 * i] - utilities[2 * i + 1]) * eps
+            }) / (self.population as f64 * sigma);
+
+            t += 1;
+            m = BETA1 * &m + (1.0 - BETA1) * &gradient;
+            v = BETA2 * &v + (1.0 - BETA2) * gradient.component_mul(&gradient);
+            let m_hat = &m / (1.0 - BETA1.powi(t));
+            let v_hat = &v / (1.0 - BETA2.powi(t));
+            let step = m_hat.zip_map(&v_hat, |mi, vi| mi / (vi.sqrt() + EPSILON));
+            mean = budget.project(mean + self.learning_rate * step);
+        };
+
+        budget.finish(reasons, sigma)
+    }
+}
+
+/// Maps `values` to utilities evenly spaced in `[-0.5, 0.5]` by rank, with the lowest value getting `0.5`
+fn centered_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+
+    let mut utilities = vec![0.0; values.len()];
+    let denominator = (values.len() - 1).max(1) as f64;
+    for (rank, i) in order.into_iter().enumerate() {
+        utilities[i] = rank as f64 / denominator - 0.5;
+    }
+    utilities
+}
@@ -0,0 +1,57 @@
+use super::{Budget, ParamOptimizer, Search};
+use crate::config::Config;
+use cmaes::objective_function::BatchObjectiveFunction;
+use cmaes::{DVector, TerminationReason};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, StandardNormal};
+use std::time::Instant;
+
+/// (1+1)-ES with the 1/5th success rule: a single parent is mutated with isotropic Gaussian noise of step size sigma
+/// and replaced by the child when the child is no worse. The step size grows by `exp(1/3)` after a success and shrinks
+/// by `exp(-1/12)` after a failure, so it stays constant when one in five children succeeds
+pub struct OnePlusOne<'a> {
+    pub config: &'a Config,
+}
+
+impl ParamOptimizer for OnePlusOne<'_> {
+    fn search<F: BatchObjectiveFunction + Clone>(
+        &self,
+        objective: F,
+        initial_mean: Vec<f64>,
+        seed: u64,
+        deadline: Option<Instant>,
+    ) -> Search {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut budget = Budget::new(objective, self.config, deadline);
+        let min_sigma = 1e-12 * self.config.initial_sigma;
+
+        let mut parent = budget.project(DVector::from(initial_mean));
+        let mut parent_value = budget.evaluate(std::slice::from_ref(&parent))[0];
+        let mut sigma = self.config.initial_sigma;
+
+        let reasons = loop {
+            let mut reasons = budget.termination_reasons();
+            if sigma < min_sigma {
+                reasons.push(TerminationReason::TolX);
+            }
+            if !reasons.is_empty() {
+                break reasons;
+            }
+
+            let step = DVector::from_fn(parent.len(), |_, _| StandardNormal.sample(&mut rng));
+            let child = budget.project(&parent + sigma * step);
+            let child_value = budget.evaluate(std::slice::from_ref(&child))[0];
+
+            if child_value <= parent_value {
+                parent = child;
+                parent_value = child_value;
+                sigma *= (1.0f64 / 3.0).exp();
+            } else {
+                sigma *= (-1.0f64 / 12.0).exp();
+            }
+        };
+
+        budget.finish(reasons, sigma)
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::config::Config;
+
+/// Runs `f` on every item with up to `jobs` items in flight at once and returns the results in item order.
+///
+/// The threads of the global rayon pool are split evenly between the jobs. Each job runs its items inside its own
+/// rayon pool, so the population-level parallelism of `solver::train` and the CPU executors stays within the job's
+/// share of the threads. Results don't depend on `jobs` or on the order in which items complete.
+pub fn run_jobs<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, items.len().max(1));
+    let pools = job_pools(jobs, rayon::current_num_threads());
+    let next = AtomicUsize::new(0);
+
+    let mut results = thread::scope(|scope| {
+        let workers = pools
+            .iter()
+            .map(|pool| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(idx) else {
+                            break;
+                        };
+                        results.push((idx, pool.install(|| f(item))));
+                    }
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// One pool per job with `threads` split as evenly as possible; every pool gets at least one thread.
+fn job_pools(jobs: usize, threads: usize) -> Vec<ThreadPool> {
+    (0..jobs)
+        .map(|job| {
+            let num_threads = (threads / jobs + usize::from(job < threads % jobs)).max(1);
+            ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(move |i| format!("job-{job}-{i}"))
+                .build()
+                .unwrap_or_else(|e| panic!("Failed to build thread pool for job {job}: {e}"))
+        })
+        .collect()
+}
+
+/// Splits the total time budget of a run between its tasks as they start.
+pub struct TimeBudget {
+    start: Instant,
+    n_tasks: usize,
+    jobs: usize,
+    started: AtomicUsize,
+}
+
+impl TimeBudget {
+    /// Starts the clock for a run that trains `n_tasks` tasks with up to `jobs` of them running at once. Tasks that are
+    /// skipped without calling `task_config` must not be counted, or the trained tasks get less than their share.
+    pub fn new(n_tasks: usize, jobs: usize) -> Self {
+        Self {
+            start: Instant::now(),
+            n_tasks,
+            jobs: jobs.max(1),
+            started: AtomicUsize::new(0),
+        }
+    }
+
+    /// Config for the next task to train. Its task budget is `config.task_time_budget_secs`, capped by an even share
+    /// of what is left of `config.total_time_budget_secs` among the tasks still to run. Returns None once the total
+    /// budget is used up.
+    pub fn task_config(&self, config: &Config) -> Option<Config> {
+        let started = self.started.fetch_add(1, Ordering::Relaxed);
+        let mut task_config = config.clone();
+
+        let Some(total_secs) = config.total_time_budget_secs else {
+            return Some(task_config);
+        };
+
+        let remaining = Duration::from_secs_f64(total_secs).saturating_sub(self.start.elapsed());
+        if remaining.is_zero() {
+            return None;
+        }
+
+        // Up to `jobs` tasks share the remaining time concurrently
+        let tasks_left = self.n_tasks.saturating_sub(started).max(1) as u32;
+        let share = (remaining * self.jobs as u32 / tasks_left).min(remaining).as_secs_f64();
+
+        task_config.task_time_budget_secs = Some(config.task_time_budget_secs.map_or(share, |secs| secs.min(share)));
+        Some(task_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget_config(task_secs: Option<f64>, total_secs: Option<f64>) -> Config {
+        Config {
+            task_time_budget_secs: task_secs,
+            total_time_budget_secs: total_secs,
+            ..Config::default()
+        }
+    }
+
+    /// Task budget of the next task, which must be set
+    fn next_share(budget: &TimeBudget, config: &Config) -> f64 {
+        budget.task_config(config).unwrap().task_time_budget_secs.unwrap()
+    }
+
+    #[test]
+    fn test_task_config_share() {
+        let config = budget_config(None, Some(100.0));
+
+        // The remaining time shrinks by the little that elapses between the calls, so the shares are slightly below
+        // an even split
+        let budget = TimeBudget::new(4, 1);
+        let share = next_share(&budget, &config);
+        assert!(share > 24.9 && share <= 25.0, "share={share}");
+        let share = next_share(&budget, &config);
+        assert!(share > 33.2 && share <= 100.0 / 3.0, "share={share}");
+
+        // Two tasks run at once, so each gets twice the share
+        let budget = TimeBudget::new(4, 2);
+        let share = next_share(&budget, &config);
+        assert!(share > 49.9 && share <= 50.0, "share={share}");
+
+        // Tasks past the count get everything that is left
+        let budget = TimeBudget::new(1, 1);
+        next_share(&budget, &config);
+        let share = next_share(&budget, &config);
+        assert!(share > 99.9 && share <= 100.0, "share={share}");
+
+        // The task budget caps the share
+        let budget = TimeBudget::new(4, 1);
+        assert_eq!(next_share(&budget, &budget_config(Some(10.0), Some(100.0))), 10.0);
+    }
+
+    #[test]
+    fn test_task_config_unlimited_and_used_up() {
+        let budget = TimeBudget::new(4, 1);
+        let config = budget_config(Some(10.0), None);
+        assert_eq!(budget.task_config(&config), Some(config));
+
+        let budget = TimeBudget::new(4, 1);
+        assert_eq!(budget.task_config(&budget_config(None, Some(0.0))), None);
+    }
+}
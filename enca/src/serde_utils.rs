@@ -14,6 +14,8 @@ pub trait JSONReadWrite {
     where
         Self: Serialize;
 
+    /// Reads every `<name>.json` file in `dir`. Files with a compound extension, such as the
+    /// `<task>.history.json` files next to the metrics, are skipped.
     fn load(dir: &str) -> Result<Vec<(String, Self)>, Box<dyn std::error::Error>>
     where
         Self: Sized;
@@ -57,6 +59,9 @@ where
             }
 
             let file_name = entry_path.file_stem().and_then(|s| s.to_str()).unwrap().to_owned();
+            if Path::new(&file_name).extension().is_some() {
+                continue;
+            }
 
             let file = File::open(entry_path)?;
             let mut rdr = BufReader::new(file);
@@ -0,0 +1,303 @@
+/*! Output shape prediction for tasks whose output grids differ in size from their inputs.
+ *
+ * A `ShapeRule` is inferred from the train pairs of a task and maps an input grid to the `Canvas` the NCA runs on.
+ * The canvas is cut out of the input, or padded with zeros where it extends past the input, before the transform
+ * pipeline is applied, so the NCA writes its prediction directly at the output size.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::MAX_GRID_DIM, dataset::Task, grid::Grid};
+
+/// Rule mapping an input grid to the size of its output grid.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum ShapeRule {
+    /// Output has the input size
+    #[default]
+    Same,
+    /// Output size is the input size scaled by `h_num / h_den` and `w_num / w_den`
+    Scale {
+        h_num: usize,
+        h_den: usize,
+        w_num: usize,
+        w_den: usize,
+    },
+    /// Output size is the input size with height and width swapped
+    Transpose,
+    /// Every output has the same size
+    Constant { height: usize, width: usize },
+    /// Output is the bounding box of all cells with `color` in the input
+    ColorBBox { color: u8 },
+}
+
+/// Region of the input grid the NCA runs on. `y` and `x` locate its top-left corner in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Canvas {
+    pub y: usize,
+    pub x: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Canvas {
+    /// Cuts the canvas out of `grid`. Cells outside of `grid` are filled with 0.
+    pub fn apply(&self, grid: &Grid) -> Grid {
+        let (height, width) = grid.shape();
+        let data = (self.y..self.y + self.height)
+            .map(|yi| {
+                (self.x..self.x + self.width)
+                    .map(|xi| if yi < height && xi < width { grid[(yi, xi)] } else { 0 })
+                    .collect()
+            })
+            .collect();
+
+        Grid::from_vec(data)
+    }
+}
+
+impl ShapeRule {
+    /// Returns the first rule, in declaration order, that predicts the output size of every train example. Simpler
+    /// rules come first so e.g. a task that preserves the size of square grids is `Same` rather than `Transpose`.
+    pub fn infer(task: &Task) -> Option<ShapeRule> {
+        let first = task.train.first()?;
+        let (in_h, in_w) = first.input.shape();
+        let (out_h, out_w) = first.output.shape();
+
+        let (h_num, h_den) = reduce(out_h, in_h);
+        let (w_num, w_den) = reduce(out_w, in_w);
+
+        let candidates = [
+            ShapeRule::Same,
+            ShapeRule::Scale {
+                h_num,
+                h_den,
+                w_num,
+                w_den,
+            },
+            ShapeRule::Transpose,
+            ShapeRule::Constant {
+                height: out_h,
+                width: out_w,
+            },
+        ]
+        .into_iter()
+        .chain((0..10).map(|color| ShapeRule::ColorBBox { color }));
+
+        candidates.into_iter().find(|rule| {
+            task.train
+                .iter()
+                .all(|example| rule.predict(&example.input) == Some(example.output.shape()))
+        })
+    }
+
+    /// Predicted `(height, width)` of the output for `input`, or None if the rule does not apply to it.
+    pub fn predict(&self, input: &Grid) -> Option<(usize, usize)> {
+        self.canvas(input).map(|canvas| (canvas.height, canvas.width))
+    }
+
+    /// Canvas of the output for `input`, or None if the rule does not apply to it, e.g. the bounding box color is
+    /// missing or a downscaled size is not whole.
+    pub fn canvas(&self, input: &Grid) -> Option<Canvas> {
+        let (height, width) = input.shape();
+        // Scaled sizes of unseen inputs can exceed what ARC allows
+        let at_origin = |height: usize, width: usize| {
+            (height > 0 && width > 0 && height <= MAX_GRID_DIM && width <= MAX_GRID_DIM).then_some(Canvas {
+                y: 0,
+                x: 0,
+                height,
+                width,
+            })
+        };
+
+        match *self {
+            ShapeRule::Same => at_origin(height, width),
+            ShapeRule::Scale {
+                h_num,
+                h_den,
+                w_num,
+                w_den,
+            } => {
+                if (height * h_num) % h_den != 0 || (width * w_num) % w_den != 0 {
+                    return None;
+                }
+                at_origin(height * h_num / h_den, width * w_num / w_den)
+            }
+            ShapeRule::Transpose => at_origin(width, height),
+            ShapeRule::Constant { height, width } => at_origin(height, width),
+            ShapeRule::ColorBBox { color } => {
+                let cells = (0..height)
+                    .flat_map(|yi| (0..width).map(move |xi| (yi, xi)))
+                    .filter(|&(yi, xi)| input[(yi, xi)] == color);
+
+                let (y0, x0, y1, x1) = cells.fold(None, |bbox, (yi, xi)| match bbox {
+                    None => Some((yi, xi, yi, xi)),
+                    Some((y0, x0, y1, x1)) => Some((y0.min(yi), x0.min(xi), y1.max(yi), x1.max(xi))),
+                })?;
+
+                Some(Canvas {
+                    y: y0,
+                    x: x0,
+                    height: y1 - y0 + 1,
+                    width: x1 - x0 + 1,
+                })
+            }
+        }
+    }
+
+    /// Canvas of the output for `input`. Falls back to the whole input when the rule does not apply to it.
+    pub fn canvas_or_input(&self, input: &Grid) -> Canvas {
+        self.canvas(input).unwrap_or(Canvas {
+            y: 0,
+            x: 0,
+            height: input.height(),
+            width: input.width(),
+        })
+    }
+}
+
+/// `num / den` in lowest terms
+fn reduce(num: usize, den: usize) -> (usize, usize) {
+    let gcd = gcd(num, den).max(1);
+    (num / gcd, den / gcd)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::TrainExample;
+
+    fn filled(height: usize, width: usize, color: u8) -> Grid {
+        Grid::from_vec(vec![vec![color; width]; height])
+    }
+
+    /// `(height, width)` of a grid
+    type Shape = (usize, usize);
+
+    fn task(shapes: &[(Shape, Shape)]) -> Task {
+        let train = shapes
+            .iter()
+            .map(|&((in_h, in_w), (out_h, out_w))| TrainExample {
+                input: filled(in_h, in_w, 1),
+                output: filled(out_h, out_w, 1),
+            })
+            .collect();
+
+        Task {
+            id: "test".to_string(),
+            train,
+            test: vec![],
+        }
+    }
+
+    #[test]
+    fn test_infer_precedence() {
+        // Square grids keep their size under both `Same` and `Transpose`
+        assert_eq!(
+            ShapeRule::infer(&task(&[((3, 3), (3, 3)), ((5, 5), (5, 5))])),
+            Some(ShapeRule::Same)
+        );
+        assert_eq!(
+            ShapeRule::infer(&task(&[((2, 3), (3, 2)), ((4, 5), (5, 4))])),
+            Some(ShapeRule::Transpose)
+        );
+        // A single example is both scaled and of constant size
+        assert_eq!(
+            ShapeRule::infer(&task(&[((2, 2), (4, 6))])),
+            Some(ShapeRule::Scale {
+                h_num: 2,
+                h_den: 1,
+                w_num: 3,
+                w_den: 1
+            })
+        );
+        assert_eq!(
+            ShapeRule::infer(&task(&[((2, 2), (4, 6)), ((3, 5), (4, 6))])),
+            Some(ShapeRule::Constant { height: 4, width: 6 })
+        );
+        // The bounding box of the input color is the whole input, so no rule predicts both examples
+        assert_eq!(ShapeRule::infer(&task(&[((2, 2), (4, 4)), ((3, 3), (2, 2))])), None);
+    }
+
+    #[test]
+    fn test_scale_not_whole() {
+        let half = ShapeRule::Scale {
+            h_num: 1,
+            h_den: 2,
+            w_num: 1,
+            w_den: 2,
+        };
+
+        assert_eq!(half.predict(&filled(4, 6, 0)), Some((2, 3)));
+        assert_eq!(half.predict(&filled(3, 6, 0)), None);
+        assert_eq!(half.predict(&filled(4, 5, 0)), None);
+    }
+
+    #[test]
+    fn test_color_bbox() {
+        let rule = ShapeRule::ColorBBox { color: 5 };
+        let input = Grid::from_vec(vec![vec![0, 0, 0, 0], vec![0, 5, 0, 0], vec![0, 0, 0, 5]]);
+
+        assert_eq!(
+            rule.canvas(&input),
+            Some(Canvas {
+                y: 1,
+                x: 1,
+                height: 2,
+                width: 3
+            })
+        );
+
+        let input = filled(3, 4, 2);
+        assert_eq!(rule.canvas(&input), None);
+        assert_eq!(
+            rule.canvas_or_input(&input),
+            Canvas {
+                y: 0,
+                x: 0,
+                height: 3,
+                width: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_grid_dim() {
+        let double = ShapeRule::Scale {
+            h_num: 2,
+            h_den: 1,
+            w_num: 2,
+            w_den: 1,
+        };
+
+        assert_eq!(double.predict(&filled(15, 15, 0)), Some((30, 30)));
+        assert_eq!(double.predict(&filled(16, 15, 0)), None);
+        assert_eq!(double.predict(&filled(15, 16, 0)), None);
+        assert_eq!(
+            ShapeRule::Constant {
+                height: MAX_GRID_DIM + 1,
+                width: 1
+            }
+            .predict(&filled(2, 2, 0)),
+            None
+        );
+        // Nothing is trained for tasks whose train outputs are out of range
+        assert_eq!(ShapeRule::infer(&task(&[((16, 16), (32, 32))])), None);
+    }
+
+    #[test]
+    fn test_canvas_zero_padding() {
+        let grid = Grid::from_vec(vec![vec![1, 2], vec![3, 4]]);
+        let canvas = Canvas {
+            y: 1,
+            x: 1,
+            height: 2,
+            width: 3,
+        };
+
+        assert_eq!(canvas.apply(&grid).data(), &vec![vec![4, 0, 0], vec![0, 0, 0]]);
+    }
+}
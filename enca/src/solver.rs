@@ -1,30 +1,133 @@
-use crate::config::Config;
-use crate::constants::{BIASES_RNG, N_PARAMS, WEIGHTS_RNG};
-use crate::env::{compute_fitness_pop, eval};
-use crate::metrics::TrainOutput;
+use crate::config::{Config, Optimizer, OrientationProbe};
+use crate::env::{compute_fitness_pop, compute_fitness_terms_pop, eval};
+use crate::metrics::{EpochHistory, IndividualEpoch, TrainHistory, TrainOutput, TrainStopReason};
+use crate::optimizer::{CmaEs, DifferentialEvolution, Nes, OnePlusOne, ParamOptimizer, Search};
 use crate::selector::{Optimize, Score, TournamentSelector};
+use crate::shape::ShapeRule;
+use crate::transforms::{Transform, TransformPipeline};
 use crate::utils::mean;
-use crate::{dataset::Task, nca::NCA};
-use cmaes::objective_function::BatchObjectiveFunction;
-use cmaes::{CMAESOptions, DVector, ObjectiveFunction};
+use crate::{
+    augment::augment_examples,
+    dataset::{Task, TrainExample},
+    nca::NCA,
+};
+use cmaes::multi_objective::MOCMAESOptions;
+use cmaes::objective_function::{BatchMultiObjectiveFunction, BatchObjectiveFunction};
+use cmaes::{DVector, Individual, ObjectiveFunction, Snapshot};
 use core::f32;
 use itertools::Itertools;
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 
+/// Evolves a population of NCAs on the train examples of `task` and returns them best first.
+///
+/// Stops before `config.epochs` when the whole population solves the train examples or when
+/// `config.task_time_budget_secs` runs out; in the latter case the searches in flight are cut short too.
 pub fn train(task: &Task, verbose: bool, config: &Config, seed: u64) -> Vec<TrainOutput> {
+    train_with_history(task, verbose, config, seed).0
+}
+
+/// Like `train`, and also returns the history of every epoch.
+///
+/// With `config.orientation_probe`, the task is trained in the orientation chosen by the probes, and the probes share
+/// the time budget of the task.
+pub fn train_with_history(task: &Task, verbose: bool, config: &Config, seed: u64) -> (Vec<TrainOutput>, TrainHistory) {
+    let deadline = config
+        .task_time_budget_secs
+        .map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+
+    let Some(probe) = config.orientation_probe else {
+        return evolve(task, verbose, config, seed, deadline, TransformPipeline::default());
+    };
+
+    let (pipeline, probe_fitness) = choose_orientation(task, verbose, config, probe, seed, deadline);
+    let (train_ncas, mut history) = evolve(task, verbose, config, seed, deadline, pipeline);
+    history.orientation_probe = probe_fitness;
+
+    (train_ncas, history)
+}
+
+/// Trains a probe population on each orientation of `task` in `Transform::dihedral_group` and returns the pipeline
+/// of the orientation whose probe reached the lowest fitness, along with the fitness of each probe. Ties go to the
+/// earlier orientation, so the task keeps its given orientation unless another one does better.
+fn choose_orientation(
+    task: &Task,
+    verbose: bool,
+    config: &Config,
+    probe: OrientationProbe,
+    seed: u64,
+    deadline: Option<Instant>,
+) -> (TransformPipeline, Vec<f32>) {
+    let probe_config = Config {
+        epochs: probe.epochs,
+        pop: probe.pop,
+        orientation_probe: None,
+        ..config.clone()
+    };
+
+    let mut best: Option<(f32, Transform)> = None;
+    let mut probe_fitness = Vec::with_capacity(8);
+
+    for transform in Transform::dihedral_group() {
+        let pipeline = TransformPipeline {
+            steps: vec![transform.clone()],
+        };
+        let (probe_ncas, _) = evolve(task, false, &probe_config, seed, deadline, pipeline);
+        let fitness = probe_ncas
+            .iter()
+            .map(|output| output.fitness)
+            .min_by(|a, b| a.total_cmp(b))
+            .unwrap();
+
+        if verbose {
+            println!("Orientation {transform:?}: probe fitness={fitness:.3e}");
+        }
+
+        if best.as_ref().is_none_or(|(best_fitness, _)| fitness < *best_fitness) {
+            best = Some((fitness, transform));
+        }
+        probe_fitness.push(fitness);
+    }
+
+    let (_, transform) = best.unwrap();
+    let pipeline = TransformPipeline { steps: vec![transform] };
+
+    (pipeline, probe_fitness)
+}
+
+/// Evolves a population of NCAs that run on the inputs transformed by `config.transforms` and `pipeline` until
+/// `deadline`
+fn evolve(
+    task: &Task,
+    verbose: bool,
+    config: &Config,
+    seed: u64,
+    deadline: Option<Instant>,
+    pipeline: TransformPipeline,
+) -> (Vec<TrainOutput>, TrainHistory) {
     let mut rng = ChaCha8Rng::seed_from_u64(seed);
     let selector = TournamentSelector::new(config.k, Optimize::Maximize);
 
+    let mut nca = NCA::new(config.max_steps, config.neighborhood, config.substrate_spec);
+    nca.shape_rule = ShapeRule::infer(task)
+        .unwrap_or_else(|| panic!("No output shape rule fits the train examples of task {}", task.id));
+    nca.transform_pipeline = TransformPipeline {
+        steps: config.transforms.clone(),
+    }
+    .compose(&pipeline);
+
     let individual = IndividualState {
-        nca: NCA::new(config.max_steps),
-        task: task.clone(),
+        examples: augment_examples(task, nca.shape_rule, config, seed),
+        nca,
         fitness: f32::INFINITY,
         config: config.clone(),
         train_param_idxs: vec![],
         mean_acc: 0.0,
+        warm_start: None,
     };
 
     let mut population = vec![individual.clone(); config.pop];
@@ -32,7 +135,23 @@ pub fn train(task: &Task, verbose: bool, config: &Config, seed: u64) -> Vec<Trai
     // Pre-generate seeds
     let seeds: Vec<u64> = (0..population.len()).map(|_| rng.random()).collect();
 
+    let mut history = TrainHistory {
+        task_id: task.id.clone(),
+        stop_reason: TrainStopReason::Epochs,
+        epochs: Vec::with_capacity(config.epochs),
+        orientation_probe: Vec::new(),
+    };
+
     for epoch in 0..config.epochs {
+        let epoch_start = Instant::now();
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            if verbose {
+                println!("Time budget used up after {epoch} epochs");
+            }
+            history.stop_reason = TrainStopReason::TimeBudget;
+            break;
+        }
+
         if verbose {
             println!("epoch={epoch}");
         }
@@ -55,55 +174,101 @@ pub fn train(task: &Task, verbose: bool, config: &Config, seed: u64) -> Vec<Trai
             }
         }
 
-        population.par_iter_mut().enumerate().for_each(|(i, individual)| {
-            let mut new_individual = individual.clone();
-            let new_nca = &mut new_individual.nca;
-
-            let mut rng = ChaCha8Rng::seed_from_u64(seeds[i] + epoch as u64);
-
-            let mut idxs = (0..N_PARAMS).into_iter().collect_vec();
-            idxs.shuffle(&mut rng);
-
-            new_individual.train_param_idxs = idxs[0..(config.subset_size).min(idxs.len())].to_vec();
-
-            let all_params = new_nca.to_vec();
-            let initial_mean: Vec<f64> = new_individual
-                .train_param_idxs
-                .iter()
-                .map(|&i| all_params[i] as f64)
-                .collect();
-
-            let mut es_state = CMAESOptions::new(initial_mean, config.initial_sigma)
-                .tol_fun_hist(1e-12)
-                .fun_target(1e-7)
-                .seed(seeds[i] + epoch as u64)
-                .max_function_evals(config.max_fun_evals)
-                .build(new_individual.clone())
-                .unwrap();
-
-            let results = es_state.run_batch();
-
-            let overall_best = results.overall_best.unwrap();
-
-            let point = overall_best.point.clone();
-            let fitness = overall_best.value;
-
-            let new_nca = construct_nca(&new_individual, &point);
-
-            let accs = task
-                .train
-                .iter()
-                .map(|example| eval(&example.input, &example.output, &new_nca, config.backend.clone()))
-                .collect_vec();
-
-            let mean_acc = mean(&accs);
-
-            if mean_acc >= individual.mean_acc {
-                individual.nca = new_nca;
-                individual.fitness = fitness as f32;
-                individual.mean_acc = mean_acc;
-                individual.train_param_idxs = new_individual.train_param_idxs;
-            }
+        let individuals = population
+            .par_iter_mut()
+            .enumerate()
+            .filter_map(|(i, individual)| {
+                // With more individuals than threads, searches wait for a thread. Those that would start after the
+                // deadline are skipped, so the epoch ends with the searches running when the time is up
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return None;
+                }
+
+                let mut new_individual = individual.clone();
+                let warm_start = new_individual.warm_start.take();
+                let new_nca = &mut new_individual.nca;
+
+                let mut rng = ChaCha8Rng::seed_from_u64(seeds[i] + epoch as u64);
+
+                let mut idxs = (0..new_nca.n_params()).collect_vec();
+                idxs.shuffle(&mut rng);
+
+                // A warm-started run continues on the subset of the previous run
+                new_individual.train_param_idxs = match &warm_start {
+                    Some(warm_start) => warm_start.train_param_idxs.clone(),
+                    None => idxs[0..(config.subset_size).min(idxs.len())].to_vec(),
+                };
+
+                let all_params = new_nca.to_vec();
+                let mut initial_mean: Vec<f64> = new_individual
+                    .train_param_idxs
+                    .iter()
+                    .map(|&i| all_params[i] as f64)
+                    .collect();
+
+                // The step count is searched as one more coordinate after the parameters
+                if let Some((lowest, highest)) = config.evolved_step_range() {
+                    initial_mean.push(new_nca.max_steps.clamp(lowest, highest) as f64);
+                }
+
+                let search = search(
+                    &new_individual,
+                    initial_mean,
+                    warm_start.map(|warm_start| warm_start.snapshot),
+                    config,
+                    seeds[i] + epoch as u64,
+                    deadline,
+                );
+                let overall_best = search.best;
+
+                let point = overall_best.point.clone();
+                let fitness = overall_best.value;
+
+                let new_nca = construct_nca(&new_individual, &point);
+
+                let accs = task
+                    .train
+                    .iter()
+                    .map(|example| eval(&example.input, &example.output, &new_nca, config.backend.clone()))
+                    .collect_vec();
+
+                let mean_acc = mean(&accs);
+                let accepted = mean_acc >= individual.mean_acc;
+
+                let record = IndividualEpoch {
+                    individual: i,
+                    fitness,
+                    mean_acc,
+                    accepted,
+                    termination_reasons: search.termination_reasons,
+                    function_evals: search.function_evals,
+                    final_sigma: search.final_sigma,
+                    subset_size: new_individual.train_param_idxs.len(),
+                    max_steps: new_nca.max_steps,
+                };
+
+                // The distribution is kept whether or not the best point is accepted, since the next run starts from
+                // the individual's parameters either way
+                individual.warm_start = search.snapshot.map(|snapshot| WarmStart {
+                    train_param_idxs: new_individual.train_param_idxs.clone(),
+                    snapshot,
+                });
+
+                if accepted {
+                    individual.nca = new_nca;
+                    individual.fitness = fitness as f32;
+                    individual.mean_acc = mean_acc;
+                    individual.train_param_idxs = new_individual.train_param_idxs;
+                }
+
+                Some(record)
+            })
+            .collect();
+
+        history.epochs.push(EpochHistory {
+            epoch,
+            duration_ms: epoch_start.elapsed().as_millis(),
+            individuals,
         });
 
         let solved = population
@@ -132,6 +297,14 @@ pub fn train(task: &Task, verbose: bool, config: &Config, seed: u64) -> Vec<Trai
                 best_acc,
             )
         }
+
+        if solved == population.len() {
+            if verbose {
+                println!("Population solved after {} epochs", epoch + 1);
+            }
+            history.stop_reason = TrainStopReason::Solved;
+            break;
+        }
     }
 
     let mut train_ncas = Vec::with_capacity(population.len());
@@ -155,17 +328,122 @@ pub fn train(task: &Task, verbose: bool, config: &Config, seed: u64) -> Vec<Trai
     train_ncas.sort_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap());
     train_ncas.sort_by(|b, a| mean(&a.train_accs).partial_cmp(&mean(&b.train_accs)).unwrap());
 
-    train_ncas
+    (train_ncas, history)
 }
 
 #[derive(Clone)]
 struct IndividualState {
-    task: Task,
+    /// Examples the fitness is computed on: the train examples of the task and their augmented copies
+    examples: Vec<TrainExample>,
     nca: NCA,
     fitness: f32,
     mean_acc: f32,
     config: Config,
     train_param_idxs: Vec<usize>,
+    /// CMA-ES state to continue from in the next epoch when `config.warm_start` is set
+    warm_start: Option<WarmStart>,
+}
+
+/// The distribution learned by the CMA-ES run of an individual and the parameters it covers
+#[derive(Clone)]
+struct WarmStart {
+    train_param_idxs: Vec<usize>,
+    snapshot: Snapshot,
+}
+
+/// Optimizes the trained parameters of `individual` from `initial_mean` with `config.optimizer`, or with MO-CMA-ES when
+/// `config.objectives` is set.
+///
+/// CMA-ES continues from the step size, covariance and evolution paths of `warm_start` when given.
+fn search(
+    individual: &IndividualState,
+    initial_mean: Vec<f64>,
+    warm_start: Option<Snapshot>,
+    config: &Config,
+    seed: u64,
+    deadline: Option<Instant>,
+) -> Search {
+    if !config.objectives.is_empty() {
+        return search_multi_objective(individual, initial_mean, config, seed, deadline);
+    }
+
+    let objective = individual.clone();
+    match config.optimizer {
+        Optimizer::CMAES => CmaEs { config, warm_start }.search(objective, initial_mean, seed, deadline),
+        Optimizer::OnePlusOne => OnePlusOne { config }.search(objective, initial_mean, seed, deadline),
+        Optimizer::NES {
+            population,
+            learning_rate,
+        } => Nes {
+            config,
+            population,
+            learning_rate,
+        }
+        .search(objective, initial_mean, seed, deadline),
+        Optimizer::DE {
+            population,
+            differential_weight,
+            crossover_rate,
+        } => DifferentialEvolution {
+            config,
+            population,
+            differential_weight,
+            crossover_rate,
+        }
+        .search(objective, initial_mean, seed, deadline),
+    }
+}
+
+/// Optimizes the trained parameters of `individual` for `config.objectives` with MO-CMA-ES and returns the point of the
+/// Pareto front that is best in the first objective, with ties broken by the following objectives.
+fn search_multi_objective(
+    individual: &IndividualState,
+    initial_mean: Vec<f64>,
+    config: &Config,
+    seed: u64,
+    deadline: Option<Instant>,
+) -> Search {
+    let mut options = MOCMAESOptions::new(initial_mean, config.initial_sigma)
+        .seed(seed)
+        .max_function_evals(config.max_fun_evals);
+
+    if let Some(deadline) = deadline {
+        options = options.max_time(deadline.saturating_duration_since(Instant::now()));
+    }
+
+    let mut mocmaes = options
+        .build(individual.clone())
+        .unwrap_or_else(|e| panic!("Invalid MO-CMA-ES options: {e:?}"));
+    let results = mocmaes.run_batch();
+
+    let best = results
+        .pareto_front
+        .iter()
+        .min_by(|a, b| {
+            a.values
+                .iter()
+                .zip(b.values.iter())
+                .map(|(x, y)| x.total_cmp(y))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap();
+
+    let function_evals = mocmaes.function_evals();
+    let final_sigma = mean(&mocmaes.step_sizes().collect_vec());
+    // The fitness of the chosen point is reported as in single-objective training
+    let value = mocmaes.into_objective_function().evaluate(&best.point);
+
+    Search {
+        best: Individual {
+            point: best.point.clone(),
+            value,
+        },
+        termination_reasons: results.reasons,
+        function_evals,
+        final_sigma,
+        snapshot: None,
+    }
 }
 
 fn construct_nca(individual: &IndividualState, x: &DVector<f64>) -> NCA {
@@ -175,31 +453,55 @@ fn construct_nca(individual: &IndividualState, x: &DVector<f64>) -> NCA {
         all_params[*idx] = x[j] as f32;
     }
 
-    NCA::from_vec(
-        &all_params[WEIGHTS_RNG],
-        &all_params[BIASES_RNG],
-        individual.config.max_steps,
-    )
+    let nca = &individual.nca;
+    let (weights, biases) = all_params.split_at(nca.n_weights());
+
+    // The point ends with the step count when it is searched, see `Config::step_range`
+    let max_steps = if x.len() > individual.train_param_idxs.len() {
+        x[x.len() - 1] as usize
+    } else {
+        nca.max_steps
+    };
+
+    let mut new_nca = NCA::from_vec(weights, biases, max_steps, nca.neighborhood, nca.substrate_spec);
+    new_nca.shape_rule = nca.shape_rule;
+    new_nca.transform_pipeline = nca.transform_pipeline.clone();
+
+    new_nca
 }
 
 impl BatchObjectiveFunction for IndividualState {
     fn evaluate_batch(&self, xs: &[DVector<f64>]) -> Vec<f64> {
         let ncas = xs.iter().map(|x| construct_nca(self, x)).collect_vec();
-        compute_fitness_pop(&self.task.train, ncas, &self.config)
+        compute_fitness_pop(&self.examples, ncas, &self.config)
+    }
+}
+
+impl BatchMultiObjectiveFunction for IndividualState {
+    fn evaluate_batch(&self, xs: &[DVector<f64>]) -> Vec<DVector<f64>> {
+        let ncas = xs.iter().map(|x| construct_nca(self, x)).collect_vec();
+        compute_fitness_terms_pop(&self.examples, ncas, &self.config)
+            .iter()
+            .map(|terms| {
+                DVector::from_iterator(
+                    self.config.objectives.len(),
+                    self.config.objectives.iter().map(|&o| terms.objective(o)),
+                )
+            })
+            .collect()
     }
 }
 
 impl BatchObjectiveFunction for &mut IndividualState {
     fn evaluate_batch(&self, x: &[DVector<f64>]) -> Vec<f64> {
-        IndividualState::evaluate_batch(self, x)
+        <IndividualState as BatchObjectiveFunction>::evaluate_batch(self, x)
     }
 }
 
 impl ObjectiveFunction for IndividualState {
     fn evaluate(&mut self, x: &DVector<f64>) -> f64 {
         let ncas = vec![construct_nca(self, x)];
-        let examples = &self.task.train;
-        compute_fitness_pop(examples, ncas, &self.config)[0]
+        compute_fitness_pop(&self.examples, ncas, &self.config)[0]
     }
 }
 
@@ -214,3 +516,54 @@ impl Score for IndividualState {
         self.mean_acc
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    /// A task of random grids, which no NCA solves before the time budget runs out
+    fn random_task(seed: u64) -> Task {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut grid = || {
+            Grid::from_vec(
+                (0..5)
+                    .map(|_| (0..5).map(|_| rng.random_range(0..10)).collect())
+                    .collect(),
+            )
+        };
+        Task {
+            id: "random".to_string(),
+            train: (0..2)
+                .map(|_| TrainExample {
+                    input: grid(),
+                    output: grid(),
+                })
+                .collect(),
+            test: vec![],
+        }
+    }
+
+    #[test]
+    fn test_train_time_budget() {
+        let budget_secs = 1.0;
+        let config = Config {
+            epochs: 1000,
+            max_steps: 10,
+            pop: 8,
+            max_fun_evals: usize::MAX,
+            task_time_budget_secs: Some(budget_secs),
+            ..Config::default()
+        };
+        let task = random_task(0);
+
+        // Four searches per thread, which used to overrun the budget by up to four times
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let start = Instant::now();
+        let (_, history) = pool.install(|| train_with_history(&task, false, &config, 0));
+        let elapsed = start.elapsed().as_secs_f64();
+
+        assert_eq!(history.stop_reason, TrainStopReason::TimeBudget);
+        assert!(elapsed < 1.5 * budget_secs, "elapsed={elapsed}");
+    }
+}
@@ -1,9 +1,59 @@
-use crate::{
-    color::{ENCODING, decode_color},
-    constants::{INP_CHS, RW_CH_RNG},
-    grid::Grid,
-};
+use crate::{color::ColorEncoding, grid::Grid};
 use ndarray::{Array3, s};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Channel layout and color codec of a substrate.
+///
+/// The channels are laid out as `[RO visible | RW visible | hidden]`. The RO channels hold the encoded input and are
+/// never written by the NCA; the RW visible and hidden channels are its outputs.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct SubstrateSpec {
+    /// Number of hidden channels
+    pub hid_chs: usize,
+    pub encoding: ColorEncoding,
+}
+
+impl Default for SubstrateSpec {
+    fn default() -> Self {
+        Self {
+            hid_chs: 2,
+            encoding: ColorEncoding::Binary,
+        }
+    }
+}
+
+impl SubstrateSpec {
+    /// Number of visible channels (RO or RW)
+    pub fn vis_chs(&self) -> usize {
+        self.encoding.n_chs()
+    }
+
+    /// Number of input channels of the NCA
+    pub fn inp_chs(&self) -> usize {
+        2 * self.vis_chs() + self.hid_chs
+    }
+
+    /// Number of output channels of the NCA
+    pub fn out_chs(&self) -> usize {
+        self.vis_chs() + self.hid_chs
+    }
+
+    /// Index range of read only visible channels
+    pub fn ro_ch_rng(&self) -> Range<usize> {
+        0..self.vis_chs()
+    }
+
+    /// Index range of read/write visible channels
+    pub fn rw_ch_rng(&self) -> Range<usize> {
+        self.vis_chs()..2 * self.vis_chs()
+    }
+
+    /// Index range of hidden channels
+    pub fn hid_ch_rng(&self) -> Range<usize> {
+        2 * self.vis_chs()..self.inp_chs()
+    }
+}
 
 /// The lattice with all visible and hidden channels that the NCA operates on.
 #[derive(Clone, Debug)]
@@ -11,35 +61,41 @@ pub struct Substrate {
     pub data: Array3<f32>,
     pub width: usize,
     pub height: usize,
+    pub spec: SubstrateSpec,
 }
 
 impl Substrate {
-    pub fn from_grid(grid: &Grid) -> Self {
+    pub fn from_grid(grid: &Grid, spec: SubstrateSpec) -> Self {
         let height = grid.height();
         let width = grid.width();
-        let mut data = Array3::zeros((height, width, INP_CHS));
+        let mut data = Array3::zeros((height, width, spec.inp_chs()));
 
         for yi in 0..height {
             for xi in 0..width {
                 let v = grid[(yi, xi)];
-                let encoded = &ENCODING[v as usize];
+                let encoded = spec.encoding.encode(v);
                 for i in 0..encoded.len() {
                     data[(yi, xi, i)] = encoded[i]
                 }
             }
         }
 
-        Self { data, width, height }
+        Self {
+            data,
+            width,
+            height,
+            spec,
+        }
     }
 
     pub fn to_grid(&self) -> Grid {
         let mut grid_data = vec![vec![0u8; self.width]; self.height];
 
-        for yi in 0..self.height {
-            for xi in 0..self.width {
+        for (yi, row) in grid_data.iter_mut().enumerate() {
+            for (xi, cell) in row.iter_mut().enumerate() {
                 // Only RW visible channels are used
-                let v = self.data.slice(s![yi, xi, RW_CH_RNG]);
-                grid_data[yi][xi] = decode_color(v.as_slice().unwrap());
+                let v = self.data.slice(s![yi, xi, self.spec.rw_ch_rng()]);
+                *cell = self.spec.encoding.decode(v.as_slice().unwrap());
             }
         }
 
@@ -1,17 +1,54 @@
-use std::fmt::Display;
+/*! Reversible grid transforms that the NCA runs under.
+ *
+ * A `TransformPipeline` applies its steps to the grid the NCA runs on and reverts them on the prediction. Each step
+ * is a `Transform`, the enum of the `GridTransform` implementations of this module. Transforms defined in other
+ * crates implement `GridTransform` and are added as `Transform::Custom`.
+ *
+ * Besides the rotations, reflections and color remappings, which can be reverted exactly, the transforms that change
+ * the grid size store what they need to revert: `CropToBoundingBox` the box and the original size, `Pad` and `Trim`
+ * the border widths, and the scaling and tiling transforms their factors. `CropToBoundingBox`, `Trim` and `Downscale`
+ * lose information.
+ */
+
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{constants::I_COL_MAP, grid::Grid};
 
-#[derive(Serialize, Deserialize, Clone)]
+/// A transform of grids and the transform back
+pub trait GridTransform: Debug + Send + Sync {
+    fn apply(&self, grid: &mut Grid);
+
+    /// Undoes `apply`. Transforms that lose information, e.g. by merging colors, restore what they can
+    fn revert(&self, grid: &mut Grid);
+
+    /// The transform whose `apply` reverts this one on every grid, if there is one. None by default
+    fn inverse(&self) -> Option<Transform> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Identity {}
 
-#[derive(Serialize, Deserialize, Clone)]
+impl GridTransform for Identity {
+    fn apply(&self, _grid: &mut Grid) {}
+
+    fn revert(&self, _grid: &mut Grid) {}
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::Identity(Identity {}))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Rotate90CW {}
 
-impl Rotate90CW {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for Rotate90CW {
+    fn apply(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
 
@@ -26,7 +63,7 @@ impl Rotate90CW {
         *grid = rotated_grid;
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
 
@@ -41,13 +78,17 @@ impl Rotate90CW {
 
         *grid = rotated_grid;
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::Rotate270CW(Rotate270CW {}))
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Rotate180 {}
 
-impl Rotate180 {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for Rotate180 {
+    fn apply(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
         let mut rotated = vec![vec![0u8; width]; height];
@@ -59,32 +100,40 @@ impl Rotate180 {
         *grid = Grid::from_vec(rotated);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         // 180-degree rotation is its own inverse
         self.apply(grid);
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::Rotate180(Rotate180 {}))
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Rotate270CW {}
 
-impl Rotate270CW {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for Rotate270CW {
+    fn apply(&self, grid: &mut Grid) {
         // 270 CW == 90 CCW => reuse Rotate90CW::revert
         Rotate90CW {}.revert(grid);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         // Inverse of 270 CW is 90 CW => reuse Rotate90CW::apply
         Rotate90CW {}.apply(grid);
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::Rotate90CW(Rotate90CW {}))
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FlipHorizontal {}
 
-impl FlipHorizontal {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for FlipHorizontal {
+    fn apply(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
         let mut flipped = vec![vec![0u8; width]; height];
@@ -96,17 +145,21 @@ impl FlipHorizontal {
         *grid = Grid::from_vec(flipped);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         // Horizontal flip is its own inverse
         self.apply(grid);
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::FlipHorizontal(FlipHorizontal {}))
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FlipVertical {}
 
-impl FlipVertical {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for FlipVertical {
+    fn apply(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
         let mut flipped = vec![vec![0u8; width]; height];
@@ -118,17 +171,21 @@ impl FlipVertical {
         *grid = Grid::from_vec(flipped);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         // Vertical flip is its own inverse
         self.apply(grid);
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::FlipVertical(FlipVertical {}))
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ReflectMainDiagonal {}
 
-impl ReflectMainDiagonal {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for ReflectMainDiagonal {
+    fn apply(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
         let mut reflected = vec![vec![0u8; height]; width];
@@ -140,17 +197,21 @@ impl ReflectMainDiagonal {
         *grid = Grid::from_vec(reflected);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         // Reflection across main diagonal is its own inverse
         self.apply(grid);
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::ReflectMainDiagonal(ReflectMainDiagonal {}))
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ReflectAntiDiagonal {}
 
-impl ReflectAntiDiagonal {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for ReflectAntiDiagonal {
+    fn apply(&self, grid: &mut Grid) {
         let width = grid.width();
         let height = grid.height();
         let mut reflected = vec![vec![0u8; height]; width];
@@ -162,10 +223,14 @@ impl ReflectAntiDiagonal {
         *grid = Grid::from_vec(reflected);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         // Reflection across anti-diagonal is its own inverse
         self.apply(grid);
     }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::ReflectAntiDiagonal(ReflectAntiDiagonal {}))
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -176,14 +241,27 @@ pub struct RemapColors {
     rev_col_map: [u8; 10],
 }
 
-impl RemapColors {
-    pub fn apply(&self, grid: &mut Grid) {
+impl GridTransform for RemapColors {
+    fn apply(&self, grid: &mut Grid) {
         self.remap_grid(grid, &self.col_map);
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn revert(&self, grid: &mut Grid) {
         self.remap_grid(grid, &self.rev_col_map);
     }
+
+    /// The swapped maps if they are permutations that are inverses of each other. Maps built with `map` that leave a
+    /// mapped-to color in place merge two colors, which `revert` cannot tell apart
+    fn inverse(&self) -> Option<Transform> {
+        let inverts = (0..10).all(|c| self.rev_col_map[self.col_map[c] as usize] as usize == c);
+
+        inverts.then(|| {
+            Transform::RemapColors(RemapColors {
+                col_map: self.rev_col_map,
+                rev_col_map: self.col_map,
+            })
+        })
+    }
 }
 
 impl RemapColors {
@@ -214,6 +292,18 @@ impl RemapColors {
         self.col_map[a as usize] = b;
         self.rev_col_map[b as usize] = a;
     }
+
+    /// Remapping with `self` and then with `next`
+    fn then(&self, next: &RemapColors) -> RemapColors {
+        RemapColors {
+            col_map: self.col_map.map(|c| next.col_map[c as usize]),
+            rev_col_map: next.rev_col_map.map(|c| self.rev_col_map[c as usize]),
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.col_map == I_COL_MAP && self.rev_col_map == I_COL_MAP
+    }
 }
 
 impl Default for RemapColors {
@@ -231,7 +321,296 @@ impl Display for RemapColors {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Crops grids to the bounding box of the cells other than `background` in the grid it was made for, see `new`.
+/// Reverting places the grid at the box in a grid of the original size filled with `background`, so on grids other
+/// than the one it was made for, cells outside of the box are lost.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CropToBoundingBox {
+    pub background: u8,
+    /// Top-left corner of the box
+    pub y: usize,
+    pub x: usize,
+    pub height: usize,
+    pub width: usize,
+    /// Size of the grid the box was found in
+    pub full_height: usize,
+    pub full_width: usize,
+}
+
+impl CropToBoundingBox {
+    /// The bounding box of the cells of `grid` other than `background`, or the whole grid if there are none
+    pub fn new(grid: &Grid, background: u8) -> Self {
+        let (full_height, full_width) = grid.shape();
+        let cells = (0..full_height)
+            .flat_map(|y| (0..full_width).map(move |x| (y, x)))
+            .filter(|&(y, x)| grid[(y, x)] != background);
+
+        let (y0, x0, y1, x1) = cells
+            .fold(None, |bbox, (y, x)| match bbox {
+                None => Some((y, x, y, x)),
+                Some((y0, x0, y1, x1)) => Some((y0.min(y), x0.min(x), y1.max(y), x1.max(x))),
+            })
+            .unwrap_or((0, 0, full_height.saturating_sub(1), full_width.saturating_sub(1)));
+
+        Self {
+            background,
+            y: y0,
+            x: x0,
+            height: y1 - y0 + 1,
+            width: x1 - x0 + 1,
+            full_height,
+            full_width,
+        }
+    }
+}
+
+impl GridTransform for CropToBoundingBox {
+    fn apply(&self, grid: &mut Grid) {
+        let (height, width) = grid.shape();
+        let data = (self.y..self.y + self.height)
+            .map(|y| {
+                (self.x..self.x + self.width)
+                    .map(|x| {
+                        if y < height && x < width {
+                            grid[(y, x)]
+                        } else {
+                            self.background
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        *grid = Grid::from_vec(data);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        let (height, width) = grid.shape();
+        let data = (0..self.full_height)
+            .map(|y| {
+                (0..self.full_width)
+                    .map(|x| {
+                        let inside = (self.y..self.y + height).contains(&y) && (self.x..self.x + width).contains(&x);
+                        if inside {
+                            grid[(y - self.y, x - self.x)]
+                        } else {
+                            self.background
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        *grid = Grid::from_vec(data);
+    }
+}
+
+/// Adds a border of `color` with the given width on each side. Reverting removes the border
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Pad {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+    pub color: u8,
+}
+
+impl GridTransform for Pad {
+    fn apply(&self, grid: &mut Grid) {
+        pad(grid, [self.top, self.bottom, self.left, self.right], self.color);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        trim(grid, [self.top, self.bottom, self.left, self.right]);
+    }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::Trim(Trim {
+            top: self.top,
+            bottom: self.bottom,
+            left: self.left,
+            right: self.right,
+            color: self.color,
+        }))
+    }
+}
+
+/// Removes a border with the given width on each side, e.g. the border added by `Pad`. A border as wide as the grid
+/// leaves one row or column of it. Lossy; reverting adds a border of `color`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Trim {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+    pub color: u8,
+}
+
+impl GridTransform for Trim {
+    fn apply(&self, grid: &mut Grid) {
+        trim(grid, [self.top, self.bottom, self.left, self.right]);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        pad(grid, [self.top, self.bottom, self.left, self.right], self.color);
+    }
+}
+
+/// Adds a border of `color` with the `[top, bottom, left, right]` widths around `grid`
+fn pad(grid: &mut Grid, [top, bottom, left, right]: [usize; 4], color: u8) {
+    let (height, width) = grid.shape();
+    let mut padded = vec![vec![color; left + width + right]; top + height + bottom];
+    for y in 0..height {
+        for x in 0..width {
+            padded[top + y][left + x] = grid[(y, x)];
+        }
+    }
+    *grid = Grid::from_vec(padded);
+}
+
+/// Removes a border with the `[top, bottom, left, right]` widths from `grid`, keeping at least one row and column as
+/// grids cannot be empty
+fn trim(grid: &mut Grid, [top, bottom, left, right]: [usize; 4]) {
+    let (height, width) = grid.shape();
+    let data = trimmed(height, top, bottom)
+        .map(|y| trimmed(width, left, right).map(|x| grid[(y, x)]).collect())
+        .collect();
+    *grid = Grid::from_vec(data);
+}
+
+/// The indices of `0..len` without the first `before` and last `after`, or the one nearest to them if none are left
+fn trimmed(len: usize, before: usize, after: usize) -> Range<usize> {
+    let start = before.min(len.saturating_sub(1));
+    start..len.saturating_sub(after).max(start + 1).min(len)
+}
+
+/// Scales grids up by repeating each cell in a block of `height_factor` by `width_factor` cells. Reverting takes the
+/// most common color of each block, so it also reverts predictions that are not exactly blocks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UpscaleNearest {
+    pub height_factor: usize,
+    pub width_factor: usize,
+}
+
+impl GridTransform for UpscaleNearest {
+    fn apply(&self, grid: &mut Grid) {
+        upscale(grid, self.height_factor, self.width_factor);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        downscale(grid, self.height_factor, self.width_factor);
+    }
+
+    fn inverse(&self) -> Option<Transform> {
+        Some(Transform::Downscale(Downscale {
+            height_factor: self.height_factor,
+            width_factor: self.width_factor,
+        }))
+    }
+}
+
+/// Scales grids down by replacing each block of `height_factor` by `width_factor` cells with its most common color,
+/// with ties going to the lower color. Blocks at the bottom and right edges of grids whose size is not a multiple of
+/// the factors are smaller. Lossy; reverting repeats each cell in a block of the full size
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Downscale {
+    pub height_factor: usize,
+    pub width_factor: usize,
+}
+
+impl GridTransform for Downscale {
+    fn apply(&self, grid: &mut Grid) {
+        downscale(grid, self.height_factor, self.width_factor);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        upscale(grid, self.height_factor, self.width_factor);
+    }
+}
+
+/// Repeats grids `rows` times vertically and `cols` times horizontally. Reverting takes the most common color of
+/// each cell across the tiles
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl GridTransform for Tile {
+    fn apply(&self, grid: &mut Grid) {
+        let (height, width) = grid.shape();
+        let data = (0..height * self.rows)
+            .map(|y| (0..width * self.cols).map(|x| grid[(y % height, x % width)]).collect())
+            .collect();
+        *grid = Grid::from_vec(data);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        let (height, width) = grid.shape();
+        let (tile_height, tile_width) = ((height / self.rows).max(1), (width / self.cols).max(1));
+        let data = (0..tile_height)
+            .map(|y| {
+                (0..tile_width)
+                    .map(|x| {
+                        let tiles = (0..self.rows).flat_map(|i| (0..self.cols).map(move |j| (i, j)));
+                        let colors = tiles
+                            .map(|(i, j)| (y + i * tile_height, x + j * tile_width))
+                            .filter(|&(yi, xi)| yi < height && xi < width)
+                            .map(|(yi, xi)| grid[(yi, xi)]);
+                        most_common(colors)
+                    })
+                    .collect()
+            })
+            .collect();
+        *grid = Grid::from_vec(data);
+    }
+}
+
+/// Repeats each cell of `grid` in a block of `height_factor` by `width_factor` cells
+fn upscale(grid: &mut Grid, height_factor: usize, width_factor: usize) {
+    let (height, width) = grid.shape();
+    let data = (0..height * height_factor)
+        .map(|y| {
+            (0..width * width_factor)
+                .map(|x| grid[(y / height_factor, x / width_factor)])
+                .collect()
+        })
+        .collect();
+    *grid = Grid::from_vec(data);
+}
+
+/// Replaces each block of `height_factor` by `width_factor` cells of `grid` with its most common color
+fn downscale(grid: &mut Grid, height_factor: usize, width_factor: usize) {
+    let (height, width) = grid.shape();
+    let data = (0..height.div_ceil(height_factor))
+        .map(|y| {
+            (0..width.div_ceil(width_factor))
+                .map(|x| {
+                    let rows = y * height_factor..((y + 1) * height_factor).min(height);
+                    let cols = x * width_factor..((x + 1) * width_factor).min(width);
+                    let cells = &*grid;
+                    most_common(rows.flat_map(|yi| cols.clone().map(move |xi| cells[(yi, xi)])))
+                })
+                .collect()
+        })
+        .collect();
+    *grid = Grid::from_vec(data);
+}
+
+/// The most common of `colors`, with ties going to the lower color
+fn most_common(colors: impl Iterator<Item = u8>) -> u8 {
+    let mut counts = [0usize; 10];
+    for color in colors {
+        counts[color as usize] += 1;
+    }
+
+    // `max_by_key` returns the last maximum, so the colors are searched from the highest
+    (0..10u8).rev().max_by_key(|&color| counts[color as usize]).unwrap()
+}
+
+/// The transforms of a `TransformPipeline`. Serialized with the name of the variant, except for `Custom`, which holds
+/// transforms defined outside of this crate and cannot be saved with a model.
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Transform {
     Identity(Identity),
     Rotate90CW(Rotate90CW),
@@ -242,41 +621,200 @@ pub enum Transform {
     ReflectMainDiagonal(ReflectMainDiagonal),
     ReflectAntiDiagonal(ReflectAntiDiagonal),
     RemapColors(RemapColors),
+    CropToBoundingBox(CropToBoundingBox),
+    Pad(Pad),
+    Trim(Trim),
+    UpscaleNearest(UpscaleNearest),
+    Downscale(Downscale),
+    Tile(Tile),
+    #[serde(skip)]
+    Custom(Arc<dyn GridTransform>),
 }
 
 impl Transform {
-    pub fn apply(&self, grid: &mut Grid) {
+    /// The eight rotations and reflections of a grid (the dihedral group D4), starting with the identity
+    pub fn dihedral_group() -> [Transform; 8] {
+        [
+            Transform::Identity(Identity {}),
+            Transform::Rotate90CW(Rotate90CW {}),
+            Transform::Rotate180(Rotate180 {}),
+            Transform::Rotate270CW(Rotate270CW {}),
+            Transform::FlipHorizontal(FlipHorizontal {}),
+            Transform::FlipVertical(FlipVertical {}),
+            Transform::ReflectMainDiagonal(ReflectMainDiagonal {}),
+            Transform::ReflectAntiDiagonal(ReflectAntiDiagonal {}),
+        ]
+    }
+
+    /// Checks that the transform can run under every example of a training run, which `Config::validate` requires of
+    /// `Config::transforms`. Factors of zero and colors out of range fail on the first grid, `CropToBoundingBox` is made
+    /// for a single grid, `Trim` cuts grids smaller than its border down to a cell, and `Custom` cannot be saved with
+    /// the config and models.
+    pub fn validate_for_config(&self) -> Result<(), String> {
+        let check = |valid: bool, msg: &str| if valid { Ok(()) } else { Err(format!("{self:?}: {msg}")) };
+
         match self {
-            Transform::Identity(_) => {}
-            Transform::Rotate90CW(t) => t.apply(grid),
-            Transform::Rotate180(t) => t.apply(grid),
-            Transform::Rotate270CW(t) => t.apply(grid),
-            Transform::FlipHorizontal(t) => t.apply(grid),
-            Transform::FlipVertical(t) => t.apply(grid),
-            Transform::ReflectMainDiagonal(t) => t.apply(grid),
-            Transform::ReflectAntiDiagonal(t) => t.apply(grid),
-            Transform::RemapColors(t) => t.apply(grid),
+            Transform::RemapColors(t) => check(
+                t.col_map.iter().chain(&t.rev_col_map).all(|&c| c < 10),
+                "colors must be below 10",
+            ),
+            Transform::CropToBoundingBox(_) => check(false, "made for a single grid; not supported in the config"),
+            Transform::Pad(t) => check(t.color < 10, "color must be below 10"),
+            Transform::Trim(_) => check(
+                false,
+                "cuts small grids down to a cell; only used as the inverse of Pad",
+            ),
+            Transform::UpscaleNearest(UpscaleNearest {
+                height_factor,
+                width_factor,
+            })
+            | Transform::Downscale(Downscale {
+                height_factor,
+                width_factor,
+            }) => check(*height_factor > 0 && *width_factor > 0, "factors must be at least 1"),
+            Transform::Tile(t) => check(t.rows > 0 && t.cols > 0, "rows and cols must be at least 1"),
+            Transform::Custom(_) => check(false, "cannot be serialized; not supported in the config"),
+            _ => Ok(()),
         }
     }
 
-    pub fn revert(&self, grid: &mut Grid) {
+    fn inner(&self) -> &dyn GridTransform {
         match self {
-            Transform::Identity(_) => {}
-            Transform::Rotate90CW(t) => t.revert(grid),
-            Transform::Rotate180(t) => t.revert(grid),
-            Transform::Rotate270CW(t) => t.revert(grid),
-            Transform::FlipHorizontal(t) => t.revert(grid),
-            Transform::FlipVertical(t) => t.revert(grid),
-            Transform::ReflectMainDiagonal(t) => t.revert(grid),
-            Transform::ReflectAntiDiagonal(t) => t.revert(grid),
-            Transform::RemapColors(t) => t.revert(grid),
+            Transform::Identity(t) => t,
+            Transform::Rotate90CW(t) => t,
+            Transform::Rotate180(t) => t,
+            Transform::Rotate270CW(t) => t,
+            Transform::FlipHorizontal(t) => t,
+            Transform::FlipVertical(t) => t,
+            Transform::ReflectMainDiagonal(t) => t,
+            Transform::ReflectAntiDiagonal(t) => t,
+            Transform::RemapColors(t) => t,
+            Transform::CropToBoundingBox(t) => t,
+            Transform::Pad(t) => t,
+            Transform::Trim(t) => t,
+            Transform::UpscaleNearest(t) => t,
+            Transform::Downscale(t) => t,
+            Transform::Tile(t) => t,
+            Transform::Custom(t) => t.as_ref(),
+        }
+    }
+
+    /// Whether the transform leaves every grid unchanged
+    fn is_identity(&self) -> bool {
+        match self {
+            Transform::RemapColors(t) => t.is_identity(),
+            _ => Dihedral::of(self) == Some(Dihedral::IDENTITY),
+        }
+    }
+
+    /// A single transform equal to `self` followed by `next`, if there is one
+    fn merge(&self, next: &Transform) -> Option<Transform> {
+        match (self, next) {
+            (Transform::RemapColors(a), Transform::RemapColors(b)) => Some(Transform::RemapColors(a.then(b))),
+            // Removing the border that was just added. Adding back a removed border loses its cells
+            (Transform::Pad(a), Transform::Trim(b)) => {
+                let (a, b) = ([a.top, a.bottom, a.left, a.right], [b.top, b.bottom, b.left, b.right]);
+                (a == b).then_some(Transform::Identity(Identity {}))
+            }
+            _ => Some(Dihedral::of(self)?.then(Dihedral::of(next)?).to_transform()),
+        }
+    }
+}
+
+/// Custom transforms are equal if they are the same instance
+impl PartialEq for Transform {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Transform::RemapColors(a), Transform::RemapColors(b)) => a == b,
+            (Transform::CropToBoundingBox(a), Transform::CropToBoundingBox(b)) => a == b,
+            (Transform::Pad(a), Transform::Pad(b)) => a == b,
+            (Transform::Trim(a), Transform::Trim(b)) => a == b,
+            (Transform::UpscaleNearest(a), Transform::UpscaleNearest(b)) => a == b,
+            (Transform::Downscale(a), Transform::Downscale(b)) => a == b,
+            (Transform::Tile(a), Transform::Tile(b)) => a == b,
+            (Transform::Custom(a), Transform::Custom(b)) => Arc::ptr_eq(a, b),
+            // The other variants have no fields
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl GridTransform for Transform {
+    fn apply(&self, grid: &mut Grid) {
+        self.inner().apply(grid);
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        self.inner().revert(grid);
+    }
+
+    fn inverse(&self) -> Option<Transform> {
+        self.inner().inverse()
+    }
+}
+
+/// An element of the dihedral group D4: a horizontal flip if `flip` is set, followed by `quarter_turns` clockwise
+/// rotations by 90 degrees
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Dihedral {
+    quarter_turns: u8,
+    flip: bool,
+}
+
+impl Dihedral {
+    const IDENTITY: Dihedral = Dihedral {
+        quarter_turns: 0,
+        flip: false,
+    };
+
+    fn of(transform: &Transform) -> Option<Dihedral> {
+        let (quarter_turns, flip) = match transform {
+            Transform::Identity(_) => (0, false),
+            Transform::Rotate90CW(_) => (1, false),
+            Transform::Rotate180(_) => (2, false),
+            Transform::Rotate270CW(_) => (3, false),
+            Transform::FlipHorizontal(_) => (0, true),
+            Transform::ReflectAntiDiagonal(_) => (1, true),
+            Transform::FlipVertical(_) => (2, true),
+            Transform::ReflectMainDiagonal(_) => (3, true),
+            _ => return None,
+        };
+
+        Some(Dihedral { quarter_turns, flip })
+    }
+
+    /// `self` followed by `next`. A flip reverses the direction of the rotations before it
+    fn then(self, next: Dihedral) -> Dihedral {
+        let turns = if next.flip {
+            4 - self.quarter_turns
+        } else {
+            self.quarter_turns
+        };
+
+        Dihedral {
+            quarter_turns: (next.quarter_turns + turns) % 4,
+            flip: self.flip != next.flip,
+        }
+    }
+
+    fn to_transform(self) -> Transform {
+        match (self.quarter_turns, self.flip) {
+            (0, false) => Transform::Identity(Identity {}),
+            (1, false) => Transform::Rotate90CW(Rotate90CW {}),
+            (2, false) => Transform::Rotate180(Rotate180 {}),
+            (3, false) => Transform::Rotate270CW(Rotate270CW {}),
+            (0, true) => Transform::FlipHorizontal(FlipHorizontal {}),
+            (1, true) => Transform::ReflectAntiDiagonal(ReflectAntiDiagonal {}),
+            (2, true) => Transform::FlipVertical(FlipVertical {}),
+            (3, true) => Transform::ReflectMainDiagonal(ReflectMainDiagonal {}),
+            _ => unreachable!(),
         }
     }
 }
 
 /// List of transforms that are applied sequentially applied
 /// in order and sequentially reverted in reverse order.
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct TransformPipeline {
     pub steps: Vec<Transform>,
 }
@@ -293,4 +831,55 @@ impl TransformPipeline {
             transform.revert(grid);
         }
     }
+
+    /// The steps of `self` followed by the steps of `next`, simplified
+    pub fn compose(&self, next: &TransformPipeline) -> TransformPipeline {
+        TransformPipeline {
+            steps: [self.steps.clone(), next.steps.clone()].concat(),
+        }
+        .simplify()
+    }
+
+    /// The pipeline whose `apply` reverts `self`, simplified. None if a step has no inverse
+    pub fn inverse(&self) -> Option<TransformPipeline> {
+        let steps = self
+            .steps
+            .iter()
+            .rev()
+            .map(|step| step.inverse())
+            .collect::<Option<_>>()?;
+        Some(TransformPipeline { steps }.simplify())
+    }
+
+    /// An equivalent pipeline in which adjacent rotations and reflections are merged into one, as are adjacent color
+    /// remappings, and steps that change nothing are removed, as is a `Pad` followed by the `Trim` of its border. E.g.
+    /// four `Rotate90CW` steps simplify to no steps.
+    pub fn simplify(&self) -> TransformPipeline {
+        let mut steps: Vec<Transform> = Vec::with_capacity(self.steps.len());
+
+        for step in &self.steps {
+            // Adjacent steps in `steps` never merge, so a step merges with at most the last one
+            let step = match steps.last().and_then(|last| last.merge(step)) {
+                Some(merged) => {
+                    steps.pop();
+                    merged
+                }
+                None => step.clone(),
+            };
+
+            if !step.is_identity() {
+                steps.push(step);
+            }
+        }
+
+        TransformPipeline { steps }
+    }
+
+    /// Whether reverting the pipeline after applying it restores `grid`, which fails for lossy steps
+    pub fn round_trips(&self, grid: &Grid) -> bool {
+        let mut transformed = grid.clone();
+        self.apply(&mut transformed);
+        self.revert(&mut transformed);
+        transformed.data() == grid.data()
+    }
 }
@@ -26,7 +26,7 @@ pub fn vote(grid: &Grid, ncas: &Vec<NCA>, k: usize, verbose: bool, backend: Back
         return ncas.clone().into_iter().take(k).collect_vec();
     }
 
-    entries.sort_by(|a, b| b.1.1.cmp(&a.1.1));
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.1.1));
 
     if verbose {
         let hash_counts = entries
@@ -0,0 +1,413 @@
+//! Property tests of the grid transforms on random grids
+
+use std::sync::Arc;
+
+use enca::grid::Grid;
+use enca::transforms::{
+    CropToBoundingBox, Downscale, GridTransform, Pad, RemapColors, Rotate90CW, Tile, Transform, TransformPipeline,
+    Trim, UpscaleNearest,
+};
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const CASES: usize = 200;
+
+/// A grid of random size, mostly non-square, with random colors
+fn random_grid(rng: &mut impl Rng) -> Grid {
+    let height = rng.random_range(1..=12);
+    let width = rng.random_range(1..=12);
+    Grid::from_vec(
+        (0..height)
+            .map(|_| (0..width).map(|_| rng.random_range(0..10)).collect())
+            .collect(),
+    )
+}
+
+/// A permutation of all colors
+fn random_permutation(rng: &mut impl Rng) -> RemapColors {
+    let mut colors = (0..10).collect::<Vec<u8>>();
+    colors.shuffle(rng);
+
+    let mut permutation = RemapColors::new();
+    for (color, mapped) in (0..10).zip(colors) {
+        permutation.map(color, mapped);
+    }
+    permutation
+}
+
+/// The rotations and reflections and a random color permutation
+fn invertible_transforms(rng: &mut impl Rng) -> Vec<Transform> {
+    let mut transforms = Transform::dihedral_group().to_vec();
+    transforms.push(Transform::RemapColors(random_permutation(rng)));
+    transforms
+}
+
+fn random_pad(rng: &mut impl Rng) -> Transform {
+    Transform::Pad(Pad {
+        top: rng.random_range(0..4),
+        bottom: rng.random_range(0..4),
+        left: rng.random_range(0..4),
+        right: rng.random_range(0..4),
+        color: rng.random_range(0..10),
+    })
+}
+
+/// A padding, upscaling and tiling with random sizes
+fn resizing_transforms(rng: &mut impl Rng) -> Vec<Transform> {
+    vec![
+        random_pad(rng),
+        Transform::UpscaleNearest(UpscaleNearest {
+            height_factor: rng.random_range(1..4),
+            width_factor: rng.random_range(1..4),
+        }),
+        Transform::Tile(Tile {
+            rows: rng.random_range(1..4),
+            cols: rng.random_range(1..4),
+        }),
+    ]
+}
+
+fn random_pipeline(rng: &mut impl Rng) -> TransformPipeline {
+    let len = rng.random_range(0..8);
+    let steps = (0..len)
+        .map(|_| invertible_transforms(rng).choose(rng).unwrap().clone())
+        .collect();
+    TransformPipeline { steps }
+}
+
+fn applied(pipeline: &TransformPipeline, grid: &Grid) -> Vec<Vec<u8>> {
+    applied_grid(pipeline, grid).data().clone()
+}
+
+fn applied_grid(pipeline: &TransformPipeline, grid: &Grid) -> Grid {
+    let mut grid = grid.clone();
+    pipeline.apply(&mut grid);
+    grid
+}
+
+/// Adds one to every color, as a transform defined outside of the crate
+#[derive(Debug)]
+struct ShiftColors;
+
+impl GridTransform for ShiftColors {
+    fn apply(&self, grid: &mut Grid) {
+        *grid = Grid::from_vec(
+            grid.data()
+                .iter()
+                .map(|row| row.iter().map(|c| (c + 1) % 10).collect())
+                .collect(),
+        );
+    }
+
+    fn revert(&self, grid: &mut Grid) {
+        *grid = Grid::from_vec(
+            grid.data()
+                .iter()
+                .map(|row| row.iter().map(|c| (c + 9) % 10).collect())
+                .collect(),
+        );
+    }
+}
+
+#[test]
+fn test_revert_apply() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+    for _ in 0..CASES {
+        let grid = random_grid(&mut rng);
+        for transform in invertible_transforms(&mut rng) {
+            let pipeline = TransformPipeline {
+                steps: vec![transform.clone()],
+            };
+            assert!(pipeline.round_trips(&grid), "{transform:?}");
+        }
+    }
+}
+
+#[test]
+fn test_inverse() {
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+
+    for _ in 0..CASES {
+        let grid = random_grid(&mut rng);
+        for transform in invertible_transforms(&mut rng) {
+            let inverse = transform.inverse().unwrap();
+            let mut transformed = grid.clone();
+            transform.apply(&mut transformed);
+            inverse.apply(&mut transformed);
+            assert_eq!(grid.data(), transformed.data(), "{transform:?}");
+        }
+    }
+}
+
+#[test]
+fn test_simplify_keeps_result() {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+    for _ in 0..CASES {
+        let grid = random_grid(&mut rng);
+        let pipeline = random_pipeline(&mut rng);
+        let simplified = pipeline.simplify();
+
+        assert_eq!(
+            applied(&pipeline, &grid),
+            applied(&simplified, &grid),
+            "{:?}",
+            pipeline.steps
+        );
+        assert!(simplified.steps.len() <= pipeline.steps.len());
+        // Rotations and reflections alternate with color remappings
+        assert!(
+            simplified.steps.windows(2).all(
+                |pair| matches!(pair[0], Transform::RemapColors(_)) != matches!(pair[1], Transform::RemapColors(_))
+            ),
+            "{:?}",
+            simplified.steps
+        );
+    }
+}
+
+#[test]
+fn test_simplify_rotations() {
+    let rotate = Transform::Rotate90CW(Rotate90CW {});
+    let pipeline = TransformPipeline {
+        steps: vec![rotate.clone(); 4],
+    };
+    assert!(pipeline.simplify().steps.is_empty());
+
+    let pipeline = TransformPipeline { steps: vec![rotate; 3] };
+    let simplified = pipeline.simplify();
+    assert!(matches!(simplified.steps[..], [Transform::Rotate270CW(_)]));
+}
+
+#[test]
+fn test_compose_inverse() {
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+
+    for _ in 0..CASES {
+        let grid = random_grid(&mut rng);
+        let mut pipeline = random_pipeline(&mut rng);
+        let n_pads = rng.random_range(0..3);
+        for _ in 0..n_pads {
+            let idx = rng.random_range(0..=pipeline.steps.len());
+            pipeline.steps.insert(idx, random_pad(&mut rng));
+        }
+        let inverse = pipeline.inverse().unwrap();
+
+        assert!(pipeline.compose(&inverse).steps.is_empty(), "{:?}", pipeline.steps);
+        assert_eq!(grid.data(), &applied(&pipeline.compose(&inverse), &grid));
+        assert_eq!(grid.data(), &applied(&inverse, &applied_grid(&pipeline, &grid)));
+        if n_pads == 0 {
+            assert!(inverse.compose(&pipeline).steps.is_empty(), "{:?}", pipeline.steps);
+        } else {
+            // Trimming loses the border cells, so padding does not revert it
+            assert!(inverse.inverse().is_none(), "{:?}", pipeline.steps);
+        }
+
+        let mut reverted = grid.clone();
+        pipeline.apply(&mut reverted);
+        inverse.revert(&mut reverted);
+        pipeline.revert(&mut reverted);
+        inverse.apply(&mut reverted);
+        assert_eq!(grid.data(), reverted.data());
+    }
+}
+
+#[test]
+fn test_lossy_remap_has_no_inverse() {
+    // Color 3 becomes 5 while 5 stays 5, so the colors cannot be told apart afterwards
+    let mut remap = RemapColors::new();
+    remap.map(3, 5);
+    let pipeline = TransformPipeline {
+        steps: vec![Transform::RemapColors(remap)],
+    };
+
+    assert!(pipeline.inverse().is_none());
+    assert!(!pipeline.round_trips(&Grid::from_vec(vec![vec![3, 5]])));
+}
+
+#[test]
+fn test_custom_transform() {
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    let custom = Transform::Custom(Arc::new(ShiftColors));
+    let rotate = Transform::Rotate90CW(Rotate90CW {});
+    let pipeline = TransformPipeline {
+        steps: vec![rotate.clone(), custom, rotate],
+    };
+
+    for _ in 0..CASES {
+        assert!(pipeline.round_trips(&random_grid(&mut rng)));
+    }
+
+    // The rotations on either side of the custom transform are not merged
+    assert_eq!(3, pipeline.simplify().steps.len());
+    assert!(pipeline.inverse().is_none());
+    assert!(serde_json::to_string(&pipeline).is_err());
+}
+
+#[test]
+fn test_resizing_revert_apply() {
+    let mut rng = ChaCha8Rng::seed_from_u64(6);
+
+    for _ in 0..CASES {
+        let grid = random_grid(&mut rng);
+        let mut steps = resizing_transforms(&mut rng);
+        steps.shuffle(&mut rng);
+        steps.push(invertible_transforms(&mut rng).choose(&mut rng).unwrap().clone());
+        let pipeline = TransformPipeline { steps };
+
+        assert!(pipeline.round_trips(&grid), "{:?}", pipeline.steps);
+        for transform in &pipeline.steps {
+            let single = TransformPipeline {
+                steps: vec![transform.clone()],
+            };
+            assert!(single.round_trips(&grid), "{transform:?}");
+        }
+    }
+}
+
+#[test]
+fn test_resizing_shapes() {
+    let grid = Grid::from_vec(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+    let shape = |transform: Transform| {
+        let mut grid = grid.clone();
+        transform.apply(&mut grid);
+        grid.shape()
+    };
+
+    let pad = Pad {
+        top: 1,
+        bottom: 0,
+        left: 2,
+        right: 3,
+        color: 0,
+    };
+    assert_eq!((3, 8), shape(Transform::Pad(pad)));
+    let trim = Trim {
+        top: 1,
+        bottom: 0,
+        left: 0,
+        right: 1,
+        color: 0,
+    };
+    assert_eq!((1, 2), shape(Transform::Trim(trim)));
+    let upscale = UpscaleNearest {
+        height_factor: 2,
+        width_factor: 3,
+    };
+    assert_eq!((4, 9), shape(Transform::UpscaleNearest(upscale)));
+    assert_eq!((6, 6), shape(Transform::Tile(Tile { rows: 3, cols: 2 })));
+    // Partial blocks at the edges are kept
+    let downscale = Downscale {
+        height_factor: 2,
+        width_factor: 2,
+    };
+    assert_eq!((1, 2), shape(Transform::Downscale(downscale)));
+}
+
+#[test]
+fn test_border_of_grid_size() {
+    let grid = Grid::from_vec(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+
+    // At least one row and column is left
+    let trim = Trim {
+        top: 2,
+        bottom: 1,
+        left: 1,
+        right: 5,
+        color: 0,
+    };
+    let mut trimmed = grid.clone();
+    trim.apply(&mut trimmed);
+    assert_eq!(vec![vec![5]], *trimmed.data());
+
+    // Reverting `Pad` on a prediction smaller than the padded grid
+    let pad = Pad {
+        top: 3,
+        bottom: 3,
+        left: 3,
+        right: 3,
+        color: 0,
+    };
+    let mut prediction = grid.clone();
+    pad.revert(&mut prediction);
+    assert_eq!((1, 1), prediction.shape());
+
+    let crop = CropToBoundingBox::new(&Grid::from_vec(vec![vec![]]), 0);
+    assert_eq!((0, 0, 1, 1), (crop.y, crop.x, crop.height, crop.width));
+}
+
+#[test]
+fn test_crop_to_bounding_box() {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+    for _ in 0..CASES {
+        // A random object on a background
+        let (height, width) = (rng.random_range(1..=12), rng.random_range(1..=12));
+        let background = rng.random_range(0..10);
+        let mut data = vec![vec![background; width]; height];
+        let (y, x) = (rng.random_range(0..height), rng.random_range(0..width));
+        for row in &mut data[y..rng.random_range(y..height) + 1] {
+            for cell in &mut row[x..rng.random_range(x..width) + 1] {
+                *cell = rng.random_range(0..10);
+            }
+        }
+        let grid = Grid::from_vec(data);
+
+        let crop = CropToBoundingBox::new(&grid, background);
+        let mut cropped = grid.clone();
+        crop.apply(&mut cropped);
+        assert_eq!((crop.height, crop.width), cropped.shape());
+        assert!(cropped.shape() == grid.shape() || cropped.data().iter().flatten().any(|&c| c != background));
+
+        let pipeline = TransformPipeline {
+            steps: vec![Transform::CropToBoundingBox(crop)],
+        };
+        assert!(pipeline.round_trips(&grid));
+        assert!(pipeline.inverse().is_none());
+    }
+}
+
+#[test]
+fn test_downscale_upscaled() {
+    let mut rng = ChaCha8Rng::seed_from_u64(8);
+
+    for _ in 0..CASES {
+        let grid = random_grid(&mut rng);
+        let upscale = Transform::UpscaleNearest(UpscaleNearest {
+            height_factor: rng.random_range(1..4),
+            width_factor: rng.random_range(1..4),
+        });
+        let inverse = upscale.inverse().unwrap();
+
+        let mut scaled = grid.clone();
+        upscale.apply(&mut scaled);
+        inverse.apply(&mut scaled);
+        assert_eq!(grid.data(), scaled.data(), "{upscale:?}");
+
+        // Downscaling loses the cells that differ from the rest of their block
+        let pipeline = TransformPipeline { steps: vec![inverse] };
+        assert!(pipeline.inverse().is_none());
+    }
+
+    // Ties go to the lower color
+    let mut grid = Grid::from_vec(vec![vec![7, 2], vec![2, 7]]);
+    Downscale {
+        height_factor: 2,
+        width_factor: 2,
+    }
+    .apply(&mut grid);
+    assert_eq!(&vec![vec![2]], grid.data());
+}
+
+#[test]
+fn test_serialize_resizing() {
+    let mut rng = ChaCha8Rng::seed_from_u64(9);
+    let pipeline = TransformPipeline {
+        steps: resizing_transforms(&mut rng),
+    };
+
+    let json = serde_json::to_string(&pipeline).unwrap();
+    assert_eq!(pipeline, serde_json::from_str(&json).unwrap());
+}